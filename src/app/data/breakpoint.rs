@@ -0,0 +1,179 @@
+use std::fmt;
+
+use super::*;
+
+/// What a breakpoint watches: a single wire, input, output or gate by id, or a pin/bus by name
+#[derive(Debug, Clone, PartialEq)]
+pub enum BreakTarget {
+    Signal(usize),
+    Pin(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BreakCondition {
+    Rises,
+    Falls,
+    Changes,
+    Equals(u64),
+}
+
+impl BreakCondition {
+    pub const NAMES: [&'static str; 4] = ["rises", "falls", "changes", "=="];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BreakCondition::Rises => Self::NAMES[0],
+            BreakCondition::Falls => Self::NAMES[1],
+            BreakCondition::Changes => Self::NAMES[2],
+            BreakCondition::Equals(_) => Self::NAMES[3],
+        }
+    }
+
+    /// Whether going from `last` to `value` triggers this condition
+    /// Equals only triggers when the value becomes equal, not on every tick it stays equal
+    fn is_met(&self, last: Option<u64>, value: u64) -> bool {
+        let Some(last) = last else {
+            return false;
+        };
+        match self {
+            BreakCondition::Rises => last == 0 && value != 0,
+            BreakCondition::Falls => last != 0 && value == 0,
+            BreakCondition::Changes => last != value,
+            BreakCondition::Equals(expected) => last != *expected && value == *expected,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Breakpoint {
+    pub target: BreakTarget,
+    pub condition: BreakCondition,
+    pub enabled: bool,
+    pub hits: usize,
+    last_value: Option<u64>,
+}
+
+impl Breakpoint {
+    pub fn new(target: BreakTarget, condition: BreakCondition) -> Self {
+        Breakpoint {
+            target,
+            condition,
+            enabled: true,
+            hits: 0,
+            last_value: None,
+        }
+    }
+
+    /// The current value of the target, None if it no longer exists
+    pub fn value(&self, data: &Data) -> Option<u64> {
+        match &self.target {
+            BreakTarget::Signal(id) => signal_value(*id, &data.live_data).map(|s| s as u64),
+            BreakTarget::Pin(name) => data.read_pin(name).ok(),
+        }
+    }
+
+    /// The ids of everything on the board this breakpoint is about
+    pub fn component_ids(&self, data: &Data) -> Vec<usize> {
+        match &self.target {
+            BreakTarget::Signal(id) => vec![*id],
+            BreakTarget::Pin(name) => data.find_pin(name),
+        }
+    }
+
+    pub fn describe(&self, live_data: &HashMap<usize, Box<dyn Logical>>) -> String {
+        let target = match &self.target {
+            BreakTarget::Signal(id) => signal_name(*id, live_data).unwrap_or_else(|| format!("#{} (removed)", id)),
+            BreakTarget::Pin(name) => name.clone(),
+        };
+        format!("{} {}", target, self)
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.condition {
+            BreakCondition::Equals(value) => write!(f, "== 0x{:X}", value),
+            condition => write!(f, "{}", condition.name()),
+        }
+    }
+}
+
+/// Parses a breakpoint value written as `0x2A`, `0b101010` or `42`
+pub fn parse_break_value(text: &str) -> Result<u64, Box<dyn Error>> {
+    let text = text.trim();
+    let parsed = if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16)
+    } else if let Some(bits) = text.strip_prefix("0b").or_else(|| text.strip_prefix("0B")) {
+        u64::from_str_radix(bits, 2)
+    } else {
+        text.parse::<u64>()
+    };
+    parsed.map_err(|_| format!("Invalid value {}", text).into())
+}
+
+/// The breakpoint that paused the simulation, and on which tick
+#[derive(Debug, Clone)]
+pub struct BreakHit {
+    pub index: usize,
+    pub tick: u64,
+    pub component_ids: Vec<usize>,
+}
+
+impl Data {
+    /// Adds a breakpoint on a target given as an id or a pin/bus name
+    pub fn add_breakpoint(&mut self, target: &str, condition: BreakCondition) -> Result<usize, Box<dyn Error>> {
+        let target = target.trim();
+        let target = match target.parse::<usize>() {
+            Ok(id) if self.live_data.contains_key(&id) => BreakTarget::Signal(id),
+            _ if !self.find_pin(target).is_empty() => BreakTarget::Pin(target.to_string()),
+            _ => return Err(format!("No component or pin named {} on this board", target).into()),
+        };
+
+        let mut breakpoint = Breakpoint::new(target, condition);
+        breakpoint.last_value = breakpoint.value(self);
+        self.breakpoints.push(breakpoint);
+        Ok(self.breakpoints.len() - 1)
+    }
+
+    pub fn remove_breakpoint(&mut self, index: usize) {
+        self.breakpoints.remove(index);
+        self.break_hit = None;
+    }
+
+    /// Checks every enabled breakpoint against the signals of the last tick
+    /// Returns true if one triggered, the first one is kept in break_hit
+    pub fn check_breakpoints(&mut self) -> bool {
+        let mut hit = None;
+        for index in 0..self.breakpoints.len() {
+            let value = self.breakpoints[index].value(self);
+            let breakpoint = &mut self.breakpoints[index];
+            let last = std::mem::replace(&mut breakpoint.last_value, value);
+            if !breakpoint.enabled {
+                continue;
+            }
+            let Some(value) = value else {
+                continue;
+            };
+            if breakpoint.condition.is_met(last, value) {
+                breakpoint.hits += 1;
+                hit.get_or_insert(index);
+            }
+        }
+
+        let Some(index) = hit else {
+            return false;
+        };
+        let component_ids = self.breakpoints[index].component_ids(self);
+        self.break_hit = Some(BreakHit {
+            index,
+            tick: self.sim.total_ticks,
+            component_ids,
+        });
+        true
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+        self.break_hit = None;
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use super::*;
+
+/// Outputs driving more wires than this are reported
+pub const MAX_FAN_OUT: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProblemKind {
+    UnconnectedInput,
+    UnusedOutput,
+    DanglingWire,
+    DeadGate,
+    DuplicatePinName,
+    ExcessiveFanOut,
+    FeedbackLoop,
+}
+
+impl ProblemKind {
+    /// Findings that are often intended, like the loops in latches, they are listed but don't fail a check
+    pub fn is_warning(&self) -> bool {
+        matches!(self, ProblemKind::FeedbackLoop)
+    }
+}
+
+impl fmt::Display for ProblemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ProblemKind::UnconnectedInput => "Unconnected input",
+            ProblemKind::UnusedOutput => "Output drives nothing",
+            ProblemKind::DanglingWire => "Dangling wire",
+            ProblemKind::DeadGate => "Dead logic",
+            ProblemKind::DuplicatePinName => "Duplicate pin name",
+            ProblemKind::ExcessiveFanOut => "Excessive fan-out",
+            ProblemKind::FeedbackLoop => "Feedback loop",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// One finding of the design check, ids are the components involved
+#[derive(Debug, Clone)]
+pub struct Problem {
+    pub kind: ProblemKind,
+    pub message: String,
+    pub ids: Vec<usize>,
+}
+
+impl Problem {
+    fn new(kind: ProblemKind, message: String, ids: Vec<usize>) -> Self {
+        Problem { kind, message, ids }
+    }
+
+    /// World position of the first component involved that has one
+    pub fn position(&self, live_data: &HashMap<usize, Box<dyn Logical>>) -> Option<Pos2> {
+        self.ids.iter().find_map(|id| component_position(*id, live_data))
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind, self.message)
+    }
+}
+
+/// World position of a gate, input, output or the start of a wire
+pub fn component_position(id: usize, live_data: &HashMap<usize, Box<dyn Logical>>) -> Option<Pos2> {
+    let any = live_data.get(&id)?.as_any();
+    if let Some(gate) = any.downcast_ref::<Gate>() {
+        Some(gate.position.to_pos2())
+    } else if let Some(input) = any.downcast_ref::<Input>() {
+        input.get_position(live_data).ok()
+    } else if let Some(output) = any.downcast_ref::<Output>() {
+        output.get_position(live_data).ok()
+    } else if let Some(wire) = any.downcast_ref::<Wire>() {
+        component_position(wire.source_id, live_data)
+    } else {
+        None
+    }
+}
+
+impl Data {
+    /// Runs every design rule over the board
+    pub fn check_design(&self) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        let name = |id: usize| signal_name(id, &self.live_data).unwrap_or_else(|| id.to_string());
+
+        let mut items: Vec<(&usize, &Box<dyn Logical>)> = self.live_data.iter().collect();
+        items.sort_by_key(|(id, _)| **id);
+
+        let mut pin_names: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (id, item) in &items {
+            let any = item.as_any();
+            if let Some(input) = any.downcast_ref::<Input>() {
+                if input.source_wire_id.is_none() {
+                    problems.push(Problem::new(
+                        ProblemKind::UnconnectedInput,
+                        format!("{} has no wire and always reads 0", name(**id)),
+                        vec![**id],
+                    ));
+                }
+            } else if let Some(output) = any.downcast_ref::<Output>() {
+                if output.out_wire_ids.is_empty() {
+                    problems.push(Problem::new(
+                        ProblemKind::UnusedOutput,
+                        format!("{} is not connected to anything", name(**id)),
+                        vec![**id],
+                    ));
+                } else if output.out_wire_ids.len() > MAX_FAN_OUT {
+                    problems.push(Problem::new(
+                        ProblemKind::ExcessiveFanOut,
+                        format!(
+                            "{} drives {} wires, more than {}",
+                            name(**id),
+                            output.out_wire_ids.len(),
+                            MAX_FAN_OUT
+                        ),
+                        vec![**id],
+                    ));
+                }
+            } else if let Some(wire) = any.downcast_ref::<Wire>() {
+                if wire.dest.is_none() {
+                    problems.push(Problem::new(
+                        ProblemKind::DanglingWire,
+                        format!("{} does not end at an input", name(**id)),
+                        vec![**id],
+                    ));
+                }
+            } else if let Some(gate) = any.downcast_ref::<Gate>()
+                && matches!(
+                    gate.kind,
+                    GateKind::Primitive(PrimitiveKind::TOGGLE) | GateKind::Primitive(PrimitiveKind::LIGHT)
+                )
+            {
+                pin_names.entry(gate.name.as_str()).or_default().push(gate.id);
+            }
+        }
+
+        let mut duplicates: Vec<(&str, Vec<usize>)> =
+            pin_names.into_iter().filter(|(_, ids)| ids.len() > 1).collect();
+        duplicates.sort();
+        for (pin, ids) in duplicates {
+            problems.push(Problem::new(
+                ProblemKind::DuplicatePinName,
+                format!("{} pins are named {}", ids.len(), pin),
+                ids,
+            ));
+        }
+
+        problems.extend(self.dead_gates().into_iter().map(|id| {
+            Problem::new(
+                ProblemKind::DeadGate,
+                format!("{} has no path to any LIGHT", name(id)),
+                vec![id],
+            )
+        }));
+
+        for feedback_loop in feedback_loops(&self.live_data) {
+            problems.push(Problem::new(
+                ProblemKind::FeedbackLoop,
+                format!(
+                    "{} gate(s) feed back into themselves, intended for latches but easy to do by accident",
+                    feedback_loop.gates.len()
+                ),
+                feedback_loop.ids().collect(),
+            ));
+        }
+
+        problems
+    }
+
+    /// Gates whose output never reaches a LIGHT, empty if the board has no LIGHT at all
+    fn dead_gates(&self) -> Vec<usize> {
+        let gates: Vec<&Gate> = self
+            .live_data
+            .values()
+            .filter_map(|item| item.as_any().downcast_ref::<Gate>())
+            .collect();
+        let mut live: Vec<usize> = gates
+            .iter()
+            .filter(|gate| gate.kind == GateKind::Primitive(PrimitiveKind::LIGHT))
+            .map(|gate| gate.id)
+            .collect();
+        if live.is_empty() {
+            return Vec::new();
+        }
+
+        let mut predecessors: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (from, to, _) in gate_edges(&self.live_data) {
+            predecessors.entry(to).or_default().push(from);
+        }
+        let mut reached: HashSet<usize> = live.iter().copied().collect();
+        while let Some(gate) = live.pop() {
+            for &from in predecessors.get(&gate).into_iter().flatten() {
+                if reached.insert(from) {
+                    live.push(from);
+                }
+            }
+        }
+
+        let mut dead: Vec<usize> = gates
+            .iter()
+            .map(|gate| gate.id)
+            .filter(|id| !reached.contains(id))
+            .collect();
+        dead.sort();
+        dead
+    }
+}
@@ -0,0 +1,474 @@
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+
+use super::*;
+
+/// Input values under which two chips disagree, with what each of them outputs
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Counterexample {
+    pub inputs: Vec<(String, u64)>,
+    pub outputs_a: Vec<(String, u64)>,
+    pub outputs_b: Vec<(String, u64)>,
+}
+
+impl Display for Counterexample {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let list = |values: &[(String, u64)]| {
+            values.iter().map(|(name, value)| format!("{}={}", name, value)).collect::<Vec<_>>().join(" ")
+        };
+        write!(
+            f,
+            "{}: the first gives {}, the second gives {}",
+            list(&self.inputs),
+            list(&self.outputs_a),
+            list(&self.outputs_b)
+        )
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub enum Equivalence {
+    Equivalent,
+    Different(Counterexample),
+}
+
+/// One bit of a pin: its name without the bus index, and the index
+type PinBit = (String, usize);
+
+/// Pin bits with the gate id of each
+type PinGates = Vec<(PinBit, usize)>;
+
+/// Boards with at most this many input bits are compared by simulating every combination of them,
+/// larger ones are handed to the SAT solver
+pub const MAX_EXHAUSTIVE_BITS: usize = 12;
+
+impl Data {
+    /// Proves that this board and `other` settle to the same outputs for every input, or finds inputs where they don't
+    /// TOGGLEs are matched to TOGGLEs and LIGHTs to LIGHTs by name and bus bit, no two of them may share one;
+    /// both boards must be flattened and free of feedback loops
+    /// An input that only one board has is still shared, the other board simply ignores it
+    /// Small boards are simulated for every input, 64 inputs at a time; larger ones are encoded into one
+    /// SAT problem (a miter) that is satisfiable only if some output differs
+    pub fn check_equivalence(&self, other: &Data) -> Result<Equivalence, CircuitError> {
+        let (ins_a, outs_a) = self.pin_bits()?;
+        let (ins_b, outs_b) = other.pin_bits()?;
+
+        let names = |outs: &[(PinBit, usize)]| outs.iter().map(|(bit, _)| bit.clone()).collect::<HashSet<PinBit>>();
+        let (names_a, names_b) = (names(&outs_a), names(&outs_b));
+        if names_a != names_b {
+            // a whole pin missing is shown by its name, a bus of another width by the bits that differ
+            let only = |a: &HashSet<PinBit>, b: &HashSet<PinBit>| {
+                let mut only: Vec<String> = a
+                    .difference(b)
+                    .map(|(name, bit)| match b.iter().any(|(other, _)| other == name) {
+                        true => format!("{}[{}]", name, bit),
+                        false => name.clone(),
+                    })
+                    .collect();
+                only.sort();
+                only.dedup();
+                only.join(", ")
+            };
+            return Err(CircuitError::Unsupported {
+                id: None,
+                message: format!(
+                    "Output pins don't match, only on the first: [{}], only on the second: [{}]",
+                    only(&names_a, &names_b),
+                    only(&names_b, &names_a)
+                ),
+            });
+        }
+
+        let (gates_a, gates_b) = (self.combinational_gates()?, other.combinational_gates()?);
+        let mut inputs: Vec<PinBit> = ins_a.iter().chain(&ins_b).map(|(bit, _)| bit.clone()).collect();
+        inputs.sort();
+        inputs.dedup();
+        let pins = [(&ins_a, &outs_a), (&ins_b, &outs_b)];
+        if inputs.len() <= MAX_EXHAUSTIVE_BITS {
+            return Ok(self.sweep_equivalence(other, &inputs, pins));
+        }
+        Ok(self.miter(other, &inputs, [&gates_a, &gates_b], pins))
+    }
+
+    /// Encodes both boards into one SAT problem that is satisfiable only if some output differs,
+    /// a solution is a counterexample; `gates` and `pins` are those of each board
+    fn miter(
+        &self,
+        other: &Data,
+        inputs: &[PinBit],
+        gates: [&[(usize, PrimitiveKind)]; 2],
+        pins: [(&PinGates, &PinGates); 2],
+    ) -> Equivalence {
+        let [(ins_a, outs_a), (ins_b, outs_b)] = pins;
+        let mut solver = SatSolver::new();
+        let truth = Lit::new(solver.new_var(), false);
+        solver.add_clause(&[truth]);
+        let input_lits: HashMap<PinBit, Lit> =
+            inputs.iter().map(|bit| (bit.clone(), Lit::new(solver.new_var(), false))).collect();
+
+        let lights_a = self.encode(&mut solver, truth, gates[0], ins_a, &input_lits);
+        let lights_b = other.encode(&mut solver, truth, gates[1], ins_b, &input_lits);
+        let by_bit = |outs: &[(PinBit, usize)], lights: &HashMap<usize, Lit>| {
+            outs.iter().map(|(bit, gate)| (bit.clone(), lights[gate])).collect::<HashMap<PinBit, Lit>>()
+        };
+        let (out_lits_a, out_lits_b) = (by_bit(outs_a, &lights_a), by_bit(outs_b, &lights_b));
+
+        // the miter: true if any pair of outputs differs
+        let differences: Vec<Lit> = out_lits_a.iter().map(|(bit, a)| xor(&mut solver, *a, out_lits_b[bit])).collect();
+        solver.add_clause(&differences);
+        let Some(model) = solver.solve() else {
+            return Equivalence::Equivalent;
+        };
+
+        let values = |lits: &HashMap<PinBit, Lit>| {
+            lits.iter().map(|(bit, lit)| (bit.clone(), model[lit.var()] != lit.negated())).collect()
+        };
+        Equivalence::Different(Counterexample {
+            inputs: pin_values(values(&input_lits)),
+            outputs_a: pin_values(values(&out_lits_a)),
+            outputs_b: pin_values(values(&out_lits_b)),
+        })
+    }
+
+    /// Simulates both boards for every combination of `inputs`, and returns the first one they disagree on
+    /// Bit k of a combination is `inputs[k]`, `pins` are the input and output pins of each board
+    fn sweep_equivalence(&self, other: &Data, inputs: &[PinBit], pins: [(&PinGates, &PinGates); 2]) -> Equivalence {
+        let mut outputs: Vec<PinBit> = pins[0].1.iter().map(|(bit, _)| bit.clone()).collect();
+        outputs.sort();
+        let boards = [self, other].map(|board| Netlist::compile(&board.live_data));
+        let boards: Vec<_> = boards
+            .into_iter()
+            .zip(pins)
+            .map(|(netlist, (ins, outs))| {
+                // a combinational board settles once the signals went through every gate
+                let max_ticks = netlist.gates.len() + 1;
+                let in_gates: Vec<(usize, usize)> = ins
+                    .iter()
+                    .map(|(bit, gate)| (inputs.binary_search(bit).expect("every input bit is listed"), *gate))
+                    .collect();
+                let by_bit: HashMap<&PinBit, usize> = outs.iter().map(|(bit, gate)| (bit, *gate)).collect();
+                let out_gates = vec![outputs.iter().map(|bit| by_bit[bit]).collect::<Vec<usize>>()];
+                (VectorSim::new(netlist), in_gates, out_gates, max_ticks)
+            })
+            .collect();
+
+        for (first, lanes) in sweep_blocks(1 << inputs.len()) {
+            let [a, b] = [&boards[0], &boards[1]].map(|(sim, ins, outs, max_ticks)| {
+                let (mut states, _) = sim.sweep_block(first, lanes, ins, outs, *max_ticks);
+                states.pop().expect("one output list")
+            });
+            let differ = a.iter().zip(&b).fold(0, |acc, (a, b)| acc | (a ^ b)) & lanes;
+            if differ == 0 {
+                continue;
+            }
+            let lane = differ.trailing_zeros() as usize;
+            let combination = first + lane as u64;
+            let named = |states: &[u64]| {
+                outputs.iter().cloned().zip(states.iter().map(|state| (state >> lane) & 1 == 1)).collect()
+            };
+            let input_values = inputs.iter().enumerate().map(|(k, bit)| (bit.clone(), (combination >> k) & 1 == 1));
+            return Equivalence::Different(Counterexample {
+                inputs: pin_values(input_values.collect()),
+                outputs_a: pin_values(named(&a)),
+                outputs_b: pin_values(named(&b)),
+            });
+        }
+        Equivalence::Equivalent
+    }
+
+    /// Every bit of the TOGGLE (input) and LIGHT (output) pins, with the gate that is that bit, sorted
+    /// Fails if two TOGGLEs or two LIGHTs are the same bit, the pins could not be matched
+    fn pin_bits(&self) -> Result<(PinGates, PinGates), CircuitError> {
+        let bits = |kind: PrimitiveKind| {
+            let kind = GateKind::Primitive(kind);
+            let mut bits: Vec<(PinBit, usize, &str)> = self
+                .live_data
+                .values()
+                .filter_map(|item| item.as_any().downcast_ref::<Gate>())
+                .filter(|gate| gate.kind == kind)
+                .map(|gate| {
+                    let bit = match split_bus_name(&gate.name) {
+                        Some((name, bit)) => (name.to_string(), bit),
+                        None => (gate.name.clone(), 0),
+                    };
+                    (bit, gate.id, gate.name.as_str())
+                })
+                .collect();
+            bits.sort();
+            if let Some(pair) = bits.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+                let ids = bits.iter().filter(|(bit, ..)| *bit == pair[0].0).map(|(_, id, _)| *id).collect();
+                return Err(CircuitError::DuplicatePin { name: pair[0].2.to_string(), ids });
+            }
+            Ok(bits.into_iter().map(|(bit, id, _)| (bit, id)).collect::<PinGates>())
+        };
+        Ok((bits(PrimitiveKind::TOGGLE)?, bits(PrimitiveKind::LIGHT)?))
+    }
+
+    /// The primitive gates of the board in evaluation order, or why the board can't be checked:
+    /// a feedback loop, a gate that isn't flattened, or a gate with the wrong number of pins
+    fn combinational_gates(&self) -> Result<Vec<(usize, PrimitiveKind)>, CircuitError> {
+        if let Some(feedback) = feedback_loops(&self.live_data).first() {
+            let gate = feedback.gates.iter().min().copied();
+            return Err(CircuitError::Unsupported {
+                id: gate,
+                message: "this gate is part of a feedback loop, only combinational chips can be checked".to_string(),
+            });
+        }
+        let mut gates = Vec::new();
+        for gate_id in gate_order(&self.live_data) {
+            let kind = match self.live_data.get(&gate_id).and_then(|item| item.as_any().downcast_ref::<Gate>()) {
+                Some(Gate { kind: GateKind::Primitive(kind), .. }) => kind.clone(),
+                Some(gate) => {
+                    return Err(CircuitError::Unsupported {
+                        id: Some(gate_id),
+                        message: format!("{} can't be checked, flatten it first", gate.name),
+                    });
+                }
+                None => continue,
+            };
+            // the same arity errors the simulator raises
+            let n_ins = self.gate_inputs(gate_id).len();
+            kind.evaluate(gate_id, &mut false, &vec![false; n_ins], self.gate_outputs(gate_id).len())?;
+            gates.push((gate_id, kind));
+        }
+        Ok(gates)
+    }
+
+    /// Adds clauses tying every gate's output to its inputs, returns the literal every LIGHT shows
+    fn encode(
+        &self,
+        solver: &mut SatSolver,
+        truth: Lit,
+        gates: &[(usize, PrimitiveKind)],
+        pins: &[(PinBit, usize)],
+        input_lits: &HashMap<PinBit, Lit>,
+    ) -> HashMap<usize, Lit> {
+        let toggles: HashMap<usize, Lit> = pins.iter().map(|(bit, gate)| (*gate, input_lits[bit])).collect();
+
+        let mut signals: HashMap<usize, Lit> = HashMap::new(); // output id -> literal
+        let mut lights: HashMap<usize, Lit> = HashMap::new();
+        for (gate_id, kind) in gates {
+            // unconnected inputs read false, and only the first output of a gate is ever driven
+            let ins: Vec<Lit> = self
+                .gate_inputs(*gate_id)
+                .into_iter()
+                .map(|input| self.source_output(input).and_then(|output| signals.get(&output).copied()).unwrap_or(!truth))
+                .collect();
+            let outs = self.gate_outputs(*gate_id);
+
+            let signal = match kind {
+                PrimitiveKind::TOGGLE => toggles.get(gate_id).copied().unwrap_or(!truth),
+                PrimitiveKind::PULSE | PrimitiveKind::LOSIGNAL => !truth, // a pulse is over once the board settled
+                PrimitiveKind::HISIGNAL => truth,
+                PrimitiveKind::LIGHT => {
+                    lights.insert(*gate_id, ins.first().copied().unwrap_or(!truth));
+                    continue;
+                }
+                PrimitiveKind::BUFFER => ins[0],
+                PrimitiveKind::NOT => !ins[0],
+                PrimitiveKind::AND => and(solver, truth, &ins),
+                PrimitiveKind::NAND => !and(solver, truth, &ins),
+                PrimitiveKind::OR => or(solver, truth, &ins),
+                PrimitiveKind::NOR => !or(solver, truth, &ins),
+                PrimitiveKind::XOR => ins.iter().fold(!truth, |acc, lit| xor(solver, acc, *lit)),
+                PrimitiveKind::None => continue, // combinational_gates already refused it
+            };
+            if let Some(&output) = outs.first() {
+                signals.insert(output, signal);
+            }
+        }
+        lights
+    }
+}
+
+/// Flattens two chips and checks them for equivalence, see `Data::check_equivalence`
+pub fn check_chip_equivalence<F>(a: &ChipDefenition, b: &ChipDefenition, mut lookup: F) -> Result<Equivalence, CircuitError>
+where
+    F: FnMut(&str) -> Option<ChipDefenition>,
+{
+    let mut boards = [Data::new(), Data::new()];
+    for (board, chip) in boards.iter_mut().zip([a, b]) {
+        board.load_chip(chip);
+        board.flatten(&mut lookup)?;
+    }
+    boards[0].check_equivalence(&boards[1])
+}
+
+/// Values of pin bits put together per pin, sorted by name, bit n of a bus is bit n of its value
+fn pin_values(mut bits: Vec<(PinBit, bool)>) -> Vec<(String, u64)> {
+    bits.sort();
+    let mut groups: Vec<(String, u64)> = Vec::new();
+    for ((name, bit), value) in bits {
+        if groups.last().is_none_or(|(last, _)| *last != name) {
+            groups.push((name.clone(), 0));
+        }
+        if bit < 64 && value {
+            groups.last_mut().expect("pushed above").1 |= 1 << bit;
+        }
+    }
+    groups
+}
+
+/// A new literal that is true exactly when all of `ins` are (true for no inputs, like the simulator's AND)
+fn and(solver: &mut SatSolver, truth: Lit, ins: &[Lit]) -> Lit {
+    match ins {
+        [] => truth,
+        [single] => *single,
+        _ => {
+            let out = Lit::new(solver.new_var(), false);
+            for lit in ins {
+                solver.add_clause(&[!out, *lit]);
+            }
+            let mut all: Vec<Lit> = ins.iter().map(|lit| !*lit).collect();
+            all.push(out);
+            solver.add_clause(&all);
+            out
+        }
+    }
+}
+
+fn or(solver: &mut SatSolver, truth: Lit, ins: &[Lit]) -> Lit {
+    let negated: Vec<Lit> = ins.iter().map(|lit| !*lit).collect();
+    !and(solver, truth, &negated)
+}
+
+fn xor(solver: &mut SatSolver, a: Lit, b: Lit) -> Lit {
+    let out = Lit::new(solver.new_var(), false);
+    solver.add_clause(&[!out, a, b]);
+    solver.add_clause(&[!out, !a, !b]);
+    solver.add_clause(&[out, !a, b]);
+    solver.add_clause(&[out, a, !b]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A board computing `y[i] = a[i] XOR b[i]` for `bits` bits, out of NANDs when `nands` is set,
+    /// with an OR instead for the bits in `wrong`
+    fn xor_board(bits: usize, nands: bool, wrong: &[usize]) -> Data {
+        let mut data = Data::new();
+        let pin = |data: &mut Data, kind, name: String| {
+            let id = data.add_primitive(kind, Pos2::ZERO);
+            data.get_gate_mut(id).expect("pin").name = name;
+            id
+        };
+        for bit in 0..bits {
+            let a = pin(&mut data, PrimitiveKind::TOGGLE, format!("a[{}]", bit));
+            let b = pin(&mut data, PrimitiveKind::TOGGLE, format!("b[{}]", bit));
+            let y = pin(&mut data, PrimitiveKind::LIGHT, format!("y[{}]", bit));
+            let wire = |data: &mut Data, from: usize, to: usize, input: usize| {
+                let output = data.gate_outputs(from)[0];
+                let input = data.gate_inputs(to)[input];
+                data.connect(output, input).expect("output to input");
+            };
+            let last = if nands && !wrong.contains(&bit) {
+                let [both, left, right, last] = [(); 4].map(|_| data.add_primitive(PrimitiveKind::NAND, Pos2::ZERO));
+                for (from, to, input) in [(a, both, 0), (b, both, 1), (a, left, 0), (both, left, 1)] {
+                    wire(&mut data, from, to, input);
+                }
+                for (from, to, input) in [(b, right, 0), (both, right, 1), (left, last, 0), (right, last, 1)] {
+                    wire(&mut data, from, to, input);
+                }
+                last
+            } else {
+                let kind = if wrong.contains(&bit) { PrimitiveKind::OR } else { PrimitiveKind::XOR };
+                let gate = data.add_primitive(kind, Pos2::ZERO);
+                wire(&mut data, a, gate, 0);
+                wire(&mut data, b, gate, 1);
+                gate
+            };
+            wire(&mut data, last, y, 0);
+        }
+        data
+    }
+
+    /// The answer of the SAT miter and of the exhaustive simulation, whatever the number of inputs
+    fn both_ways(a: &Data, b: &Data) -> [Equivalence; 2] {
+        let ((ins_a, outs_a), (ins_b, outs_b)) = (a.pin_bits().expect("pins"), b.pin_bits().expect("pins"));
+        let gates = [a.combinational_gates().expect("gates"), b.combinational_gates().expect("gates")];
+        let mut inputs: Vec<PinBit> = ins_a.iter().chain(&ins_b).map(|(bit, _)| bit.clone()).collect();
+        inputs.sort();
+        inputs.dedup();
+        let pins = [(&ins_a, &outs_a), (&ins_b, &outs_b)];
+        [a.miter(b, &inputs, [&gates[0], &gates[1]], pins), a.sweep_equivalence(b, &inputs, pins)]
+    }
+
+    /// The value of pin `name` in a list of pin values
+    fn value(values: &[(String, u64)], name: &str) -> u64 {
+        values.iter().find(|(pin, _)| pin == name).map_or(0, |(_, value)| *value)
+    }
+
+    #[test]
+    fn equivalent_boards() {
+        for result in both_ways(&xor_board(3, false, &[]), &xor_board(3, true, &[])) {
+            assert!(matches!(result, Equivalence::Equivalent), "{:?}", result);
+        }
+        // past MAX_EXHAUSTIVE_BITS inputs only the miter is used
+        let result = xor_board(7, false, &[]).check_equivalence(&xor_board(7, true, &[])).expect("checkable");
+        assert!(matches!(result, Equivalence::Equivalent), "{:?}", result);
+    }
+
+    #[test]
+    fn different_boards_give_a_counterexample() {
+        let (a, b) = (xor_board(3, false, &[]), xor_board(3, true, &[1]));
+        let mut results = both_ways(&a, &b).to_vec();
+        results.push(xor_board(7, false, &[]).check_equivalence(&xor_board(7, true, &[1])).expect("checkable"));
+        for result in results {
+            let Equivalence::Different(counterexample) = result else {
+                panic!("{:?} should differ", result);
+            };
+            // XOR and OR only differ when both inputs are high
+            let (in_a, in_b) = (value(&counterexample.inputs, "a"), value(&counterexample.inputs, "b"));
+            assert_eq!((in_a >> 1) & (in_b >> 1) & 1, 1, "{}", counterexample);
+            let (y_a, y_b) = (value(&counterexample.outputs_a, "y"), value(&counterexample.outputs_b, "y"));
+            assert_eq!(y_a, in_a ^ in_b, "{}", counterexample);
+            assert_eq!(y_b, (in_a ^ in_b) | 0b10, "{}", counterexample);
+        }
+    }
+
+    #[test]
+    fn boards_without_inputs() {
+        let constant = |kind| {
+            let mut data = Data::new();
+            let signal = data.add_primitive(kind, Pos2::ZERO);
+            let light = data.add_primitive(PrimitiveKind::LIGHT, Pos2::ZERO);
+            data.get_gate_mut(light).expect("light").name = "y".to_string();
+            let (output, input) = (data.gate_outputs(signal)[0], data.gate_inputs(light)[0]);
+            data.connect(output, input).expect("output to input");
+            data
+        };
+        let (high, low) = (constant(PrimitiveKind::HISIGNAL), constant(PrimitiveKind::LOSIGNAL));
+        for result in both_ways(&high, &constant(PrimitiveKind::HISIGNAL)) {
+            assert!(matches!(result, Equivalence::Equivalent), "{:?}", result);
+        }
+        for result in both_ways(&high, &low) {
+            let Equivalence::Different(counterexample) = result else {
+                panic!("{:?} should differ", result);
+            };
+            assert!(counterexample.inputs.is_empty());
+            assert_eq!((value(&counterexample.outputs_a, "y"), value(&counterexample.outputs_b, "y")), (1, 0));
+        }
+    }
+
+    #[test]
+    fn pins_are_matched_by_kind() {
+        // a LIGHT named like a TOGGLE is another pin, not a duplicate
+        let mut a = xor_board(1, false, &[]);
+        let light = a.add_primitive(PrimitiveKind::LIGHT, Pos2::ZERO);
+        a.get_gate_mut(light).expect("light").name = "a[0]".to_string();
+        let mut b = xor_board(1, true, &[]);
+        let light = b.add_primitive(PrimitiveKind::LIGHT, Pos2::ZERO);
+        b.get_gate_mut(light).expect("light").name = "a[0]".to_string();
+        assert!(matches!(a.check_equivalence(&b), Ok(Equivalence::Equivalent)));
+
+        // two TOGGLEs for the same bit can't be matched
+        let toggle = a.add_primitive(PrimitiveKind::TOGGLE, Pos2::ZERO);
+        a.get_gate_mut(toggle).expect("toggle").name = "b[0]".to_string();
+        let Err(CircuitError::DuplicatePin { name, ids }) = a.check_equivalence(&b) else {
+            panic!("two b[0] TOGGLEs should be refused");
+        };
+        assert_eq!((name.as_str(), ids.len()), ("b[0]", 2));
+    }
+}
@@ -0,0 +1,268 @@
+use super::*;
+
+/// Chips nested deeper than this are taken to contain themselves
+pub const MAX_CHIP_DEPTH: usize = 32;
+
+/// A placed sub-chip waiting to be expanded, with how deep it is nested
+struct PendingChip {
+    chip: ChipDefenition,
+    pos: Pos2,
+    depth: usize,
+}
+
+/// What is known while flattening: the chips still to expand and the definitions seen so far
+#[derive(Default)]
+struct Flattening {
+    pending: Vec<PendingChip>,
+    definitions: Vec<ChipDefenition>,
+    depths: HashMap<usize, usize>, // custom gate id -> nesting depth
+}
+
+impl Data {
+    /// Replaces every chip on the board with the gates inside it, until only primitives are left
+    /// Custom gates are looked up by name among the sub-chips seen so far, then through `lookup`
+    /// A chip's TOGGLE and LIGHT pins are matched to the gate's inputs and outputs in name order
+    /// (the order of `pin_groups`, bus bits lowest first) and become BUFFERs joining the two sides
+    /// Sub-chips placed without connections get their inputs tied low and their outputs dropped
+    /// Returns how many chips were expanded
+    /// The chips are expanded on a copy of the board, which only replaces it once every chip expanded,
+    /// so the board is left as it was when one of them fails
+    pub fn flatten<F>(&mut self, lookup: F) -> Result<usize, CircuitError>
+    where
+        F: FnMut(&str) -> Option<ChipDefenition>,
+    {
+        let mut board = Data::new();
+        board.ids = self.ids.clone();
+        board.live_data = copy_items(&self.live_data)?;
+        board.sub_chips = self.sub_chips.clone();
+        let expanded = board.expand_chips(lookup)?;
+
+        self.live_data = board.live_data;
+        self.sub_chips.clear();
+        self.check_graph_after_edit();
+        Ok(expanded)
+    }
+
+    /// Expands every sub-chip and custom gate of the board, see `flatten`
+    fn expand_chips<F>(&mut self, mut lookup: F) -> Result<usize, CircuitError>
+    where
+        F: FnMut(&str) -> Option<ChipDefenition>,
+    {
+        let _ids = self.ids.enter();
+        let mut state = Flattening::default();
+        let mut sub_chips: Vec<ChipDefenition> = self.sub_chips.values().cloned().collect();
+        sub_chips.sort_by_key(|chip| chip.id);
+        for chip in sub_chips {
+            state.definitions.push(chip.clone());
+            let pos = chip.position.as_ref().map_or(Pos2::ZERO, |position| position.to_pos2());
+            state.pending.push(PendingChip { chip, pos, depth: 1 });
+        }
+
+        let mut expanded = 0;
+        loop {
+            if let Some(pending) = state.pending.pop() {
+                self.expand_sub_chip(pending, &mut state)?;
+                expanded += 1;
+                continue;
+            }
+            let mut custom: Vec<usize> = self
+                .live_data
+                .values()
+                .filter_map(|item| item.as_any().downcast_ref::<Gate>())
+                .filter(|gate| matches!(gate.kind, GateKind::Custom(_)))
+                .map(|gate| gate.id)
+                .collect();
+            custom.sort();
+            let Some(&gate_id) = custom.first() else {
+                break;
+            };
+            self.expand_custom_gate(gate_id, &mut state, &mut lookup)?;
+            expanded += 1;
+        }
+        Ok(expanded)
+    }
+
+    /// Swaps a custom gate for the contents of its chip
+    fn expand_custom_gate<F>(&mut self, gate_id: usize, state: &mut Flattening, lookup: &mut F) -> Result<(), CircuitError>
+    where
+        F: FnMut(&str) -> Option<ChipDefenition>,
+    {
+        let Some(gate) = self.live_data.get(&gate_id).and_then(|item| item.as_any().downcast_ref::<Gate>()) else {
+            return Err(CircuitError::InvalidDowncast { id: gate_id, expected: "Gate" });
+        };
+        let GateKind::Custom(name) = gate.kind.clone() else {
+            return Ok(());
+        };
+        let pos = gate.position.to_pos2();
+        let depth = state.depths.get(&gate_id).copied().unwrap_or(0);
+        if depth >= MAX_CHIP_DEPTH {
+            return Err(CircuitError::Unsupported {
+                id: Some(gate_id),
+                message: format!("{} is nested more than {} chips deep, does it contain itself?", name, MAX_CHIP_DEPTH),
+            });
+        }
+        let chip = state
+            .definitions
+            .iter()
+            .find(|chip| chip.name == name)
+            .cloned()
+            .or_else(|| lookup(&name))
+            .ok_or_else(|| CircuitError::Unsupported { id: Some(gate_id), message: format!("No chip named {} to flatten", name) })?;
+
+        let (pin_ins, pin_outs) = chip_pins(&chip);
+        let ins = self.gate_inputs(gate_id);
+        let outs = self.gate_outputs(gate_id);
+        for (pins, expected, found) in [("inputs", pin_ins.len(), ins.len()), ("outputs", pin_outs.len(), outs.len())] {
+            if expected != found {
+                return Err(CircuitError::ArityMismatch { id: gate_id, kind: name, pins, expected, found });
+            }
+        }
+
+        let map = self.place_nested(&chip, pos, depth + 1, state);
+        for (pin, input) in pin_ins.iter().zip(ins) {
+            self.pin_to_buffer(map[pin], &name, Some(input), None);
+        }
+        for (pin, output) in pin_outs.iter().zip(outs) {
+            self.pin_to_buffer(map[pin], &name, None, Some(output));
+        }
+        // its inputs and outputs now belong to the buffers
+        self.live_data.remove(&gate_id);
+        Ok(())
+    }
+
+    /// Places the contents of a sub-chip that has no connections to the board
+    fn expand_sub_chip(&mut self, pending: PendingChip, state: &mut Flattening) -> Result<(), CircuitError> {
+        if pending.depth > MAX_CHIP_DEPTH {
+            return Err(CircuitError::Unsupported {
+                id: Some(pending.chip.id),
+                message: format!("{} is nested more than {} chips deep, does it contain itself?", pending.chip.name, MAX_CHIP_DEPTH),
+            });
+        }
+        let (pin_ins, pin_outs) = chip_pins(&pending.chip);
+        let map = self.place_nested(&pending.chip, pending.pos, pending.depth, state);
+        for pin in pin_ins {
+            if let Some(gate) = self.get_gate_mut(map[&pin]) {
+                gate.name = format!("{}.{}", pending.chip.name, gate.name);
+                gate.kind = GateKind::Primitive(PrimitiveKind::LOSIGNAL); // nothing drives it, like an unconnected input
+            }
+        }
+        for pin in pin_outs {
+            self.remove_gate(map[&pin]);
+        }
+        Ok(())
+    }
+
+    /// Pastes a chip's contents centered on `pos`, and queues the chips nested inside it
+    fn place_nested(&mut self, chip: &ChipDefenition, pos: Pos2, depth: usize, state: &mut Flattening) -> HashMap<usize, usize> {
+        let offset = chip_offset(chip, pos);
+        let map = self.paste(chip.to_live_data(), offset);
+        for gate in chip.sub_gates.values().filter(|gate| matches!(gate.kind, GateKind::Custom(_))) {
+            state.depths.insert(map[&gate.id], depth);
+        }
+        let mut sub_chips: Vec<&ChipDefenition> = chip.sub_chips.values().collect();
+        sub_chips.sort_by_key(|sub_chip| sub_chip.id);
+        for sub_chip in sub_chips {
+            state.definitions.push(sub_chip.clone());
+            let sub_pos = sub_chip.position.as_ref().map_or(Pos2::ZERO, |position| position.to_pos2()) + offset;
+            state.pending.push(PendingChip { chip: sub_chip.clone(), pos: sub_pos, depth: depth + 1 });
+        }
+        map
+    }
+
+    /// Turns a TOGGLE or LIGHT pin of an expanded chip into a BUFFER, taking over the chip gate's input or output
+    fn pin_to_buffer(&mut self, pin_id: usize, chip_name: &str, input: Option<usize>, output: Option<usize>) {
+        if let Some(input) = input.and_then(|id| self.get_input_mut(id)) {
+            input.parent_id = Some(pin_id);
+            input.index = 0;
+        }
+        if let Some(output) = output.and_then(|id| self.get_output_mut(id)) {
+            output.parent_id = Some(pin_id);
+            output.index = 0;
+        }
+        if let Some(gate) = self.get_gate_mut(pin_id) {
+            gate.name = format!("{}.{}", chip_name, gate.name);
+            gate.kind = GateKind::Primitive(PrimitiveKind::BUFFER);
+            if let Some(input) = input {
+                gate.ins.insert(input, false);
+                gate.n_in = 1;
+            }
+            if let Some(output) = output {
+                gate.outs.insert(output, false);
+                gate.n_out = 1;
+            }
+        }
+    }
+}
+
+/// Gate ids of a chip's input and output pins, in the order they match a custom gate's inputs and outputs
+pub fn chip_pins(chip: &ChipDefenition) -> (Vec<usize>, Vec<usize>) {
+    let mut board = Data::new();
+    board.live_data = chip.to_live_data();
+    let (inputs, outputs) = board.pin_groups();
+    let ids = |pins: Vec<PinGroup>| pins.iter().flat_map(|pin| board.find_pin(&pin.name)).collect();
+    (ids(inputs), ids(outputs))
+}
+
+/// A copy of every item of a board, with the same ids
+fn copy_items(live_data: &HashMap<usize, Box<dyn Logical>>) -> Result<HashMap<usize, Box<dyn Logical>>, CircuitError> {
+    live_data
+        .iter()
+        .map(|(id, item)| {
+            let any = item.as_any();
+            let copy: Box<dyn Logical> = if let Some(gate) = any.downcast_ref::<Gate>() {
+                Box::new(gate.clone())
+            } else if let Some(wire) = any.downcast_ref::<Wire>() {
+                Box::new(wire.clone())
+            } else if let Some(input) = any.downcast_ref::<Input>() {
+                Box::new(input.clone())
+            } else if let Some(output) = any.downcast_ref::<Output>() {
+                Box::new(output.clone())
+            } else {
+                return Err(CircuitError::InvalidDowncast { id: *id, expected: "Gate, Wire, Input or Output" });
+            };
+            Ok((*id, copy))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A TOGGLE `a` wired through a custom gate `chip` to a LIGHT `y`
+    fn through_chip(chip: &str) -> Data {
+        let mut data = Data::new();
+        let a = data.add_primitive(PrimitiveKind::TOGGLE, Pos2::ZERO);
+        data.get_gate_mut(a).expect("pin").name = "a".to_string();
+        let y = data.add_primitive(PrimitiveKind::LIGHT, Pos2::ZERO);
+        data.get_gate_mut(y).expect("pin").name = "y".to_string();
+        let gate = data.add_custom_gate(chip, 1, 1, Pos2::ZERO);
+        data.connect(data.gate_outputs(a)[0], data.gate_inputs(gate)[0]).expect("a to chip");
+        data.connect(data.gate_outputs(gate)[0], data.gate_inputs(y)[0]).expect("chip to y");
+        data
+    }
+
+    #[test]
+    fn missing_nested_chip_leaves_the_board_alone() {
+        let outer = through_chip("Inner").to_chip("Outer");
+        let mut data = through_chip("Outer");
+        let mut before: Vec<usize> = data.live_data.keys().copied().collect();
+        before.sort();
+
+        let result = data.flatten(|name| (name == "Outer").then(|| outer.clone()));
+        let Err(CircuitError::Unsupported { message, .. }) = result else {
+            panic!("flattening without Inner should fail, got {:?}", result);
+        };
+        assert!(message.contains("Inner"), "{}", message);
+        let mut after: Vec<usize> = data.live_data.keys().copied().collect();
+        after.sort();
+        assert_eq!(before, after);
+        let still_custom = data
+            .live_data
+            .values()
+            .filter_map(|item| item.as_any().downcast_ref::<Gate>())
+            .any(|gate| matches!(&gate.kind, GateKind::Custom(name) if name == "Outer"));
+        assert!(still_custom, "the Outer gate should still be there");
+        assert!(data.validate_graph().is_empty());
+    }
+}
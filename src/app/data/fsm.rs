@@ -0,0 +1,532 @@
+use std::fmt::{self, Display, Formatter};
+use std::path::Path;
+
+use super::*;
+
+/// Limits that keep the next-state logic small enough to minimize exactly
+pub const MAX_FSM_STATES: usize = 16;
+pub const MAX_FSM_INPUTS: usize = 6;
+
+/// Pin that clocks the flip-flops, the same one test scripts tick
+pub const FSM_CLOCK_PIN: &str = "clk";
+/// Bus of LIGHTs showing the flip-flops
+pub const FSM_STATE_PIN: &str = "state";
+
+/// Whether the outputs depend on the state only, or also on the inputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum MachineKind {
+    #[default]
+    Moore,
+    Mealy,
+}
+
+/// How states are stored in the flip-flops
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum StateEncoding {
+    #[default]
+    Binary,
+    Gray,
+    OneHot,
+}
+
+impl StateEncoding {
+    pub const ALL: [StateEncoding; 3] = [StateEncoding::Binary, StateEncoding::Gray, StateEncoding::OneHot];
+
+    /// Flip-flops needed for this many states
+    pub fn bits(self, states: usize) -> usize {
+        match self {
+            StateEncoding::OneHot => states,
+            _ => (usize::BITS - (states.max(2) - 1).leading_zeros()) as usize,
+        }
+    }
+
+    /// The flip-flop values of a state, bit k goes to `state[k]`
+    /// The first state is 0 in binary and Gray, neighbouring states differ in one bit in Gray
+    pub fn code(self, state: usize) -> usize {
+        match self {
+            StateEncoding::Binary => state,
+            StateEncoding::Gray => state ^ (state >> 1),
+            StateEncoding::OneHot => 1 << state,
+        }
+    }
+
+    /// Parses "binary", "gray" or "one-hot", in any case
+    pub fn parse(text: &str) -> Result<Self, String> {
+        StateEncoding::ALL
+            .into_iter()
+            .find(|encoding| encoding.to_string().eq_ignore_ascii_case(text))
+            .ok_or_else(|| format!("unknown state encoding {}, use binary, gray or one-hot", text))
+    }
+}
+
+impl Display for StateEncoding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            StateEncoding::Binary => "binary",
+            StateEncoding::Gray => "gray",
+            StateEncoding::OneHot => "one-hot",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+pub struct FsmState {
+    pub name: String,
+    pub outputs: Vec<bool>, // Moore outputs, one per output of the machine
+    pub position: Pos2,     // where the state is drawn in the diagram
+}
+
+#[derive(Debug, Clone, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+pub struct Transition {
+    pub from: usize, // index into the states
+    pub to: usize,
+    pub condition: String, // see `StateMachine::check`, empty always holds
+    pub outputs: Vec<bool>, // Mealy outputs while the transition is the one taken
+}
+
+/// A state diagram, the first state is the one the machine starts in
+/// From each state the first transition whose condition holds is taken on the rising edge of the clock,
+/// with none the machine stays where it is and the Mealy outputs are 0
+#[derive(Debug, Clone, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+pub struct StateMachine {
+    pub name: String,
+    pub kind: MachineKind,
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+    pub states: Vec<FsmState>,
+    pub transitions: Vec<Transition>,
+}
+
+/// A transition condition, parsed
+#[derive(Debug, Clone, PartialEq)]
+enum Condition {
+    Constant(bool),
+    Input(usize),
+    Not(Box<Condition>),
+    And(Box<Condition>, Box<Condition>),
+    Xor(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+}
+
+impl Condition {
+    fn holds(&self, values: &[bool]) -> bool {
+        match self {
+            Condition::Constant(value) => *value,
+            Condition::Input(index) => values[*index],
+            Condition::Not(a) => !a.holds(values),
+            Condition::And(a, b) => a.holds(values) && b.holds(values),
+            Condition::Xor(a, b) => a.holds(values) != b.holds(values),
+            Condition::Or(a, b) => a.holds(values) || b.holds(values),
+        }
+    }
+}
+
+/// Recursive descent over the tokens of a condition, `|` binds loosest, then `^`, `&` and `!`
+struct ConditionParser<'a> {
+    tokens: Vec<String>,
+    at: usize,
+    inputs: &'a [String],
+}
+
+impl ConditionParser<'_> {
+    fn parse(text: &str, inputs: &[String]) -> Result<Condition, String> {
+        let mut tokens = Vec::new();
+        let mut name = String::new();
+        for c in text.chars() {
+            if c.is_alphanumeric() || "_[].".contains(c) {
+                name.push(c);
+                continue;
+            }
+            if !name.is_empty() {
+                tokens.push(std::mem::take(&mut name));
+            }
+            match c {
+                '!' | '&' | '|' | '^' | '(' | ')' => tokens.push(c.to_string()),
+                _ if c.is_whitespace() => {}
+                _ => return Err(format!("unexpected {} in condition {}", c, text)),
+            }
+        }
+        if !name.is_empty() {
+            tokens.push(name);
+        }
+        if tokens.is_empty() {
+            return Ok(Condition::Constant(true));
+        }
+
+        let mut parser = ConditionParser { tokens, at: 0, inputs };
+        let condition = parser.binary(0)?;
+        match parser.tokens.get(parser.at) {
+            None => Ok(condition),
+            Some(token) => Err(format!("unexpected {} in condition {}", token, text)),
+        }
+    }
+
+    /// Operators from loosest to tightest, `level` is the loosest one still allowed
+    fn binary(&mut self, level: usize) -> Result<Condition, String> {
+        const OPERATORS: [&str; 3] = ["|", "^", "&"];
+        if level == OPERATORS.len() {
+            return self.unary();
+        }
+        let mut condition = self.binary(level + 1)?;
+        while self.tokens.get(self.at).is_some_and(|token| token == OPERATORS[level]) {
+            self.at += 1;
+            let right = Box::new(self.binary(level + 1)?);
+            let left = Box::new(condition);
+            condition = match level {
+                0 => Condition::Or(left, right),
+                1 => Condition::Xor(left, right),
+                _ => Condition::And(left, right),
+            };
+        }
+        Ok(condition)
+    }
+
+    fn unary(&mut self) -> Result<Condition, String> {
+        let token = self.tokens.get(self.at).cloned().ok_or("condition ends too early")?;
+        self.at += 1;
+        match token.as_str() {
+            "!" => Ok(Condition::Not(Box::new(self.unary()?))),
+            "(" => {
+                let condition = self.binary(0)?;
+                match self.tokens.get(self.at) {
+                    Some(token) if token == ")" => {
+                        self.at += 1;
+                        Ok(condition)
+                    }
+                    _ => Err("missing )".to_string()),
+                }
+            }
+            "1" => Ok(Condition::Constant(true)),
+            "0" => Ok(Condition::Constant(false)),
+            name => match self.inputs.iter().position(|input| input == name) {
+                Some(index) => Ok(Condition::Input(index)),
+                None => Err(format!("no input named {}", name)),
+            },
+        }
+    }
+}
+
+/// What happens in a state for one combination of inputs
+#[derive(Debug, Clone)]
+struct Step {
+    next: usize,
+    outputs: Vec<bool>, // what the output pins show, Moore or Mealy
+}
+
+/// The minimized logic of a machine, every cover is over `variables`
+struct FsmLogic {
+    variables: Vec<String>, // state[bits - 1] down to state[0], then the inputs
+    next_state: Vec<Vec<Implicant>>, // index k feeds the flip-flop of state[k]
+    outputs: Vec<Vec<Implicant>>,
+}
+
+impl StateMachine {
+    /// Pads or cuts the output values of every state and transition to the number of outputs
+    pub fn fit_outputs(&mut self) {
+        let outputs = self.outputs.len();
+        for state in &mut self.states {
+            state.outputs.resize(outputs, false);
+        }
+        for transition in &mut self.transitions {
+            transition.outputs.resize(outputs, false);
+        }
+    }
+
+    /// Removes a state with every transition into or out of it
+    pub fn remove_state(&mut self, state: usize) {
+        self.states.remove(state);
+        self.transitions.retain(|transition| transition.from != state && transition.to != state);
+        for transition in &mut self.transitions {
+            transition.from -= (transition.from > state) as usize;
+            transition.to -= (transition.to > state) as usize;
+        }
+    }
+
+    /// Problems that keep the machine from being built
+    /// Conditions are input names combined with `!`, `&`, `^`, `|` and parentheses, `1` and `0` are constants
+    pub fn check(&self) -> Result<(), String> {
+        if self.states.is_empty() {
+            return Err("The machine has no states".to_string());
+        }
+        if self.states.len() > MAX_FSM_STATES {
+            return Err(format!("{} states is too many, at most {} are supported", self.states.len(), MAX_FSM_STATES));
+        }
+        if self.inputs.len() > MAX_FSM_INPUTS {
+            return Err(format!("{} inputs is too many, at most {} are supported", self.inputs.len(), MAX_FSM_INPUTS));
+        }
+        let mut names: Vec<&String> = self.inputs.iter().chain(&self.outputs).collect();
+        names.sort();
+        if let Some(pair) = names.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(format!("Two pins are named {}", pair[0]));
+        }
+        if let Some(name) = names.iter().find(|name| name.is_empty() || **name == FSM_CLOCK_PIN || **name == FSM_STATE_PIN) {
+            return Err(format!("A pin can't be named \"{}\"", name));
+        }
+        for transition in &self.transitions {
+            if transition.from >= self.states.len() || transition.to >= self.states.len() {
+                return Err(format!("A transition goes from state {} to state {}, which don't both exist", transition.from, transition.to));
+            }
+            ConditionParser::parse(&transition.condition, &self.inputs)
+                .map_err(|e| format!("{} -> {}: {}", self.states[transition.from].name, self.states[transition.to].name, e))?;
+        }
+        Ok(())
+    }
+
+    /// For every state and every combination of inputs (the first input most significant), what happens
+    fn steps(&self) -> Result<Vec<Vec<Step>>, String> {
+        self.check()?;
+        let conditions: Vec<Condition> = self
+            .transitions
+            .iter()
+            .map(|transition| ConditionParser::parse(&transition.condition, &self.inputs))
+            .collect::<Result<_, _>>()?;
+        let n = self.inputs.len();
+        let output = |values: &[bool], index: usize| values.get(index).copied().unwrap_or(false);
+        Ok((0..self.states.len())
+            .map(|state| {
+                (0..1usize << n)
+                    .map(|combination| {
+                        let values: Vec<bool> = (0..n).map(|index| combination >> (n - 1 - index) & 1 == 1).collect();
+                        let taken = self.transitions.iter().zip(&conditions).find(|(transition, condition)| transition.from == state && condition.holds(&values));
+                        let outputs = (0..self.outputs.len()).map(|index| match (self.kind, taken) {
+                            (MachineKind::Moore, _) => output(&self.states[state].outputs, index),
+                            (MachineKind::Mealy, Some((transition, _))) => output(&transition.outputs, index),
+                            (MachineKind::Mealy, None) => false,
+                        });
+                        Step { next: taken.map_or(state, |(transition, _)| transition.to), outputs: outputs.collect() }
+                    })
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// Minimizes the next-state and output logic for an encoding
+    /// Binary and Gray minimize over every flip-flop and input, with the unused codes as don't-cares;
+    /// one-hot reads a single flip-flop per term, so only the inputs need minimizing
+    fn logic(&self, encoding: StateEncoding) -> Result<FsmLogic, String> {
+        let steps = self.steps()?;
+        let n_in = self.inputs.len();
+        let bits = encoding.bits(self.states.len());
+        let mut variables: Vec<String> = (0..bits).rev().map(|bit| format!("{}[{}]", FSM_STATE_PIN, bit)).collect();
+        variables.extend(self.inputs.iter().cloned());
+
+        // the flip-flop inputs state[0] and up, then the outputs
+        let function = |index: usize, step: &Step| match index.checked_sub(bits) {
+            None => encoding.code(step.next) >> index & 1 == 1,
+            Some(output) => step.outputs[output],
+        };
+
+        let mut covers: Vec<Vec<Implicant>> = Vec::new();
+        for index in 0..bits + self.outputs.len() {
+            let cover = match encoding {
+                StateEncoding::OneHot => {
+                    let mut cover = Vec::new();
+                    for (state, steps) in steps.iter().enumerate() {
+                        let cells = steps.iter().map(|step| if function(index, step) { Cell::One } else { Cell::Zero }).collect();
+                        let map = KarnaughMap { inputs: self.inputs.clone(), output: String::new(), cells };
+                        let flip_flop = 1 << (n_in + state);
+                        cover.extend(map.minimal_cover().into_iter().map(|term| Implicant { bits: term.bits | flip_flop, mask: term.mask | flip_flop }));
+                    }
+                    cover
+                }
+                _ => {
+                    let mut cells = vec![Cell::DontCare; 1 << (bits + n_in)];
+                    for (state, steps) in steps.iter().enumerate() {
+                        for (combination, step) in steps.iter().enumerate() {
+                            cells[encoding.code(state) << n_in | combination] = if function(index, step) { Cell::One } else { Cell::Zero };
+                        }
+                    }
+                    KarnaughMap { inputs: variables.clone(), output: String::new(), cells }.minimal_cover()
+                }
+            };
+            covers.push(cover);
+        }
+        let outputs = covers.split_off(bits);
+        Ok(FsmLogic { variables, next_state: covers, outputs })
+    }
+
+    /// Writes the machine to `path` in RON format
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let ron_string = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new())?;
+        std::fs::write(path, ron_string)?;
+        Ok(())
+    }
+
+    /// Reads a machine previously written with `save_to_file`
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let content = std::fs::read_to_string(&path)?;
+        ron::from_str(&content).map_err(|e| CircuitError::file_format(&path.as_ref().display().to_string(), e).into())
+    }
+}
+
+/// What was built for a state machine
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FsmReport {
+    pub encoding: StateEncoding,
+    pub codes: Vec<(String, String)>, // state, its flip-flop values from state[bits - 1] down to state[0]
+    pub equations: Vec<(String, String)>, // signal, the sum of products it is built from
+    pub flip_flops: usize,
+    pub gates: usize, // every gate added, pins and flip-flops included
+}
+
+impl Display for FsmReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} encoding: {} flip-flops, {} gates", self.encoding, self.flip_flops, self.gates)?;
+        for (state, code) in &self.codes {
+            writeln!(f, "  {} = {}", state, code)?;
+        }
+        for (signal, expression) in &self.equations {
+            writeln!(f, "  {} = {}", signal, expression)?;
+        }
+        Ok(())
+    }
+}
+
+impl Data {
+    /// Builds a state machine on the board from D flip-flops and minimized logic, the top left corner at `origin`
+    /// Pins are TOGGLEs `clk` and one per input, LIGHTs one per output and `state[k]` per flip-flop
+    /// The flip-flops take their next state when `clk` goes from 0 to 1; every gate starts out false,
+    /// which is the first state in every encoding as one-hot stores the first state's bit inverted
+    pub fn build_state_machine(&mut self, machine: &StateMachine, encoding: StateEncoding, origin: Pos2) -> Result<FsmReport, CircuitError> {
+        let logic = machine.logic(encoding).map_err(|message| CircuitError::Unsupported { id: None, message })?;
+        let _ids = self.ids.enter();
+        let bits = logic.next_state.len();
+        let one_hot = encoding == StateEncoding::OneHot;
+
+        // pins, then the logic in the next three columns, the flip-flops and the LIGHTs
+        let mut builder = SopBuilder::new(origin);
+        builder.logic_column = 1;
+        let pin = |data: &mut Data, builder: &mut SopBuilder, kind: PrimitiveKind, column: usize, name: String, ins: &[usize]| {
+            let gate = builder.gate(data, kind, column, ins);
+            if let Some(gate) = data.get_gate_mut(gate) {
+                gate.name = name;
+            }
+            data.gate_outputs(gate).first().copied()
+        };
+        let clk = pin(self, &mut builder, PrimitiveKind::TOGGLE, 0, FSM_CLOCK_PIN.to_string(), &[]).expect("a TOGGLE has an output");
+        let mut inputs = Vec::new();
+        for name in &machine.inputs {
+            inputs.push(pin(self, &mut builder, PrimitiveKind::TOGGLE, 0, name.clone(), &[]).expect("a TOGGLE has an output"));
+        }
+        let clk_n = builder.not(self, clk);
+
+        // master latch open while clk is 0, slave latch open while it is 1
+        let mut flip_flops = Vec::new();
+        for _ in 0..bits {
+            let (master, d) = self.latch(&mut builder, 4, clk_n, clk);
+            let (q, slave_d) = self.latch(&mut builder, 5, clk, clk_n);
+            for input in slave_d {
+                self.connect(master, input).expect("a new latch has nothing plugged in");
+            }
+            flip_flops.push((q, d));
+        }
+        // the signals of the variables, state[k] is flip-flop k, most significant first
+        let mut signals: Vec<usize> = Vec::new();
+        for bit in (0..bits).rev() {
+            let q = flip_flops[bit].0;
+            signals.push(if one_hot && bit == 0 { builder.not(self, q) } else { q });
+        }
+        signals.extend(&inputs);
+
+        for (bit, cover) in logic.next_state.iter().enumerate() {
+            let mut next = builder.build(self, cover, &signals);
+            if one_hot && bit == 0 {
+                next = builder.not(self, next);
+            }
+            for input in &flip_flops[bit].1 {
+                self.connect(next, *input).expect("a new latch has nothing plugged in");
+            }
+        }
+        for (name, cover) in machine.outputs.iter().zip(&logic.outputs) {
+            let output = builder.build(self, cover, &signals);
+            pin(self, &mut builder, PrimitiveKind::LIGHT, 6, name.clone(), &[output]);
+        }
+        for bit in 0..bits {
+            let name = format!("{}[{}]", FSM_STATE_PIN, bit);
+            pin(self, &mut builder, PrimitiveKind::LIGHT, 6, name, &[signals[bits - 1 - bit]]);
+        }
+        self.check_graph_after_edit();
+
+        let names = KarnaughMap { inputs: logic.variables, output: String::new(), cells: Vec::new() }; // only writes the covers out
+        let next_state = logic.next_state.iter().enumerate().map(|(bit, cover)| (format!("next {}[{}]", FSM_STATE_PIN, bit), cover));
+        let equations = next_state
+            .chain(machine.outputs.iter().cloned().zip(&logic.outputs))
+            .map(|(signal, cover)| (signal, names.expression(cover)))
+            .collect();
+        let codes = machine
+            .states
+            .iter()
+            .enumerate()
+            .map(|(state, fsm_state)| {
+                let code = encoding.code(state);
+                (fsm_state.name.clone(), (0..bits).rev().map(|bit| if code >> bit & 1 == 1 { '1' } else { '0' }).collect())
+            })
+            .collect();
+        Ok(FsmReport { encoding, codes, equations, flip_flops: bits, gates: builder.added })
+    }
+
+    /// A D latch holding its value while `enable` is 0: Q = D & enable | Q & !enable | D & Q
+    /// The last term keeps Q steady while `enable` and its inverse switch one tick apart
+    /// Returns the output Q and the two inputs D has to be plugged into
+    fn latch(&mut self, builder: &mut SopBuilder, column: usize, enable: usize, disable: usize) -> (usize, [usize; 2]) {
+        let gates: Vec<usize> = [PrimitiveKind::AND, PrimitiveKind::AND, PrimitiveKind::AND, PrimitiveKind::OR, PrimitiveKind::OR]
+            .into_iter()
+            .map(|kind| builder.gate(self, kind, column, &[]))
+            .collect();
+        let ins: Vec<Vec<usize>> = gates.iter().map(|gate| self.gate_inputs(*gate)).collect();
+        let outs: Vec<usize> = gates.iter().map(|gate| self.gate_outputs(*gate)[0]).collect();
+        let q = outs[4];
+        for (output, input) in [(enable, ins[0][1]), (q, ins[1][0]), (disable, ins[1][1]), (q, ins[2][1]), (outs[0], ins[3][0]), (outs[1], ins[3][1]), (outs[3], ins[4][0]), (outs[2], ins[4][1])] {
+            self.connect(output, input).expect("a new latch has nothing plugged in");
+        }
+        (q, [ins[0][0], ins[2][0]])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Off and On, going to the other state on every clock edge while `go` is high, `on` shows which
+    fn flip() -> StateMachine {
+        let state = |name: &str, on: bool| FsmState { name: name.to_string(), outputs: vec![on], position: Pos2::ZERO };
+        let transition = |from, to| Transition { from, to, condition: "go".to_string(), outputs: vec![false] };
+        StateMachine {
+            name: "flip".to_string(),
+            inputs: vec!["go".to_string()],
+            outputs: vec!["on".to_string()],
+            states: vec![state("Off", false), state("On", true)],
+            transitions: vec![transition(0, 1), transition(1, 0)],
+            ..Default::default()
+        }
+    }
+
+    /// One rising edge of the clock, letting the board settle after each half
+    fn clock(data: &mut Data) {
+        for value in [0, 1] {
+            data.set_pin(FSM_CLOCK_PIN, value).expect("clock pin");
+            assert!(data.settle(256), "the machine should settle");
+        }
+    }
+
+    #[test]
+    fn two_states_in_every_encoding() {
+        for encoding in StateEncoding::ALL {
+            let mut data = Data::new();
+            let report = data.build_state_machine(&flip(), encoding, Pos2::ZERO).expect("a valid machine");
+            assert_eq!(report.flip_flops, encoding.bits(2), "{} encoding", encoding);
+            assert!(data.settle(256), "the machine should settle");
+
+            let mut state = 0;
+            for go in [0, 1, 1, 0, 1] {
+                let (on, code) = (data.read_pin("on").expect("output pin"), data.read_pin(FSM_STATE_PIN).expect("state pins"));
+                assert_eq!(on, state as u64, "{} encoding", encoding);
+                assert_eq!(code, encoding.code(state) as u64, "{} encoding", encoding);
+                data.set_pin("go", go).expect("input pin");
+                clock(&mut data);
+                state ^= go as usize;
+            }
+        }
+    }
+}
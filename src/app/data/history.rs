@@ -0,0 +1,153 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use super::*;
+
+pub const DEFAULT_HISTORY_CAPACITY: usize = 1000;
+
+/// The order the signals of a snapshot are stored in: gate states, then inputs, outputs and wires, like the netlist
+/// Every snapshot taken from the same compiled netlist shares one layout
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SignalLayout {
+    pub gate_ids: Vec<usize>,
+    pub input_ids: Vec<usize>,
+    pub output_ids: Vec<usize>,
+    pub wire_ids: Vec<usize>,
+}
+
+impl SignalLayout {
+    pub fn len(&self) -> usize {
+        self.gate_ids.len() + self.input_ids.len() + self.output_ids.len() + self.wire_ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The state of the whole board right after a tick, one bit per signal
+#[derive(Debug, Clone)]
+pub struct TickSnapshot {
+    pub tick: u64,
+    pub layout: Arc<SignalLayout>,
+    pub bits: Vec<u64>, // signal i is bit i % 64 of word i / 64, in the order of `layout`
+}
+
+impl TickSnapshot {
+    pub fn capture(tick: u64, netlist: &Netlist) -> Self {
+        let mut bits = vec![0u64; netlist.layout.len().div_ceil(64)];
+        for (i, signal) in netlist.signals().enumerate() {
+            bits[i / 64] |= (signal as u64) << (i % 64);
+        }
+        TickSnapshot {
+            tick,
+            layout: netlist.layout.clone(),
+            bits,
+        }
+    }
+
+    fn bit(&self, i: usize) -> bool {
+        (self.bits[i / 64] >> (i % 64)) & 1 == 1
+    }
+
+    /// Writes the snapshot back onto the board, anything added since is left as it is
+    pub fn restore(&self, live_data: &mut HashMap<usize, Box<dyn Logical>>) {
+        let layout = &self.layout;
+        let ids = layout
+            .gate_ids
+            .iter()
+            .chain(&layout.input_ids)
+            .chain(&layout.output_ids)
+            .chain(&layout.wire_ids);
+        for (i, id) in ids.enumerate() {
+            let signal = self.bit(i);
+            let Some(item) = live_data.get_mut(id) else {
+                continue;
+            };
+            let any = item.as_any_mut();
+            if let Some(gate) = any.downcast_mut::<Gate>() {
+                gate.state = signal;
+            } else if let Some(wire) = any.downcast_mut::<Wire>() {
+                wire.set_signal(signal);
+            } else if let Some(input) = any.downcast_mut::<Input>() {
+                input.signal = signal;
+            } else if let Some(output) = any.downcast_mut::<Output>() {
+                output.signal = signal;
+            }
+        }
+    }
+}
+
+/// Ring buffer of the last few ticks, used to scrub back and forth in time
+/// Off unless turned on, the editor does that, headless runs don't pay for snapshots
+#[derive(Debug, Clone)]
+pub struct History {
+    pub enabled: bool,
+    pub capacity: usize,
+    pub snapshots: VecDeque<TickSnapshot>,
+    pub cursor: Option<usize>, // index of the snapshot on the board while scrubbing, None when live
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History {
+            enabled: false,
+            capacity: DEFAULT_HISTORY_CAPACITY,
+            snapshots: VecDeque::new(),
+            cursor: None,
+        }
+    }
+}
+
+impl History {
+    pub fn record(&mut self, tick: u64, netlist: &Netlist) {
+        // ticking while looking at the past starts a new timeline from there
+        if let Some(cursor) = self.cursor.take() {
+            self.snapshots.truncate(cursor + 1);
+        }
+        if !self.enabled || self.capacity == 0 {
+            return;
+        }
+        while self.snapshots.len() >= self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(TickSnapshot::capture(tick, netlist));
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.cursor = None;
+    }
+
+    /// Index of the snapshot shown on the board
+    pub fn position(&self) -> Option<usize> {
+        self.cursor.or(self.snapshots.len().checked_sub(1))
+    }
+}
+
+impl Data {
+    /// Pauses and puts the board back to how it was after a recorded tick
+    /// Ticking from there throws away the snapshots after it
+    pub fn rewind_to(&mut self, index: usize) -> Result<(), Box<dyn Error>> {
+        let snapshot = self
+            .history
+            .snapshots
+            .get(index)
+            .ok_or_else(|| format!("No snapshot {} in the history", index))?;
+        snapshot.restore(&mut self.live_data);
+        self.netlist = None; // the compiled signals are stale now
+        self.sim.pause();
+        self.sim.total_ticks = snapshot.tick;
+        self.history.cursor = Some(index);
+        self.break_hit = None;
+        Ok(())
+    }
+
+    /// Goes back to the newest snapshot, which is where the simulation was before scrubbing
+    pub fn return_to_latest(&mut self) {
+        if let Some(last) = self.history.snapshots.len().checked_sub(1) {
+            let _ = self.rewind_to(last);
+        }
+        self.history.cursor = None;
+    }
+}
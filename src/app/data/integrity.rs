@@ -0,0 +1,299 @@
+use std::fmt;
+
+use super::*;
+
+/// Repairs can uncover more problems (a removed output leaves its wires dangling), so repair runs in passes
+const MAX_REPAIR_PASSES: usize = 8;
+
+/// How a broken reference gets fixed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repair {
+    /// Remove the item from the board
+    Remove(usize),
+    /// Unplug a wire from its input, it stays attached to its output
+    DisconnectWire(usize),
+    /// Set whether a wire counts as connected
+    SetConnected(usize, bool),
+    /// Point an input back at the wire plugged into it: (input, wire)
+    SetInputSource(usize, usize),
+    ClearInputSource(usize),
+    /// Add or drop a wire from an output's list: (output, wire)
+    AddOutWire(usize, usize),
+    DropOutWire(usize, usize),
+    /// Add or drop an input or output from a gate's pins: (gate, pin)
+    AddGatePin(usize, usize),
+    DropGatePin(usize, usize),
+}
+
+/// One back-reference that does not agree with the reference it mirrors
+#[derive(Debug, Clone)]
+pub struct GraphIssue {
+    pub message: String,
+    pub ids: Vec<usize>,
+    pub repair: Repair,
+}
+
+impl GraphIssue {
+    fn new(message: String, ids: Vec<usize>, repair: Repair) -> Self {
+        GraphIssue { message, ids, repair }
+    }
+}
+
+impl fmt::Display for GraphIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Data {
+    /// Checks that every connection is recorded on both ends:
+    /// `Wire::source_id` and `Output::out_wire_ids`, `Wire::dest` and `Input::source_wire_id`,
+    /// `Input`/`Output::parent_id` and `Gate::ins`/`outs`
+    pub fn validate_graph(&self) -> Vec<GraphIssue> {
+        let mut issues = Vec::new();
+        let mut ids: Vec<usize> = self.live_data.keys().copied().collect();
+        ids.sort();
+
+        let input = |id: usize| self.live_data.get(&id).and_then(|item| item.as_any().downcast_ref::<Input>());
+        let output = |id: usize| self.live_data.get(&id).and_then(|item| item.as_any().downcast_ref::<Output>());
+        let wire = |id: usize| self.live_data.get(&id).and_then(|item| item.as_any().downcast_ref::<Wire>());
+        let gate = |id: usize| self.live_data.get(&id).and_then(|item| item.as_any().downcast_ref::<Gate>());
+
+        for id in ids {
+            let any = self.live_data[&id].as_any();
+            if let Some(w) = any.downcast_ref::<Wire>() {
+                match output(w.source_id) {
+                    None => issues.push(GraphIssue::new(
+                        format!("Wire {} starts at {}, which is not an output", id, w.source_id),
+                        vec![id],
+                        Repair::Remove(id),
+                    )),
+                    Some(source) if !source.out_wire_ids.contains(&id) => issues.push(GraphIssue::new(
+                        format!("Output {} does not list its wire {}", w.source_id, id),
+                        vec![w.source_id, id],
+                        Repair::AddOutWire(w.source_id, id),
+                    )),
+                    Some(_) => {}
+                }
+                if let Some(dest) = w.dest {
+                    match input(dest) {
+                        None => issues.push(GraphIssue::new(
+                            format!("Wire {} ends at {}, which is not an input", id, dest),
+                            vec![id],
+                            Repair::DisconnectWire(id),
+                        )),
+                        Some(i) => match i.source_wire_id {
+                            Some(wire_id) if wire_id == id => {}
+                            None => issues.push(GraphIssue::new(
+                                format!("Input {} does not know wire {} is plugged into it", dest, id),
+                                vec![dest, id],
+                                Repair::SetInputSource(dest, id),
+                            )),
+                            // a wire that really is plugged in wins, a stale one is cleared when the input is checked
+                            Some(other) if wire(other).is_some_and(|o| o.dest == Some(dest)) => {
+                                issues.push(GraphIssue::new(
+                                    format!("Wire {} ends at input {}, which is taken by wire {}", id, dest, other),
+                                    vec![id, dest, other],
+                                    Repair::DisconnectWire(id),
+                                ))
+                            }
+                            Some(_) => {}
+                        },
+                    }
+                }
+                if w.connected != w.dest.is_some() {
+                    issues.push(GraphIssue::new(
+                        if w.connected {
+                            format!("Wire {} is marked connected but has no input", id)
+                        } else {
+                            format!("Wire {} has an input but is marked unconnected", id)
+                        },
+                        vec![id],
+                        Repair::SetConnected(id, w.dest.is_some()),
+                    ));
+                }
+            } else if let Some(i) = any.downcast_ref::<Input>() {
+                if let Some(wire_id) = i.source_wire_id
+                    && wire(wire_id).is_none_or(|w| w.dest != Some(id))
+                {
+                    issues.push(GraphIssue::new(
+                        format!("Input {} points at wire {}, which is not plugged into it", id, wire_id),
+                        vec![id],
+                        Repair::ClearInputSource(id),
+                    ));
+                }
+                if let Some(parent) = i.parent_id {
+                    match gate(parent) {
+                        None => issues.push(GraphIssue::new(
+                            format!("Input {} belongs to missing gate {}", id, parent),
+                            vec![id],
+                            Repair::Remove(id),
+                        )),
+                        Some(g) if !g.ins.contains_key(&id) => issues.push(GraphIssue::new(
+                            format!("Gate {} does not list its input {}", parent, id),
+                            vec![parent, id],
+                            Repair::AddGatePin(parent, id),
+                        )),
+                        Some(_) => {}
+                    }
+                }
+            } else if let Some(o) = any.downcast_ref::<Output>() {
+                let mut seen = Vec::new();
+                for &wire_id in &o.out_wire_ids {
+                    let message = if wire(wire_id).is_none_or(|w| w.source_id != id) {
+                        format!("Output {} lists wire {}, which does not start there", id, wire_id)
+                    } else if seen.contains(&wire_id) {
+                        format!("Output {} lists wire {} twice", id, wire_id)
+                    } else {
+                        seen.push(wire_id);
+                        continue;
+                    };
+                    issues.push(GraphIssue::new(message, vec![id], Repair::DropOutWire(id, wire_id)));
+                }
+                if let Some(parent) = o.parent_id {
+                    match gate(parent) {
+                        None => issues.push(GraphIssue::new(
+                            format!("Output {} belongs to missing gate {}", id, parent),
+                            vec![id],
+                            Repair::Remove(id),
+                        )),
+                        Some(g) if !g.outs.contains_key(&id) => issues.push(GraphIssue::new(
+                            format!("Gate {} does not list its output {}", parent, id),
+                            vec![parent, id],
+                            Repair::AddGatePin(parent, id),
+                        )),
+                        Some(_) => {}
+                    }
+                }
+            } else if let Some(g) = any.downcast_ref::<Gate>() {
+                let mut pins: Vec<usize> = g.ins.keys().copied().collect();
+                pins.sort();
+                for pin in pins {
+                    if input(pin).is_none_or(|i| i.parent_id != Some(id)) {
+                        issues.push(GraphIssue::new(
+                            format!("Gate {} lists {} as an input, but it is not one of its inputs", id, pin),
+                            vec![id],
+                            Repair::DropGatePin(id, pin),
+                        ));
+                    }
+                }
+                let mut pins: Vec<usize> = g.outs.keys().copied().collect();
+                pins.sort();
+                for pin in pins {
+                    if output(pin).is_none_or(|o| o.parent_id != Some(id)) {
+                        issues.push(GraphIssue::new(
+                            format!("Gate {} lists {} as an output, but it is not one of its outputs", id, pin),
+                            vec![id],
+                            Repair::DropGatePin(id, pin),
+                        ));
+                    }
+                }
+            }
+        }
+        issues
+    }
+
+    /// Fixes every issue `validate_graph` finds, dropping dangling references rather than guessing
+    /// Returns everything that was fixed
+    pub fn repair_graph(&mut self) -> Vec<GraphIssue> {
+        self.invalidate_netlist();
+        let mut fixed = Vec::new();
+        for _ in 0..MAX_REPAIR_PASSES {
+            let issues = self.validate_graph();
+            if issues.is_empty() {
+                break;
+            }
+            for issue in &issues {
+                self.apply_repair(issue.repair);
+            }
+            fixed.extend(issues);
+        }
+        fixed
+    }
+
+    fn apply_repair(&mut self, repair: Repair) {
+        match repair {
+            Repair::Remove(id) => {
+                self.live_data.remove(&id);
+            }
+            Repair::DisconnectWire(id) => {
+                if let Some(wire) = self.item_mut::<Wire>(id) {
+                    wire.dest = None;
+                    wire.connected = false;
+                }
+            }
+            Repair::SetConnected(id, connected) => {
+                if let Some(wire) = self.item_mut::<Wire>(id) {
+                    wire.connected = connected;
+                }
+            }
+            Repair::SetInputSource(id, wire_id) => {
+                if let Some(input) = self.item_mut::<Input>(id) {
+                    input.source_wire_id = Some(wire_id);
+                }
+            }
+            Repair::ClearInputSource(id) => {
+                if let Some(input) = self.item_mut::<Input>(id) {
+                    input.source_wire_id = None;
+                }
+            }
+            Repair::AddOutWire(id, wire_id) => {
+                if let Some(output) = self.item_mut::<Output>(id)
+                    && !output.out_wire_ids.contains(&wire_id)
+                {
+                    output.out_wire_ids.push(wire_id);
+                }
+            }
+            Repair::DropOutWire(id, wire_id) => {
+                let keep = self.live_data.get(&wire_id).is_some_and(|item| {
+                    item.as_any().downcast_ref::<Wire>().is_some_and(|wire| wire.source_id == id)
+                });
+                if let Some(output) = self.item_mut::<Output>(id) {
+                    output.out_wire_ids.retain(|&x| x != wire_id);
+                    if keep {
+                        output.out_wire_ids.push(wire_id); // it was only listed twice
+                    }
+                }
+            }
+            Repair::AddGatePin(id, pin) => {
+                let is_input = self.live_data.get(&pin).is_some_and(|item| item.as_any().is::<Input>());
+                if let Some(gate) = self.get_gate_mut(id) {
+                    if is_input {
+                        gate.ins.insert(pin, false);
+                    } else {
+                        gate.outs.insert(pin, false);
+                    }
+                }
+            }
+            Repair::DropGatePin(id, pin) => {
+                if let Some(gate) = self.get_gate_mut(id) {
+                    gate.ins.remove(&pin);
+                    gate.outs.remove(&pin);
+                }
+            }
+        }
+    }
+
+    fn item_mut<T: 'static>(&mut self, id: usize) -> Option<&mut T> {
+        self.live_data.get_mut(&id)?.as_any_mut().downcast_mut::<T>()
+    }
+
+    /// Called after every edit, counts it so the netlist is compiled again, and in debug builds validates the board
+    /// Inconsistencies are repaired when `auto_repair_graph` is set, otherwise they panic so the bad edit is caught
+    pub fn check_graph_after_edit(&mut self) {
+        self.invalidate_netlist();
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        let issues = self.validate_graph();
+        if issues.is_empty() {
+            return;
+        }
+        let list: Vec<String> = issues.iter().map(|issue| issue.to_string()).collect();
+        if !self.auto_repair_graph {
+            panic!("Board graph is inconsistent after an edit:\n{}", list.join("\n"));
+        }
+        eprintln!("Board graph was inconsistent after an edit, repairing:\n{}", list.join("\n"));
+        self.repair_graph();
+    }
+}
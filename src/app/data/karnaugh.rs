@@ -0,0 +1,513 @@
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+
+use super::*;
+
+/// Karnaugh maps are drawn for at most this many input bits (a 8 by 8 grid)
+pub const MAX_KMAP_INPUTS: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum Cell {
+    Zero,
+    One,
+    DontCare,
+}
+
+impl Cell {
+    /// The value a click on the cell changes it to
+    pub fn next(self) -> Cell {
+        match self {
+            Cell::Zero => Cell::One,
+            Cell::One => Cell::DontCare,
+            Cell::DontCare => Cell::Zero,
+        }
+    }
+}
+
+impl Display for Cell {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Cell::Zero => "0",
+            Cell::One => "1",
+            Cell::DontCare => "X",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+/// A product term: the inputs where `mask` has a 1 must equal the same bits of `bits`, the rest can be anything
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize)]
+pub struct Implicant {
+    pub bits: u32,
+    pub mask: u32,
+}
+
+impl Implicant {
+    pub fn covers(&self, minterm: usize) -> bool {
+        minterm as u32 & self.mask == self.bits
+    }
+
+    pub fn literals(&self) -> u32 {
+        self.mask.count_ones()
+    }
+}
+
+/// The truth table of one output as a grid whose neighbouring cells differ in one input
+/// The first half of the inputs picks the row and the rest the column, both in Gray code order
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct KarnaughMap {
+    pub inputs: Vec<String>, // one per input bit, the first is the most significant bit of a minterm
+    pub output: String,
+    pub cells: Vec<Cell>, // indexed by minterm
+}
+
+/// Bit names of pins, most significant first: `a[1]`, `a[0]`, `b`
+pub fn pin_bit_names(pins: &[PinGroup]) -> Vec<String> {
+    pins.iter()
+        .flat_map(|pin| match pin.width {
+            1 => vec![pin.name.clone()],
+            width => (0..width).rev().map(|bit| format!("{}[{}]", pin.name, bit)).collect(),
+        })
+        .collect()
+}
+
+fn gray(index: usize) -> usize {
+    index ^ (index >> 1)
+}
+
+impl KarnaughMap {
+    /// The map of one output bit, `output` indexes `pin_bit_names(&table.outputs)`
+    /// Rows that never settled become don't-cares
+    pub fn from_truth_table(table: &TruthTable, output: usize) -> Result<Self, String> {
+        let inputs = pin_bit_names(&table.inputs);
+        if inputs.len() > MAX_KMAP_INPUTS {
+            return Err(format!(
+                "{} input bits is too many for a Karnaugh map, at most {} are supported",
+                inputs.len(),
+                MAX_KMAP_INPUTS
+            ));
+        }
+        let outputs = pin_bit_names(&table.outputs);
+        let name = outputs.get(output).ok_or_else(|| format!("No output bit {}", output))?;
+
+        // which pin and bit of that pin the output is
+        let mut pin = 0;
+        let mut bit = output;
+        while bit >= table.outputs[pin].width {
+            bit -= table.outputs[pin].width;
+            pin += 1;
+        }
+        let shift = table.outputs[pin].width - 1 - bit;
+
+        let mut cells = vec![Cell::Zero; 1 << inputs.len()];
+        for row in &table.rows {
+            let minterm = row.inputs.iter().zip(&table.inputs).fold(0, |acc, (value, pin)| (acc << pin.width) | *value as usize);
+            cells[minterm] = match (row.stable, row.outputs[pin] >> shift & 1) {
+                (false, _) => Cell::DontCare,
+                (true, 1) => Cell::One,
+                _ => Cell::Zero,
+            };
+        }
+        Ok(KarnaughMap { inputs, output: name.clone(), cells })
+    }
+
+    /// Inputs that pick the row, the rest pick the column
+    pub fn row_inputs(&self) -> usize {
+        self.inputs.len() / 2
+    }
+
+    pub fn rows(&self) -> usize {
+        1 << self.row_inputs()
+    }
+
+    pub fn columns(&self) -> usize {
+        1 << (self.inputs.len() - self.row_inputs())
+    }
+
+    /// The minterm shown in a cell of the grid
+    pub fn minterm(&self, row: usize, column: usize) -> usize {
+        gray(row) << (self.inputs.len() - self.row_inputs()) | gray(column)
+    }
+
+    /// Label of a row or column: the values of its inputs, e.g. "01"
+    pub fn label(&self, index: usize, width: usize) -> String {
+        (0..width).rev().map(|bit| if gray(index) >> bit & 1 == 1 { '1' } else { '0' }).collect()
+    }
+
+    /// Every product term that covers only 1s and don't-cares and can't be made any larger (Quine-McCluskey)
+    /// Terms covering nothing but don't-cares are left out
+    pub fn prime_implicants(&self) -> Vec<Implicant> {
+        let full = (1u32 << self.inputs.len()) - 1;
+        let mut current: HashSet<Implicant> = (0..self.cells.len())
+            .filter(|minterm| self.cells[*minterm] != Cell::Zero)
+            .map(|minterm| Implicant { bits: minterm as u32, mask: full })
+            .collect();
+        let mut primes: HashSet<Implicant> = HashSet::new();
+        while !current.is_empty() {
+            let mut merged: HashSet<Implicant> = HashSet::new();
+            let mut used: HashSet<Implicant> = HashSet::new();
+            let terms: Vec<Implicant> = current.iter().copied().collect();
+            for (index, a) in terms.iter().enumerate() {
+                for b in &terms[index + 1..] {
+                    let difference = a.bits ^ b.bits;
+                    if a.mask == b.mask && difference.count_ones() == 1 {
+                        merged.insert(Implicant { bits: a.bits & !difference, mask: a.mask & !difference });
+                        used.insert(*a);
+                        used.insert(*b);
+                    }
+                }
+            }
+            primes.extend(current.difference(&used));
+            current = merged;
+        }
+        let mut primes: Vec<Implicant> = primes
+            .into_iter()
+            .filter(|prime| (0..self.cells.len()).any(|minterm| self.cells[minterm] == Cell::One && prime.covers(minterm)))
+            .collect();
+        primes.sort_by_key(|prime| (prime.literals(), prime.mask, prime.bits));
+        primes
+    }
+
+    /// The fewest prime implicants that cover every 1, with the fewest literals among those
+    /// Very large maps may get a cover that is only close to minimal, see `COVER_SEARCH_STEPS`
+    pub fn minimal_cover(&self) -> Vec<Implicant> {
+        let primes = self.prime_implicants();
+        let ones: Vec<usize> = (0..self.cells.len()).filter(|minterm| self.cells[*minterm] == Cell::One).collect();
+        let covering = ones.iter().map(|one| (0..primes.len()).filter(|prime| primes[*prime].covers(*one)).collect()).collect();
+        let mut search = CoverSearch { primes: &primes, ones, covering, best: Vec::new(), steps: COVER_SEARCH_STEPS };
+        search.greedy();
+        let all: Vec<usize> = (0..search.ones.len()).collect();
+        search.search(&all, &mut Vec::new());
+        let mut cover: Vec<Implicant> = search.best.iter().map(|prime| primes[*prime]).collect();
+        cover.sort_by_key(|term| (term.literals(), std::cmp::Reverse(term.mask), term.bits));
+        cover
+    }
+
+    /// A product term written with the input names, e.g. "a & !b"
+    pub fn term(&self, term: &Implicant) -> String {
+        let n = self.inputs.len();
+        let literals: Vec<String> = (0..n)
+            .filter(|index| term.mask >> (n - 1 - index) & 1 == 1)
+            .map(|index| match term.bits >> (n - 1 - index) & 1 {
+                1 => self.inputs[index].clone(),
+                _ => format!("!{}", self.inputs[index]),
+            })
+            .collect();
+        match literals.is_empty() {
+            true => "1".to_string(),
+            false => literals.join(" & "),
+        }
+    }
+
+    /// A sum of products written with the input names, e.g. "a & !b | c"
+    pub fn expression(&self, cover: &[Implicant]) -> String {
+        match cover.is_empty() {
+            true => "0".to_string(),
+            false => cover.iter().map(|term| self.term(term)).collect::<Vec<_>>().join(" | "),
+        }
+    }
+}
+
+/// Steps the cover search may take before settling for the best cover found so far
+pub const COVER_SEARCH_STEPS: usize = 200_000;
+
+/// Branch and bound over the primes for the fewest of them that cover every 1, then the fewest literals
+struct CoverSearch<'a> {
+    primes: &'a [Implicant],
+    ones: Vec<usize>,
+    covering: Vec<Vec<usize>>, // per 1, the primes covering it
+    best: Vec<usize>,          // indexes into primes
+    steps: usize,
+}
+
+impl CoverSearch<'_> {
+    fn literals(&self, chosen: &[usize]) -> u32 {
+        chosen.iter().map(|prime| self.primes[*prime].literals()).sum()
+    }
+
+    fn better(&self, chosen: &[usize]) -> bool {
+        (chosen.len(), self.literals(chosen)) < (self.best.len(), self.literals(&self.best))
+    }
+
+    /// Repeatedly takes the prime covering the most 1s still uncovered, as the bound to beat
+    fn greedy(&mut self) {
+        let mut uncovered: Vec<usize> = (0..self.ones.len()).collect();
+        while !uncovered.is_empty() {
+            let gain = |prime: usize| uncovered.iter().filter(|one| self.covering[**one].contains(&prime)).count();
+            let Some(prime) = self.covering[uncovered[0]].iter().copied().max_by_key(|prime| (gain(*prime), std::cmp::Reverse(self.primes[*prime].literals()))) else {
+                return;
+            };
+            uncovered.retain(|one| !self.covering[*one].contains(&prime));
+            self.best.push(prime);
+        }
+    }
+
+    /// `uncovered` are indexes into ones
+    fn search(&mut self, uncovered: &[usize], chosen: &mut Vec<usize>) {
+        if self.steps == 0 {
+            return;
+        }
+        self.steps -= 1;
+        if uncovered.is_empty() {
+            if self.better(chosen) {
+                self.best = chosen.clone();
+            }
+            return;
+        }
+
+        // 1s that share no prime each need a prime of their own, the one with the fewest primes first
+        let mut sorted = uncovered.to_vec();
+        sorted.sort_by_key(|one| self.covering[*one].len());
+        let mut used = vec![false; self.primes.len()];
+        let mut independent = 0;
+        for one in &sorted {
+            if self.covering[*one].iter().all(|prime| !used[*prime]) {
+                independent += 1;
+                for prime in &self.covering[*one] {
+                    used[*prime] = true;
+                }
+            }
+        }
+        let bound = chosen.len() + independent;
+        if bound > self.best.len() || (bound == self.best.len() && self.literals(chosen) >= self.literals(&self.best)) {
+            return;
+        }
+
+        let hardest = sorted[0];
+        let mut options = self.covering[hardest].clone();
+        options.sort_by_key(|prime| std::cmp::Reverse(uncovered.iter().filter(|one| self.covering[**one].contains(prime)).count()));
+        for prime in options {
+            let rest: Vec<usize> = uncovered.iter().copied().filter(|one| !self.covering[*one].contains(&prime)).collect();
+            chosen.push(prime);
+            self.search(&rest, chosen);
+            chosen.pop();
+        }
+    }
+}
+
+impl Display for KarnaughMap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let row_inputs = self.row_inputs();
+        let column_inputs = self.inputs.len() - row_inputs;
+        let row_names = self.inputs[..row_inputs].join(",");
+        let width = row_names.len().max(row_inputs);
+        writeln!(f, "{}", self.output)?;
+        writeln!(f, "{:width$} | {}", "", self.inputs[row_inputs..].join(","))?;
+        let labels: Vec<String> = (0..self.columns()).map(|column| format!("{:>w$}", self.label(column, column_inputs), w = column_inputs.max(1))).collect();
+        writeln!(f, "{:width$} | {}", row_names, labels.join(" "))?;
+        for row in 0..self.rows() {
+            let cells: Vec<String> = (0..self.columns())
+                .map(|column| format!("{:>w$}", self.cells[self.minterm(row, column)], w = column_inputs.max(1)))
+                .collect();
+            writeln!(f, "{:width$} | {}", self.label(row, row_inputs), cells.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds sums of products out of NOT, AND and OR gates, placed in columns of `LAYOUT_SPACING` from `origin`
+/// Every signal is inverted at most once, the NOT is shared by all sums built with the same builder
+pub struct SopBuilder {
+    pub origin: Pos2,
+    pub logic_column: usize, // column of the NOTs, the ANDs and ORs take the two after it
+    pub added: usize, // gates made so far
+    placed: Vec<usize>, // gates per column
+    inverted: HashMap<usize, usize>, // output -> output of the NOT reading it
+}
+
+impl SopBuilder {
+    pub fn new(origin: Pos2) -> Self {
+        SopBuilder { origin, logic_column: 0, added: 0, placed: Vec::new(), inverted: HashMap::new() }
+    }
+
+    /// Adds a gate below the others of its column with `ins` plugged into its inputs
+    /// Returns the gate, the first output is `data.gate_outputs(gate)[0]`
+    pub fn gate(&mut self, data: &mut Data, kind: PrimitiveKind, column: usize, ins: &[usize]) -> usize {
+        if self.placed.len() <= column {
+            self.placed.resize(column + 1, 0);
+        }
+        let pos = self.origin + egui::vec2(column as f32 * LAYOUT_SPACING.x, self.placed[column] as f32 * LAYOUT_SPACING.y);
+        self.placed[column] += 1;
+        self.added += 1;
+        let gate = data.add_primitive(kind, pos);
+        for (output, input) in ins.iter().zip(data.gate_inputs(gate)) {
+            data.connect(*output, input).expect("a new gate has nothing plugged in");
+        }
+        gate
+    }
+
+    fn output(&mut self, data: &mut Data, kind: PrimitiveKind, column: usize, ins: &[usize]) -> usize {
+        let gate = self.gate(data, kind, column, ins);
+        data.gate_outputs(gate)[0]
+    }
+
+    /// The output of a NOT reading `signal`
+    pub fn not(&mut self, data: &mut Data, signal: usize) -> usize {
+        if let Some(not) = self.inverted.get(&signal) {
+            return *not;
+        }
+        let not = self.output(data, PrimitiveKind::NOT, self.logic_column, &[signal]);
+        self.inverted.insert(signal, not);
+        not
+    }
+
+    /// Gates computing `cover` from 2-input ANDs and ORs, with HISIGNAL or LOSIGNAL for the constants
+    /// `signals` are the outputs carrying the variables of the cover, most significant first
+    /// Returns the output carrying the sum
+    pub fn build(&mut self, data: &mut Data, cover: &[Implicant], signals: &[usize]) -> usize {
+        let n = signals.len();
+        let (and_column, or_column) = (self.logic_column + 1, self.logic_column + 2);
+        let mut terms: Vec<usize> = Vec::new();
+        for term in cover {
+            let mut literals: Vec<usize> = Vec::new();
+            for (index, signal) in signals.iter().enumerate().filter(|(index, _)| term.mask >> (n - 1 - index) & 1 == 1) {
+                literals.push(match term.bits >> (n - 1 - index) & 1 {
+                    1 => *signal,
+                    _ => self.not(data, *signal),
+                });
+            }
+            let term = match literals.split_first() {
+                None => self.output(data, PrimitiveKind::HISIGNAL, and_column, &[]),
+                Some((first, rest)) => rest.iter().fold(*first, |acc, literal| self.output(data, PrimitiveKind::AND, and_column, &[acc, *literal])),
+            };
+            terms.push(term);
+        }
+        match terms.split_first() {
+            None => self.output(data, PrimitiveKind::LOSIGNAL, or_column, &[]),
+            Some((first, rest)) => rest.iter().fold(*first, |acc, term| self.output(data, PrimitiveKind::OR, or_column, &[acc, *term])),
+        }
+    }
+}
+
+impl Data {
+    /// Rebuilds the logic driving a map's output LIGHT as its minimal sum of products, from NOT, AND and OR gates
+    /// Gates that only fed that LIGHT are removed, logic shared with other outputs is kept
+    /// Returns how many gates were added
+    pub fn replace_with_minimized(&mut self, map: &KarnaughMap) -> Result<usize, CircuitError> {
+        let pin = |kind: PrimitiveKind, name: &str| {
+            self.live_data
+                .values()
+                .filter_map(|item| item.as_any().downcast_ref::<Gate>())
+                // a bus of one bit is a pin without the index, `b[0]` is named `b`
+                .find(|gate| (gate.name == name || split_bus_name(&gate.name) == Some((name, 0))) && gate.kind == GateKind::Primitive(kind.clone()))
+                .map(|gate| gate.id)
+                .ok_or_else(|| CircuitError::Unsupported { id: None, message: format!("No {} pin named {} on the board", kind, name) })
+        };
+        let light = pin(PrimitiveKind::LIGHT, &map.output)?;
+        let toggles: Vec<usize> = map.inputs.iter().map(|name| pin(PrimitiveKind::TOGGLE, name)).collect::<Result<_, _>>()?;
+        let light_input = *self.gate_inputs(light).first().ok_or(CircuitError::ArityMismatch {
+            id: light,
+            kind: PrimitiveKind::LIGHT.to_string(),
+            pins: "inputs",
+            expected: 1,
+            found: 0,
+        })?;
+        let _ids = self.ids.enter();
+        let cone = self.driving_gates(&[light]);
+
+        // NOTs in the third column left of the LIGHT, ANDs in the second and ORs in the first
+        let light_pos = self.get_gate_mut(light).map_or(Pos2::ZERO, |gate| gate.position.to_pos2());
+        let mut builder = SopBuilder::new(light_pos - egui::vec2(3.0 * LAYOUT_SPACING.x, 0.0));
+        let signals: Vec<usize> = toggles.iter().map(|toggle| self.gate_outputs(*toggle)[0]).collect();
+        let result = builder.build(self, &map.minimal_cover(), &signals);
+
+        if let Some(wire) = self.get_input_mut(light_input).and_then(|input| input.source_wire_id) {
+            self.remove_wire(wire);
+        }
+        self.connect(result, light_input).expect("the LIGHT was just unplugged");
+
+        let lights: Vec<usize> = self
+            .live_data
+            .values()
+            .filter_map(|item| item.as_any().downcast_ref::<Gate>())
+            .filter(|gate| gate.kind == GateKind::Primitive(PrimitiveKind::LIGHT))
+            .map(|gate| gate.id)
+            .collect();
+        let live = self.driving_gates(&lights);
+        let mut dead: Vec<usize> = cone.difference(&live).copied().collect();
+        dead.sort();
+        for gate in dead {
+            self.remove_gate(gate);
+        }
+        self.check_graph_after_edit();
+        Ok(builder.added)
+    }
+
+    /// Every gate some of `gates` read from, directly or through other gates, pins left out
+    fn driving_gates(&self, gates: &[usize]) -> HashSet<usize> {
+        let mut found: HashSet<usize> = HashSet::new();
+        let mut stack: Vec<usize> = gates.to_vec();
+        while let Some(gate) = stack.pop() {
+            for input in self.gate_inputs(gate) {
+                let Some(source) = self.source_output(input).and_then(|output| self.live_data.get(&output)?.as_any().downcast_ref::<Output>()?.parent_id) else {
+                    continue;
+                };
+                let is_pin = self
+                    .live_data
+                    .get(&source)
+                    .and_then(|item| item.as_any().downcast_ref::<Gate>())
+                    .is_some_and(|gate| matches!(gate.kind, GateKind::Primitive(PrimitiveKind::TOGGLE | PrimitiveKind::PULSE)));
+                if !is_pin && found.insert(source) {
+                    stack.push(source);
+                }
+            }
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A map over inputs named a, b, c, ... with 1s and don't-cares at the given minterms
+    fn map(inputs: usize, ones: &[usize], dont_cares: &[usize]) -> KarnaughMap {
+        let mut cells = vec![Cell::Zero; 1 << inputs];
+        for minterm in ones {
+            cells[*minterm] = Cell::One;
+        }
+        for minterm in dont_cares {
+            cells[*minterm] = Cell::DontCare;
+        }
+        let inputs = (b'a'..).take(inputs).map(|name| (name as char).to_string()).collect();
+        KarnaughMap { inputs, output: "f".to_string(), cells }
+    }
+
+    /// True if the cover has every 1 and no 0 of the map
+    fn covers_exactly(map: &KarnaughMap, cover: &[Implicant]) -> bool {
+        map.cells.iter().enumerate().all(|(minterm, cell)| match cell {
+            Cell::One => cover.iter().any(|term| term.covers(minterm)),
+            Cell::Zero => !cover.iter().any(|term| term.covers(minterm)),
+            Cell::DontCare => true,
+        })
+    }
+
+    #[test]
+    fn known_minimal_cover() {
+        let map = map(4, &[0, 1, 2, 5, 8, 9, 10], &[]);
+        let cover = map.minimal_cover();
+        assert_eq!(map.expression(&cover), "!b & !c | !b & !d | !a & !c & d");
+        assert!(covers_exactly(&map, &cover));
+    }
+
+    #[test]
+    fn dont_cares_make_terms_larger() {
+        let with_dont_cares = map(3, &[0, 2], &[4, 6]);
+        assert_eq!(with_dont_cares.expression(&with_dont_cares.minimal_cover()), "!c");
+        // a term of nothing but don't-cares is never a prime worth having
+        let only_dont_cares = map(2, &[], &[3]);
+        assert!(only_dont_cares.prime_implicants().is_empty());
+        assert_eq!(only_dont_cares.expression(&only_dont_cares.minimal_cover()), "0");
+    }
+
+    #[test]
+    fn cyclic_map_needs_three_terms() {
+        // every 1 is covered by two primes and none of the six primes is essential
+        let map = map(3, &[0, 1, 2, 5, 6, 7], &[]);
+        assert_eq!(map.prime_implicants().len(), 6);
+        let cover = map.minimal_cover();
+        assert_eq!(cover.len(), 3);
+        assert_eq!(cover.iter().map(Implicant::literals).sum::<u32>(), 6);
+        assert!(covers_exactly(&map, &cover));
+    }
+}
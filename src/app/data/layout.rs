@@ -0,0 +1,102 @@
+use crate::gate::GridVec2;
+
+use super::*;
+
+/// Distance between the columns and between the rows of `auto_layout`
+pub const LAYOUT_SPACING: egui::Vec2 = egui::vec2(220.0, 140.0);
+
+impl Data {
+    /// Places every gate in columns by how many gates it is behind the inputs, signals flowing left to right
+    /// TOGGLE pins take the first column and LIGHT pins the last, both in pin order
+    /// Within a column, gates sit near the average row of the gates they read from
+    /// The board stays centered where it was
+    pub fn auto_layout(&mut self) {
+        let gates: Vec<(usize, Pos2, Option<PrimitiveKind>)> = {
+            let mut gates: Vec<_> = self
+                .live_data
+                .values()
+                .filter_map(|item| item.as_any().downcast_ref::<Gate>())
+                .map(|gate| {
+                    let kind = match &gate.kind {
+                        GateKind::Primitive(kind) => Some(kind.clone()),
+                        _ => None,
+                    };
+                    (gate.id, gate.position.to_pos2(), kind)
+                })
+                .collect();
+            gates.sort_by_key(|(id, _, _)| *id);
+            gates
+        };
+        if gates.is_empty() {
+            return;
+        }
+        let center = gates.iter().fold(egui::Vec2::ZERO, |sum, (_, pos, _)| sum + pos.to_vec2()) / gates.len() as f32;
+
+        let mut predecessors: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (from, to, _) in gate_edges(&self.live_data) {
+            predecessors.entry(to).or_default().push(from);
+        }
+        let column_after = |columns: &HashMap<usize, usize>, gate: usize| {
+            predecessors.get(&gate).into_iter().flatten().filter_map(|pred| columns.get(pred)).map(|column| column + 1).max()
+        };
+        // gates in order first, then gates in feedback loops behind whatever they read that is placed
+        let mut columns: HashMap<usize, usize> = HashMap::new();
+        for gate in gate_order(&self.live_data) {
+            let column = column_after(&columns, gate).unwrap_or(0);
+            columns.insert(gate, column);
+        }
+        for (gate, _, _) in &gates {
+            if !columns.contains_key(gate) {
+                let column = column_after(&columns, *gate).unwrap_or(0);
+                columns.insert(*gate, column);
+            }
+        }
+        let (input_pins, output_pins) = self.pin_groups();
+        let pin_rank: HashMap<usize, usize> = input_pins
+            .iter()
+            .chain(&output_pins)
+            .flat_map(|pin| self.find_pin(&pin.name))
+            .enumerate()
+            .map(|(rank, id)| (id, rank))
+            .collect();
+        let last = columns.values().max().copied().unwrap_or(0).max(1);
+        for (gate, _, kind) in &gates {
+            match kind {
+                Some(PrimitiveKind::TOGGLE) => columns.insert(*gate, 0),
+                Some(PrimitiveKind::LIGHT) => columns.insert(*gate, last),
+                _ => None,
+            };
+        }
+
+        let mut by_column: Vec<Vec<usize>> = vec![Vec::new(); last + 1];
+        for (gate, _, _) in &gates {
+            by_column[columns[gate]].push(*gate);
+        }
+        let mut rows: HashMap<usize, f32> = HashMap::new();
+        for column in &mut by_column {
+            let key = |gate: &usize| -> (usize, f32) {
+                if let Some(rank) = pin_rank.get(gate) {
+                    return (0, *rank as f32);
+                }
+                let placed: Vec<f32> = predecessors.get(gate).into_iter().flatten().filter_map(|pred| rows.get(pred)).copied().collect();
+                match placed.is_empty() {
+                    true => (2, *gate as f32),
+                    false => (1, placed.iter().sum::<f32>() / placed.len() as f32),
+                }
+            };
+            column.sort_by(|a, b| key(a).partial_cmp(&key(b)).unwrap_or(std::cmp::Ordering::Equal).then(a.cmp(b)));
+            let middle = (column.len() as f32 - 1.0) / 2.0;
+            for (row, gate) in column.iter().enumerate() {
+                rows.insert(*gate, row as f32 - middle);
+            }
+        }
+
+        let middle = last as f32 / 2.0;
+        for (gate, column) in columns {
+            let offset = egui::vec2((column as f32 - middle) * LAYOUT_SPACING.x, rows[&gate] * LAYOUT_SPACING.y);
+            if let Some(gate) = self.get_gate_mut(gate) {
+                gate.position = GridVec2::from(Pos2::ZERO + center + offset);
+            }
+        }
+    }
+}
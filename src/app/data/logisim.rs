@@ -0,0 +1,840 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+use eframe::egui::{Pos2, Vec2};
+
+use super::{ChipDefenition, Data, PrimitiveKind, chip_pins};
+
+/// Logisim coordinates are scaled by this factor when placed on the PanArea
+const LOGISIM_SCALE: f32 = 2.0;
+
+/// Where the top left corner of an imported circuit ends up in world space
+const IMPORT_ORIGIN: Pos2 = Pos2::new(300.0, 300.0);
+
+/// Horizontal distance between a gate's center and its pins on the PanArea
+const PIN_OFFSET: f32 = 50.0;
+
+type Point = (i32, i32);
+
+/// Everything translated out of a Logisim `.circ` file
+/// Every Logisim circuit becomes a chip, `main` names the one Logisim opens first
+pub struct LogisimImport {
+    pub main: String,
+    pub circuits: Vec<ChipDefenition>,
+    pub untranslated: Vec<UntranslatedComponent>,
+}
+
+/// A component that could not be (fully) translated, with the reason why
+#[derive(Debug, Clone)]
+pub struct UntranslatedComponent {
+    pub circuit: String,
+    pub name: String,
+    pub location: Point,
+    pub reason: String,
+}
+
+impl Display for UntranslatedComponent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} at ({}, {}): {}",
+            self.circuit, self.name, self.location.0, self.location.1, self.reason
+        )
+    }
+}
+
+/// A Logisim component that maps onto one of our primitives,
+/// with its connection points in Logisim coordinates
+struct PlacedComponent {
+    kind: PrimitiveKind,
+    label: Option<String>,
+    outputs: Vec<Point>,
+    inputs: Vec<Point>,
+    facing: Option<String>, // only for pins, it decides where they show up on a subcircuit's box
+}
+
+/// How a circuit's pins are laid out around its box when it is used as a subcircuit
+enum Appearance {
+    /// Logisim 2.x, every pin sits on the side opposite its facing
+    Classic,
+    /// Logisim-evolution, inputs on the west side and outputs on the east side, 20 apart
+    Evolution,
+    /// Drawn by hand, the ports are stored as (pin location in the circuit, port location on the box)
+    Custom { anchor: Point, ports: Vec<(Point, Point)> },
+}
+
+/// A pin of a subcircuit, relative to the instance's location for an east facing instance
+/// Pins on the west side of a Logisim-evolution box move left with its width, which depends on the label text
+#[derive(Clone, Copy)]
+struct Port {
+    offset: Point,
+    west: bool,
+}
+
+/// A translated circuit, with the port of every pin gate on its chip
+#[derive(Clone)]
+struct Converted {
+    chip: ChipDefenition,
+    ports: HashMap<usize, Port>,
+    stretches: bool, // the box width is unknown, see `Port`
+}
+
+/// Widest Logisim-evolution box tried when looking for the west side of a subcircuit
+const MAX_BOX_WIDTH: i32 = 600;
+
+/// A raw `<comp>` element
+struct CompElement {
+    lib: Option<String>,
+    name: String,
+    loc: Point,
+    attrs: HashMap<String, String>,
+}
+
+struct CircuitElement {
+    name: String,
+    wires: Vec<(Point, Point)>,
+    comps: Vec<CompElement>,
+    appearance: Appearance,
+}
+
+impl LogisimImport {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let content = std::fs::read_to_string(path)?;
+        LogisimImport::parse(&content)
+    }
+
+    pub fn parse(xml: &str) -> Result<Self, Box<dyn Error>> {
+        let doc = roxmltree::Document::parse(xml)?;
+        let project = doc.root_element();
+        if project.tag_name().name() != "project" {
+            return Err("Not a Logisim project file".into());
+        }
+
+        // Logisim 2.x gates default to 5 inputs, Logisim-evolution gates default to 2
+        let legacy = project.attribute("source").is_some_and(|v| v.starts_with("2."));
+
+        let mut circuits = Vec::new();
+        for circuit in project.children().filter(|n| n.has_tag_name("circuit")) {
+            let name = circuit.attribute("name").unwrap_or("main").to_string();
+            let mut element = CircuitElement {
+                name,
+                wires: Vec::new(),
+                comps: Vec::new(),
+                appearance: if legacy { Appearance::Classic } else { Appearance::Evolution },
+            };
+            // a drawn appearance is used unless the circuit was switched back to a standard one
+            let mut standard = None;
+            let mut custom = None;
+            for child in circuit.children().filter(|n| n.is_element()) {
+                match child.tag_name().name() {
+                    "a" if child.attribute("name") == Some("appearance") => match child.attribute("val") {
+                        Some("classic") => standard = Some(Appearance::Classic),
+                        Some("logisim_evolution") => standard = Some(Appearance::Evolution),
+                        _ => {}
+                    },
+                    "appear" => custom = parse_appearance(child)?,
+                    "wire" => {
+                        let from = parse_point(child.attribute("from").unwrap_or_default())?;
+                        let to = parse_point(child.attribute("to").unwrap_or_default())?;
+                        element.wires.push((from, to));
+                    }
+                    "comp" => {
+                        let attrs = child
+                            .children()
+                            .filter(|n| n.has_tag_name("a"))
+                            .filter_map(|a| {
+                                let val = a.attribute("val").or(a.text()).unwrap_or_default();
+                                Some((a.attribute("name")?.to_string(), val.to_string()))
+                            })
+                            .collect();
+                        element.comps.push(CompElement {
+                            lib: child.attribute("lib").map(str::to_string),
+                            name: child.attribute("name").unwrap_or_default().to_string(),
+                            loc: parse_point(child.attribute("loc").unwrap_or_default())?,
+                            attrs,
+                        });
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(appearance) = standard.or(custom) {
+                element.appearance = appearance;
+            }
+            circuits.push(element);
+        }
+        if circuits.is_empty() {
+            return Err("Logisim file contains no circuits".into());
+        }
+
+        let main = project
+            .children()
+            .find(|n| n.has_tag_name("main"))
+            .and_then(|n| n.attribute("name"))
+            .unwrap_or(&circuits[0].name)
+            .to_string();
+
+        let mut import = LogisimImport {
+            main,
+            circuits: Vec::new(),
+            untranslated: Vec::new(),
+        };
+        let mut done = HashMap::new();
+        for circuit in &circuits {
+            import.convert_circuit(&circuit.name, &circuits, &mut done, legacy)?;
+        }
+        import.circuits = circuits.iter().filter_map(|c| done.get(&c.name).map(|converted| converted.chip.clone())).collect();
+        Ok(import)
+    }
+
+    pub fn main_chip(&self) -> Option<&ChipDefenition> {
+        self.circuits.iter().find(|c| c.name == self.main)
+    }
+
+    /// Converts one circuit into a chip, converting any subcircuits it uses first
+    /// Subcircuits become custom gates wired to the ports on their box, flattening swaps them for their chip
+    fn convert_circuit(
+        &mut self,
+        name: &str,
+        circuits: &[CircuitElement],
+        done: &mut HashMap<String, Converted>,
+        legacy: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        if done.contains_key(name) {
+            return Ok(());
+        }
+        let circuit = circuits
+            .iter()
+            .find(|c| c.name == name)
+            .ok_or_else(|| format!("Unknown subcircuit {}", name))?;
+
+        // Sort components into primitives and subcircuit instances
+        let mut placed = Vec::new();
+        let mut sub_chips = Vec::new();
+        for comp in &circuit.comps {
+            let is_subcircuit = comp.lib.is_none() && circuits.iter().any(|c| c.name == comp.name);
+            if is_subcircuit {
+                if comp.name == name {
+                    return Err(format!("Circuit {} contains itself", name).into());
+                }
+                self.convert_circuit(&comp.name, circuits, done, legacy)?;
+                sub_chips.push(comp);
+                continue;
+            }
+            match translate_component(comp, legacy) {
+                Ok(Some(component)) => placed.push(component),
+                Ok(None) => {}
+                Err(reason) => self.untranslated.push(UntranslatedComponent {
+                    circuit: name.to_string(),
+                    name: comp.name.clone(),
+                    location: comp.loc,
+                    reason,
+                }),
+            }
+        }
+
+        name_pins(&mut placed);
+
+        // Where every subcircuit instance has the pins of its chip
+        let mut instances = Vec::new();
+        for comp in sub_chips {
+            let converted = &done[&comp.name];
+            let (ins, outs) = chip_pins(&converted.chip);
+            let facing = comp.attrs.get("facing").map_or("east", String::as_str);
+            let width = converted.box_width(&ins, comp.loc, facing, &circuit.wires);
+            let locate = |pins: Vec<usize>| {
+                pins.into_iter()
+                    .map(|pin| converted.port_at(pin, comp.loc, facing, width))
+                    .collect()
+            };
+            instances.push(Instance {
+                name: comp.name.clone(),
+                loc: comp.loc,
+                inputs: locate(ins),
+                outputs: locate(outs),
+            });
+        }
+
+        // Every point that matters for connectivity, used to map coordinates and to build nets
+        let mut points: HashSet<Point> = HashSet::new();
+        for (a, b) in &circuit.wires {
+            points.insert(*a);
+            points.insert(*b);
+        }
+        for component in &placed {
+            points.extend(component.outputs.iter().chain(&component.inputs));
+        }
+        for instance in &instances {
+            points.extend(instance.inputs.iter().chain(&instance.outputs).flatten());
+        }
+        let min = points
+            .iter()
+            .chain(circuit.comps.iter().map(|c| &c.loc))
+            .fold((i32::MAX, i32::MAX), |acc, p| (acc.0.min(p.0), acc.1.min(p.1)));
+        let to_world = |p: Point| {
+            IMPORT_ORIGIN + Vec2::new((p.0 - min.0) as f32, (p.1 - min.1) as f32) * LOGISIM_SCALE
+        };
+
+        let nets = Nets::build(&points, &circuit.wires);
+
+        let mut data = Data::new();
+        let mut drivers: HashMap<Point, Vec<(usize, Point)>> = HashMap::new(); // net root -> (output id, pin point)
+        let mut sinks: Vec<(Point, usize)> = Vec::new();
+        let mut pins = Vec::new(); // (gate id, pin) for laying out this circuit's own box
+        for mut component in placed {
+            // Drop gate inputs that nothing is attached to, so gates with unused extra inputs still fit our primitives
+            let max_inputs = component.kind.get_n_desired_inputs();
+            if component.inputs.len() > max_inputs {
+                component.inputs.retain(|p| nets.is_attached(*p));
+                if component.inputs.len() > max_inputs {
+                    self.untranslated.push(UntranslatedComponent {
+                        circuit: name.to_string(),
+                        name: component.kind.to_string(),
+                        location: component.outputs.first().copied().unwrap_or_default(),
+                        reason: format!(
+                            "{} inputs are connected but {} supports at most {}",
+                            component.inputs.len(),
+                            component.kind,
+                            max_inputs
+                        ),
+                    });
+                    continue;
+                }
+            }
+
+            let pos = match (component.outputs.first(), component.inputs.first()) {
+                (Some(out), _) => to_world(*out) - Vec2::new(PIN_OFFSET, 0.0),
+                (None, Some(input)) => to_world(*input) + Vec2::new(PIN_OFFSET, 0.0),
+                (None, None) => continue,
+            };
+            let gate_id = data.add_primitive(component.kind.clone(), pos);
+
+            // Remove the inputs that were dropped above
+            let removed = data.gate_inputs(gate_id).split_off(component.inputs.len());
+            for id in &removed {
+                data.live_data.remove(id);
+            }
+            if let Some(gate) = data.get_gate_mut(gate_id) {
+                if let Some(label) = component.label.clone() {
+                    gate.name = label;
+                }
+                gate.n_in = component.inputs.len();
+                gate.ins.retain(|id, _| !removed.contains(id));
+            }
+
+            for (point, output_id) in component.outputs.iter().zip(data.gate_outputs(gate_id)) {
+                drivers.entry(nets.root(*point)).or_default().push((output_id, *point));
+            }
+            for (point, input_id) in component.inputs.iter().zip(data.gate_inputs(gate_id)) {
+                sinks.push((*point, input_id));
+            }
+            if component.facing.is_some() {
+                pins.push((gate_id, component));
+            }
+        }
+
+        for instance in instances {
+            let pos = to_world(instance.loc) - Vec2::new(PIN_OFFSET, 0.0);
+            let gate_id = data.add_custom_gate(&instance.name, instance.inputs.len(), instance.outputs.len(), pos);
+            // pins without a port on the box (LEDs inside the subcircuit) stay unconnected
+            for (point, output_id) in instance.outputs.iter().zip(data.gate_outputs(gate_id)) {
+                if let Some(point) = point {
+                    drivers.entry(nets.root(*point)).or_default().push((output_id, *point));
+                }
+            }
+            for (point, input_id) in instance.inputs.iter().zip(data.gate_inputs(gate_id)) {
+                if let Some(point) = point {
+                    sinks.push((*point, input_id));
+                }
+            }
+        }
+
+        // Connect every sink to the single driver on its net, following the Logisim wire segments
+        for (point, input_id) in sinks {
+            let Some(net_drivers) = drivers.get(&nets.root(point)) else {
+                continue; // nothing drives this net, the input simply reads false
+            };
+            if net_drivers.len() > 1 {
+                self.untranslated.push(UntranslatedComponent {
+                    circuit: name.to_string(),
+                    name: "wire".to_string(),
+                    location: point,
+                    reason: format!("net is driven by {} outputs", net_drivers.len()),
+                });
+                continue;
+            }
+            let (output_id, driver_point) = net_drivers[0];
+            let wire_id = data.connect(output_id, input_id)?;
+            if let Some(wire) = data.get_wire_mut(wire_id) {
+                wire.line.waypoints = nets.bends(driver_point, point).into_iter().map(to_world).collect();
+            }
+        }
+
+        let chip = ChipDefenition::from_live_data(&data.live_data, name.to_string());
+        done.insert(name.to_string(), Converted::new(chip, &circuit.appearance, &pins));
+        Ok(())
+    }
+}
+
+/// A subcircuit placed in a circuit, with where each of its chip's pins ended up
+/// None for pins that have no port on the subcircuit's box
+struct Instance {
+    name: String,
+    loc: Point,
+    inputs: Vec<Option<Point>>,
+    outputs: Vec<Option<Point>>,
+}
+
+impl Converted {
+    /// Lays out the ports of a circuit's pins the way Logisim draws its box when it is used as a subcircuit
+    fn new(chip: ChipDefenition, appearance: &Appearance, pins: &[(usize, PlacedComponent)]) -> Self {
+        let mut ports = HashMap::new();
+        let mut stretches = false;
+        match appearance {
+            Appearance::Classic => {
+                // the sides in Logisim's order, every pin sits opposite its facing
+                let mut sides: [Vec<(usize, Point)>; 4] = Default::default(); // west, east, north, south
+                for (id, pin) in pins {
+                    let side = match pin.facing.as_deref() {
+                        Some("west") => 1,
+                        Some("south") => 2,
+                        Some("north") => 3,
+                        _ => 0,
+                    };
+                    sides[side].push((*id, pin.location()));
+                }
+                for (side, list) in sides.iter_mut().enumerate() {
+                    list.sort_by_key(|(_, (x, y))| if side < 2 { (*y, *x) } else { (*x, *y) });
+                }
+                let count = |side: usize| sides[side].len() as i32;
+                let (west, east, north, south) = (count(0), count(1), count(2), count(3));
+                let (vertical, horizontal) = (north.max(south), east.max(west));
+                let offset = |this: i32, opposite: i32, others: i32| {
+                    let most = this.max(opposite);
+                    let start = match most {
+                        0 | 1 if others == 0 => 15,
+                        3.. if others == 0 => 5,
+                        _ => 10,
+                    };
+                    start + 10 * ((most - this) / 2)
+                };
+                let dimension = |this: i32, others: i32| match this {
+                    0..3 => 30,
+                    _ if others == 0 => 10 * this,
+                    _ => 10 * this + 10,
+                };
+                let (width, height) = (dimension(vertical, horizontal), dimension(horizontal, vertical));
+                let starts = [
+                    (0, offset(west, east, vertical)),
+                    (width, offset(east, west, vertical)),
+                    (offset(north, south, horizontal), 0),
+                    (offset(south, north, horizontal), height),
+                ];
+                let anchor = [1, 2, 0, 3]
+                    .into_iter()
+                    .find(|side| !sides[*side].is_empty())
+                    .map_or((0, 0), |side| starts[side]);
+                for (side, list) in sides.iter().enumerate() {
+                    for (i, (id, _)) in list.iter().enumerate() {
+                        let step = 10 * i as i32;
+                        let (x, y) = if side < 2 { (starts[side].0, starts[side].1 + step) } else { (starts[side].0 + step, starts[side].1) };
+                        ports.insert(*id, Port { offset: (x - anchor.0, y - anchor.1), west: false });
+                    }
+                }
+            }
+            Appearance::Evolution => {
+                // the anchor is the first output, or the first input if there are none
+                let has_outputs = pins.iter().any(|(_, pin)| pin.kind == PrimitiveKind::LIGHT);
+                for is_output in [false, true] {
+                    let mut list: Vec<(usize, Point)> = pins
+                        .iter()
+                        .filter(|(_, pin)| (pin.kind == PrimitiveKind::LIGHT) == is_output)
+                        .map(|(id, pin)| (*id, pin.location()))
+                        .collect();
+                    list.sort_by_key(|(_, (x, y))| (*y, *x));
+                    for (i, (id, _)) in list.into_iter().enumerate() {
+                        let west = !is_output && has_outputs;
+                        ports.insert(id, Port { offset: (0, 20 * i as i32), west });
+                        stretches |= west;
+                    }
+                }
+            }
+            Appearance::Custom { anchor, ports: drawn } => {
+                for (id, pin) in pins {
+                    if let Some((_, port)) = drawn.iter().find(|(at, _)| *at == pin.location()) {
+                        ports.insert(*id, Port { offset: (port.0 - anchor.0, port.1 - anchor.1), west: false });
+                    }
+                }
+            }
+        }
+        Converted { chip, ports, stretches }
+    }
+
+    /// Where the port of the pin gate `pin` is, for an instance at `loc` with a box `width` wide
+    fn port_at(&self, pin: usize, loc: Point, facing: &str, width: i32) -> Option<Point> {
+        let port = self.ports.get(&pin)?;
+        let x = if port.west { port.offset.0 - width } else { port.offset.0 };
+        let (dx, dy) = rotate((x, port.offset.1), facing);
+        Some((loc.0 + dx, loc.1 + dy))
+    }
+
+    /// Logisim-evolution sizes a box to fit its labels, so its width is taken to be the narrowest
+    /// one that puts the most input ports of this instance on a wire
+    fn box_width(&self, inputs: &[usize], loc: Point, facing: &str, wires: &[(Point, Point)]) -> i32 {
+        if !self.stretches {
+            return 0;
+        }
+        let mut best = (0, 0);
+        for width in (10..=MAX_BOX_WIDTH).step_by(10) {
+            let attached = inputs
+                .iter()
+                .filter_map(|pin| self.port_at(*pin, loc, facing, width))
+                .filter(|p| wires.iter().any(|(a, b)| on_segment(*p, *a, *b)))
+                .count();
+            if attached > best.1 {
+                best = (width, attached);
+            }
+        }
+        best.0
+    }
+}
+
+impl PlacedComponent {
+    /// Where a pin sits in its circuit
+    fn location(&self) -> Point {
+        self.outputs.first().or(self.inputs.first()).copied().unwrap_or_default()
+    }
+}
+
+/// Names unlabeled pins in0, in1, ... and out0, out1, ... in reading order
+/// A chip's pins are told apart by their names, Logisim pins don't need one
+fn name_pins(placed: &mut [PlacedComponent]) {
+    let taken: HashSet<String> = placed.iter().filter_map(|component| component.label.clone()).collect();
+    let mut unnamed: Vec<&mut PlacedComponent> = placed
+        .iter_mut()
+        .filter(|component| component.facing.is_some() && component.label.is_none())
+        .collect();
+    unnamed.sort_by_key(|component| {
+        let (x, y) = component.location();
+        (y, x)
+    });
+    let mut counts = [0, 0];
+    for component in unnamed {
+        let (prefix, count) = if component.kind == PrimitiveKind::LIGHT {
+            ("out", &mut counts[1])
+        } else {
+            ("in", &mut counts[0])
+        };
+        let name = loop {
+            let name = format!("{}{}", prefix, count);
+            *count += 1;
+            if !taken.contains(&name) {
+                break name;
+            }
+        };
+        component.label = Some(name);
+    }
+}
+
+/// Reads a hand drawn subcircuit appearance, None if it has no anchor
+/// Ports and the anchor are stored as boxes, their centers are what connects
+fn parse_appearance(appear: roxmltree::Node) -> Result<Option<Appearance>, Box<dyn Error>> {
+    let center = |node: roxmltree::Node| -> Result<Point, Box<dyn Error>> {
+        let number = |name: &str| -> Result<i32, Box<dyn Error>> { Ok(node.attribute(name).unwrap_or("0").parse()?) };
+        Ok((number("x")? + number("width")? / 2, number("y")? + number("height")? / 2))
+    };
+    let mut anchor = None;
+    let mut ports = Vec::new();
+    for child in appear.children().filter(|n| n.is_element()) {
+        match child.tag_name().name() {
+            "circ-anchor" => anchor = Some(center(child)?),
+            "circ-port" => ports.push((parse_point(child.attribute("pin").unwrap_or_default())?, center(child)?)),
+            _ => {}
+        }
+    }
+    Ok(anchor.map(|anchor| Appearance::Custom { anchor, ports }))
+}
+
+/// Maps a Logisim component onto one of our primitives
+/// Returns Ok(None) for purely decorative components and Err with a reason for anything we cannot translate
+fn translate_component(comp: &CompElement, legacy: bool) -> Result<Option<PlacedComponent>, String> {
+    let attr = |name: &str| comp.attrs.get(name).map(String::as_str);
+    let label = attr("label").filter(|l| !l.is_empty()).map(str::to_string);
+    let facing = attr("facing").unwrap_or("east");
+
+    if let Some(width) = attr("width").filter(|w| *w != "1") {
+        return Err(format!("{} bits wide, only single bit signals are supported", width));
+    }
+    if comp.attrs.iter().any(|(k, v)| k.starts_with("negate") && v == "true") {
+        return Err("negated inputs are not supported".to_string());
+    }
+
+    let single = |kind: PrimitiveKind, is_output: bool| {
+        Ok(Some(PlacedComponent {
+            kind,
+            label: label.clone(),
+            outputs: if is_output { vec![comp.loc] } else { Vec::new() },
+            inputs: if is_output { Vec::new() } else { vec![comp.loc] },
+            facing: None,
+        }))
+    };
+
+    let (kind, width) = match comp.name.as_str() {
+        "Text" => return Ok(None),
+        "Pin" => {
+            let is_output = attr("output") == Some("true");
+            let pin = if is_output {
+                single(PrimitiveKind::LIGHT, false)
+            } else {
+                single(PrimitiveKind::TOGGLE, true)
+            };
+            return pin.map(|pin| pin.map(|pin| PlacedComponent { facing: Some(facing.to_string()), ..pin }));
+        }
+        "LED" => return single(PrimitiveKind::LIGHT, false),
+        "Button" => return single(PrimitiveKind::PULSE, true),
+        "Power" => return single(PrimitiveKind::HISIGNAL, true),
+        "Ground" => return single(PrimitiveKind::LOSIGNAL, true),
+        "Constant" => {
+            let value = attr("value").unwrap_or("0x1");
+            let high = i64::from_str_radix(value.trim_start_matches("0x"), 16).unwrap_or(1) != 0;
+            let kind = if high { PrimitiveKind::HISIGNAL } else { PrimitiveKind::LOSIGNAL };
+            return single(kind, true);
+        }
+        "NOT Gate" => (PrimitiveKind::NOT, attr_size(attr("size"), 30)),
+        "Buffer" => (PrimitiveKind::BUFFER, 20),
+        "AND Gate" => (PrimitiveKind::AND, attr_size(attr("size"), 50)),
+        "OR Gate" => (PrimitiveKind::OR, attr_size(attr("size"), 50)),
+        "NAND Gate" => (PrimitiveKind::NAND, attr_size(attr("size"), 50) + 10),
+        "NOR Gate" => (PrimitiveKind::NOR, attr_size(attr("size"), 50) + 10),
+        "XOR Gate" => (PrimitiveKind::XOR, attr_size(attr("size"), 50) + 10),
+        other => return Err(format!("no equivalent for {}", other)),
+    };
+
+    let n_inputs = match kind {
+        PrimitiveKind::NOT | PrimitiveKind::BUFFER => 1,
+        _ => attr("inputs")
+            .and_then(|n| n.parse::<i32>().ok())
+            .unwrap_or(if legacy { 5 } else { 2 }),
+    };
+    let size = attr_size(attr("size"), 50);
+    let inputs = (0..n_inputs)
+        .map(|i| {
+            let (dx, dy) = rotate((-width, input_offset(i, n_inputs, size)), facing);
+            (comp.loc.0 + dx, comp.loc.1 + dy)
+        })
+        .collect();
+
+    Ok(Some(PlacedComponent {
+        kind,
+        label,
+        outputs: vec![comp.loc],
+        inputs,
+        facing: None,
+    }))
+}
+
+/// Vertical offset of input `index` on an east facing Logisim gate, relative to its output
+fn input_offset(index: i32, n_inputs: i32, size: i32) -> i32 {
+    let spacing = if n_inputs <= 3 && size >= 50 { 20 } else { 10 };
+    if n_inputs % 2 == 1 {
+        (index - (n_inputs - 1) / 2) * spacing
+    } else if index < n_inputs / 2 {
+        (index - n_inputs / 2) * spacing
+    } else {
+        (index - n_inputs / 2 + 1) * spacing
+    }
+}
+
+/// Logisim-evolution writes sizes as numbers, older versions as narrow/medium/wide
+fn attr_size(value: Option<&str>, default: i32) -> i32 {
+    match value {
+        Some("narrow") => 30,
+        Some("medium") => 50,
+        Some("wide") => 70,
+        Some(n) => n.parse().unwrap_or(default),
+        None => default,
+    }
+}
+
+/// Rotates an offset given for an east facing component to the component's facing
+fn rotate((dx, dy): Point, facing: &str) -> Point {
+    match facing {
+        "west" => (-dx, -dy),
+        "north" => (dy, -dx),
+        "south" => (-dy, dx),
+        _ => (dx, dy),
+    }
+}
+
+fn parse_point(text: &str) -> Result<Point, Box<dyn Error>> {
+    let inner = text.trim().trim_start_matches('(').trim_end_matches(')');
+    let (x, y) = inner
+        .split_once(',')
+        .ok_or_else(|| format!("Invalid Logisim location {}", text))?;
+    Ok((x.trim().parse()?, y.trim().parse()?))
+}
+
+/// Logisim connects everything that touches: wire endpoints, wires passing through endpoints and component pins.
+/// Points are grouped into nets with a union-find, and the wire segments are kept as a graph for routing.
+struct Nets {
+    parent: HashMap<Point, Point>,
+    attached: HashSet<Point>, // points that touch at least one wire
+    edges: HashMap<Point, Vec<Point>>,
+}
+
+impl Nets {
+    fn build(points: &HashSet<Point>, wires: &[(Point, Point)]) -> Self {
+        let mut nets = Nets {
+            parent: points.iter().map(|p| (*p, *p)).collect(),
+            attached: HashSet::new(),
+            edges: HashMap::new(),
+        };
+        for (a, b) in wires {
+            let mut on_wire: Vec<Point> = points.iter().filter(|p| on_segment(**p, *a, *b)).cloned().collect();
+            on_wire.sort();
+            for pair in on_wire.windows(2) {
+                nets.union(pair[0], pair[1]);
+                nets.edges.entry(pair[0]).or_default().push(pair[1]);
+                nets.edges.entry(pair[1]).or_default().push(pair[0]);
+            }
+            nets.attached.extend(on_wire);
+        }
+        nets
+    }
+
+    fn root(&self, mut p: Point) -> Point {
+        while let Some(next) = self.parent.get(&p) {
+            if *next == p {
+                break;
+            }
+            p = *next;
+        }
+        p
+    }
+
+    fn union(&mut self, a: Point, b: Point) {
+        let (ra, rb) = (self.root(a), self.root(b));
+        if ra != rb {
+            self.parent.insert(ra, rb);
+        }
+    }
+
+    fn is_attached(&self, p: Point) -> bool {
+        self.attached.contains(&p)
+    }
+
+    /// The corners of the shortest wire route between two points of the same net
+    fn bends(&self, from: Point, to: Point) -> Vec<Point> {
+        let mut previous: HashMap<Point, Point> = HashMap::new();
+        let mut queue = VecDeque::from([from]);
+        while let Some(p) = queue.pop_front() {
+            if p == to {
+                break;
+            }
+            for next in self.edges.get(&p).into_iter().flatten() {
+                if *next != from && !previous.contains_key(next) {
+                    previous.insert(*next, p);
+                    queue.push_back(*next);
+                }
+            }
+        }
+
+        let mut path = vec![to];
+        while let Some(p) = previous.get(path.last().unwrap()) {
+            path.push(*p);
+        }
+        if *path.last().unwrap() != from {
+            return Vec::new();
+        }
+        path.reverse();
+
+        // keep only the points where the route changes direction
+        path.windows(3)
+            .filter(|w| {
+                let d1 = (w[1].0 - w[0].0, w[1].1 - w[0].1);
+                let d2 = (w[2].0 - w[1].0, w[2].1 - w[1].1);
+                d1.0 * d2.1 != d1.1 * d2.0
+            })
+            .map(|w| w[1])
+            .collect()
+    }
+}
+
+/// Logisim wires are always horizontal or vertical
+fn on_segment(p: Point, a: Point, b: Point) -> bool {
+    let within = |v: i32, a: i32, b: i32| v >= a.min(b) && v <= a.max(b);
+    (a.0 == b.0 && p.0 == a.0 && within(p.1, a.1, b.1)) || (a.1 == b.1 && p.1 == a.1 && within(p.0, a.0, b.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// y = a AND b, with a 4 bit pin that can't be translated
+    const AND_CIRCUIT: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
+<project source="3.8.0" version="1.0">
+  <main name="main"/>
+  <circuit name="main">
+    <comp lib="0" loc="(100,100)" name="Pin"><a name="label" val="a"/></comp>
+    <comp lib="0" loc="(100,140)" name="Pin"><a name="label" val="b"/></comp>
+    <comp lib="0" loc="(300,120)" name="Pin"><a name="facing" val="west"/><a name="output" val="true"/><a name="label" val="y"/></comp>
+    <comp lib="0" loc="(100,200)" name="Pin"><a name="width" val="4"/></comp>
+    <comp lib="1" loc="(200,120)" name="AND Gate"/>
+    <wire from="(100,100)" to="(150,100)"/>
+    <wire from="(100,140)" to="(150,140)"/>
+    <wire from="(200,120)" to="(300,120)"/>
+  </circuit>
+</project>
+"#;
+
+    /// y = NOT a through a subcircuit, its box is as wide as the wire into its input leaves room for
+    const SUBCIRCUIT: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
+<project source="3.8.0" version="1.0">
+  <main name="main"/>
+  <circuit name="main">
+    <comp lib="0" loc="(100,100)" name="Pin"><a name="label" val="a"/></comp>
+    <comp loc="(300,100)" name="inv"/>
+    <comp lib="0" loc="(400,100)" name="Pin"><a name="facing" val="west"/><a name="output" val="true"/><a name="label" val="y"/></comp>
+    <wire from="(100,100)" to="(240,100)"/>
+    <wire from="(300,100)" to="(400,100)"/>
+  </circuit>
+  <circuit name="inv">
+    <comp lib="0" loc="(100,100)" name="Pin"><a name="label" val="in"/></comp>
+    <comp lib="1" loc="(160,100)" name="NOT Gate"/>
+    <comp lib="0" loc="(200,100)" name="Pin"><a name="facing" val="west"/><a name="output" val="true"/><a name="label" val="out"/></comp>
+    <wire from="(100,100)" to="(130,100)"/>
+    <wire from="(160,100)" to="(200,100)"/>
+  </circuit>
+</project>
+"#;
+
+    #[test]
+    fn imports_a_gate_wired_to_pins() {
+        let import = LogisimImport::parse(AND_CIRCUIT).expect("a valid project");
+        assert_eq!(import.untranslated.len(), 1, "only the 4 bit pin is left out");
+        let mut data = Data::new();
+        data.load_chip(import.main_chip().expect("main circuit"));
+        for (a, b) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            data.set_pin("a", a).expect("pin a");
+            data.set_pin("b", b).expect("pin b");
+            assert!(data.settle(16), "the board should settle");
+            assert_eq!(data.read_pin("y").expect("pin y"), a & b, "{} AND {}", a, b);
+        }
+    }
+
+    #[test]
+    fn imports_a_subcircuit_wired_to_its_box() {
+        let import = LogisimImport::parse(SUBCIRCUIT).expect("a valid project");
+        assert!(import.untranslated.is_empty(), "{:?}", import.untranslated);
+        let mut data = Data::new();
+        data.load_chip(import.main_chip().expect("main circuit"));
+        let expanded = data.flatten(|name| import.circuits.iter().find(|chip| chip.name == name).cloned());
+        assert_eq!(expanded.expect("inv is in the project"), 1);
+        for a in [0, 1] {
+            data.set_pin("a", a).expect("pin a");
+            assert!(data.settle(16), "the board should settle");
+            assert_eq!(data.read_pin("y").expect("pin y"), 1 - a, "NOT {}", a);
+        }
+    }
+}
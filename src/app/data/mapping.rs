@@ -0,0 +1,224 @@
+use std::fmt::{self, Display, Formatter};
+
+use super::*;
+
+/// A gate every other logic gate can be built from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum UniversalGate {
+    Nand,
+    Nor,
+}
+
+impl UniversalGate {
+    pub const ALL: [UniversalGate; 2] = [UniversalGate::Nand, UniversalGate::Nor];
+
+    pub fn kind(self) -> PrimitiveKind {
+        match self {
+            UniversalGate::Nand => PrimitiveKind::NAND,
+            UniversalGate::Nor => PrimitiveKind::NOR,
+        }
+    }
+
+    /// Parses "nand" or "nor", in any case
+    pub fn parse(text: &str) -> Result<Self, String> {
+        UniversalGate::ALL
+            .into_iter()
+            .find(|gate| gate.to_string().eq_ignore_ascii_case(text))
+            .ok_or_else(|| format!("can only map to NAND or NOR, not {}", text))
+    }
+}
+
+impl Display for UniversalGate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind())
+    }
+}
+
+/// Logic gates before and after mapping, and what every kind of gate was turned into
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MappingReport {
+    pub target: UniversalGate,
+    pub gates_before: usize,
+    pub gates_after: usize,
+    pub replaced: Vec<(String, usize, usize)>, // (kind, gates of that kind, universal gates they became)
+}
+
+impl Display for MappingReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}-only: {} -> {} gates", self.target, self.gates_before, self.gates_after)?;
+        for (kind, count, gates) in &self.replaced {
+            writeln!(f, "  {} x{} -> {} {}", kind, count, gates, self.target)?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds a replacement network out of one kind of gate, stacked around where the old gate was
+/// A signal is an output id, None is an unconnected input which reads false
+struct Network<'a> {
+    data: &'a mut Data,
+    target: UniversalGate,
+    pos: Pos2,
+    gates: usize,
+}
+
+impl Network<'_> {
+    /// One universal gate with two inputs
+    fn gate(&mut self, a: Option<usize>, b: Option<usize>) -> Option<usize> {
+        // two to a row, going down from where the old gate was
+        let pos = self.pos + egui::vec2((self.gates % 2) as f32 * 120.0 - 60.0, (self.gates / 2) as f32 * 80.0);
+        let gate_id = self.data.add_primitive(self.target.kind(), pos);
+        self.gates += 1;
+        for (signal, input) in [a, b].into_iter().zip(self.data.gate_inputs(gate_id)) {
+            if let Some(output) = signal {
+                self.data.connect(output, input).expect("a new gate has nothing plugged in");
+            }
+        }
+        self.data.gate_outputs(gate_id).first().copied()
+    }
+
+    fn not(&mut self, a: Option<usize>) -> Option<usize> {
+        self.gate(a, a)
+    }
+
+    /// The universal gate without its inversion: AND for NAND, OR for NOR
+    fn base(&mut self, a: Option<usize>, b: Option<usize>) -> Option<usize> {
+        let inverted = self.gate(a, b);
+        self.not(inverted)
+    }
+
+    /// The other one of AND and OR, by De Morgan: a OR b = NAND(NOT a, NOT b), a AND b = NOR(NOT a, NOT b)
+    fn dual(&mut self, a: Option<usize>, b: Option<usize>) -> Option<usize> {
+        let (not_a, not_b) = (self.not(a), self.not(b));
+        self.gate(not_a, not_b)
+    }
+
+    fn and(&mut self, a: Option<usize>, b: Option<usize>) -> Option<usize> {
+        match self.target {
+            UniversalGate::Nand => self.base(a, b),
+            UniversalGate::Nor => self.dual(a, b),
+        }
+    }
+
+    fn or(&mut self, a: Option<usize>, b: Option<usize>) -> Option<usize> {
+        match self.target {
+            UniversalGate::Nand => self.dual(a, b),
+            UniversalGate::Nor => self.base(a, b),
+        }
+    }
+
+    /// Four gates give XOR from NANDs and XNOR from NORs
+    fn xor(&mut self, a: Option<usize>, b: Option<usize>) -> Option<usize> {
+        let both = self.gate(a, b);
+        let (left, right) = (self.gate(a, both), self.gate(b, both));
+        let out = self.gate(left, right);
+        match self.target {
+            UniversalGate::Nand => out,
+            UniversalGate::Nor => self.not(out),
+        }
+    }
+
+    /// The same function as a gate of `kind` reading `ins`
+    fn build(&mut self, kind: &PrimitiveKind, ins: &[Option<usize>]) -> Option<usize> {
+        // `empty` is what the gate gives with no inputs, false is an unconnected input and needs no gate
+        let fold = |network: &mut Self, op: fn(&mut Self, Option<usize>, Option<usize>) -> Option<usize>, empty: bool| match ins {
+            [] if empty => network.not(None),
+            [] => None,
+            [single] => {
+                let inverted = network.not(*single);
+                network.not(inverted)
+            }
+            _ => ins[1..].iter().fold(ins[0], |acc, signal| op(network, acc, *signal)),
+        };
+        match kind {
+            PrimitiveKind::NOT => self.not(ins[0]),
+            PrimitiveKind::BUFFER => {
+                let inverted = self.not(ins[0]);
+                self.not(inverted)
+            }
+            PrimitiveKind::AND => fold(self, Self::and, true),
+            PrimitiveKind::OR => fold(self, Self::or, false),
+            PrimitiveKind::XOR => fold(self, Self::xor, false),
+            PrimitiveKind::NAND | PrimitiveKind::NOR if ins.len() == 1 => self.not(ins[0]),
+            PrimitiveKind::NAND => {
+                let out = fold(self, Self::and, true);
+                self.not(out)
+            }
+            PrimitiveKind::NOR => {
+                let out = fold(self, Self::or, false);
+                self.not(out)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Data {
+    /// Rewrites every logic gate as a network of 2-input NANDs or NORs that settles to the same value
+    /// Gates already of the target kind stay whatever their inputs, pins and constant signals are never touched
+    /// The board must be flattened; timing in ticks changes as one gate becomes several
+    /// With `layout` the whole board is laid out again, otherwise new gates are stacked where the old one was
+    pub fn map_to_universal(&mut self, target: UniversalGate, layout: bool) -> Result<MappingReport, CircuitError> {
+        let mut gates: Vec<(usize, GateKind)> = self
+            .live_data
+            .values()
+            .filter_map(|item| item.as_any().downcast_ref::<Gate>())
+            .map(|gate| (gate.id, gate.kind.clone()))
+            .collect();
+        gates.sort_by_key(|(id, _)| *id);
+        if let Some((gate_id, GateKind::Custom(name))) = gates.iter().find(|(_, kind)| matches!(kind, GateKind::Custom(_))) {
+            return Err(CircuitError::Unsupported {
+                id: Some(*gate_id),
+                message: format!("{} is a chip, flatten the board before mapping it", name),
+            });
+        }
+        let _ids = self.ids.enter();
+        let is_logic = |kind: &GateKind| matches!(kind, GateKind::Primitive(kind) if DELAY_KINDS.contains(kind));
+        let gates_before = gates.iter().filter(|(_, kind)| is_logic(kind)).count();
+
+        let mut replaced: Vec<(String, usize, usize)> = Vec::new();
+        for (gate_id, kind) in gates {
+            let GateKind::Primitive(kind) = kind else {
+                continue;
+            };
+            if !DELAY_KINDS.contains(&kind) || kind == target.kind() {
+                continue;
+            }
+            let ins: Vec<Option<usize>> = self.gate_inputs(gate_id).into_iter().map(|input| self.source_output(input)).collect();
+            let outs = self.gate_outputs(gate_id);
+            // the same arity errors the simulator raises
+            kind.evaluate(gate_id, &mut false, &vec![false; ins.len()], outs.len())?;
+            let pos = self.get_gate_mut(gate_id).map_or(Pos2::ZERO, |gate| gate.position.to_pos2());
+
+            let mut network = Network { data: self, target, pos, gates: 0 };
+            let out = network.build(&kind, &ins);
+            let built = network.gates;
+            // only the first output is ever driven, the others read false like the wires of a removed gate
+            if let (Some(out), Some(&old)) = (out, outs.first()) {
+                self.move_fan_out(old, out);
+            }
+            self.remove_gate(gate_id);
+
+            match replaced.iter_mut().find(|(name, _, _)| *name == kind.to_string()) {
+                Some((_, count, total)) => {
+                    *count += 1;
+                    *total += built;
+                }
+                None => replaced.push((kind.to_string(), 1, built)),
+            }
+        }
+        replaced.sort();
+
+        if layout {
+            self.auto_layout();
+        }
+        self.check_graph_after_edit();
+        let gates_after = self
+            .live_data
+            .values()
+            .filter_map(|item| item.as_any().downcast_ref::<Gate>())
+            .filter(|gate| is_logic(&gate.kind))
+            .count();
+        Ok(MappingReport { target, gates_before, gates_after, replaced })
+    }
+}
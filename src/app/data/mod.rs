@@ -1,30 +1,142 @@
-use std::io::Write;
-use ron::ser::{to_string, PrettyConfig};
+use std::error::Error;
 
 use super::*;    
 
 mod theme;
 pub use theme::SkeletonTheme;
+
+mod stats;
+pub use stats::{ChipStats, chip_stats};
+
+mod test_script;
+pub use test_script::{TestMismatch, TestReport, TestScript};
+
+mod breakpoint;
+pub use breakpoint::{BreakCondition, BreakHit, BreakTarget, Breakpoint, parse_break_value};
+
+mod design_check;
+pub use design_check::{MAX_FAN_OUT, Problem, ProblemKind, component_position};
+
+mod equivalence;
+pub use equivalence::{Counterexample, Equivalence, MAX_EXHAUSTIVE_BITS, check_chip_equivalence};
+
+mod flatten;
+pub use flatten::{MAX_CHIP_DEPTH, chip_pins};
+
+mod fsm;
+pub use fsm::{FSM_CLOCK_PIN, FSM_STATE_PIN, FsmReport, FsmState, MAX_FSM_INPUTS, MAX_FSM_STATES, MachineKind, StateEncoding, StateMachine, Transition};
+
+mod history;
+pub use history::{DEFAULT_HISTORY_CAPACITY, History, SignalLayout, TickSnapshot};
+
+mod integrity;
+pub use integrity::{GraphIssue, Repair};
+
+mod karnaugh;
+pub use karnaugh::{Cell, Implicant, KarnaughMap, MAX_KMAP_INPUTS, SopBuilder, pin_bit_names};
+
+mod layout;
+pub use layout::LAYOUT_SPACING;
+
+mod logisim;
+pub use logisim::{LogisimImport, UntranslatedComponent};
+
+mod mapping;
+pub use mapping::{MappingReport, UniversalGate};
+
+mod netlist;
+pub use netlist::{MIN_GATES_PER_THREAD, NetGate, Netlist};
+
+mod optimize;
+pub use optimize::{OptimizePass, OptimizeReport};
+
+mod oscillation;
+pub use oscillation::{FeedbackLoop, OscillationMonitor, OscillationReport, feedback_loops, gate_edges, gate_order};
+
+mod probe;
+pub use probe::Probe;
+
+mod sat;
+pub use sat::{Lit, SatSolver};
+
+mod sim_control;
+pub use sim_control::{MAX_STABLE_TICKS, MAX_TICKS_PER_FRAME, SimControl};
+
+mod timing;
+pub use timing::{DELAY_KINDS, PinPath, TimingReport, parse_gate_delay};
+
+mod truth_table;
+pub use truth_table::{MAX_TRUTH_TABLE_BITS, PinGroup, TruthRow, TruthTable};
+
+mod vector_sim;
+pub use vector_sim::{LANES, VectorSim, sweep_blocks};
+
+mod verilog;
+pub use verilog::verilog_identifier;
+
+mod waveform;
+pub use waveform::{RecordedSignal, SignalGroup, SignalRecorder, TimeUnit, Timescale, signal_name, signal_value, split_bus_name};
+
+pub const SAVES_DIR: &str = "./saves";
+
 pub struct Data{
     pub live_data: HashMap<usize, Box<dyn Logical>>, // (id, position, id)
+    pub ids: IdAllocator, // ids for everything in live_data come from here
     pub available_themes: HashMap<String, SkeletonTheme>,
     pub color_values: HashMap<String, Color32>,
 
     pub prim_templates: Vec<PrimitiveTemplate>,
     pub saved_chips: Vec<ChipDefenition>,
+    pub sub_chips: HashMap<usize, ChipDefenition>, // chips placed on the board, kept until flattened
+
+    pub recorder: SignalRecorder,
+    pub probes: Vec<Probe>,
+    pub sim: SimControl,
+    pub gate_delays: HashMap<PrimitiveKind, f64>, // timing analysis delay per kind of gate, 1 if missing
+    pub breakpoints: Vec<Breakpoint>,
+    pub break_hit: Option<BreakHit>,
+    pub history: History,
+    pub oscillation: OscillationMonitor,
+    pub auto_repair_graph: bool, // repair inconsistencies found after edits instead of panicking
+    pub diagnostics: Vec<CircuitError>, // errors raised during the last tick
+    pub edit_errors: Vec<CircuitError>, // errors raised while editing, kept until cleared
+    netlist: Option<Netlist>, // compiled board the simulator runs on, rebuilt after edits
+    edits: u64,               // edits made to the board, the netlist is stale once this moves on
 }
 
 
+impl Default for Data {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Data{
     pub fn new() -> Self {
         Data {
             live_data: HashMap::new(),
+            ids: IdAllocator::new(),
 
             available_themes: HashMap::new(),
             color_values: HashMap::new(),
 
             prim_templates: Vec::new(),
             saved_chips: Vec::new(),
+            sub_chips: HashMap::new(),
+
+            recorder: SignalRecorder::default(),
+            probes: Vec::new(),
+            sim: SimControl::default(),
+            gate_delays: HashMap::new(),
+            breakpoints: Vec::new(),
+            break_hit: None,
+            history: History::default(),
+            oscillation: OscillationMonitor::default(),
+            auto_repair_graph: true,
+            diagnostics: Vec::new(),
+            edit_errors: Vec::new(),
+            netlist: None,
+            edits: 0,
         }
     }
 
@@ -135,180 +247,499 @@ impl Data{
     }
 
     ///loads saved chips from the saves directory
-    ///returns a vector of Primitive right now but this NEEDS to be changed to a vector of Gate(Custom)
+    ///every file ending in ".chip" is read as a RON encoded ChipDefenition
     pub fn load_chips() -> Vec<ChipDefenition> {
-        let gates = Vec::<ChipDefenition>::new();
-
-        //read saves directory for each file add a gate to the vector
-        let dir = std::fs::read_dir("./saves").unwrap();
-        for entry in dir {
-            print!("Loading gate: ");
-            let entry = entry.unwrap();
-            if entry.path().is_file() {
-                println!("{}", entry.path().display());
-                // Check if the file name ends with ".gate"
-                let file_name = entry.file_name().into_string().unwrap();
-                if file_name.ends_with(".chip") {
-                    // Load the gate from the file
-                    // let gate_template = Primitive::new(file_name);
-                    // gates.push(gate_template);
+        let mut chips = Vec::<ChipDefenition>::new();
+
+        //read saves directory for each file add a chip to the vector
+        let dir = std::fs::read_dir(SAVES_DIR).unwrap();
+        for entry in dir.flatten() {
+            let path = entry.path();
+            if path.is_file() && path.extension().is_some_and(|ext| ext == "chip") {
+                print!("Loading chip: {} ", path.display());
+                match ChipDefenition::load_from_file(&path) {
+                    Ok(chip) => {
+                        println!("ok");
+                        chips.push(chip);
+                    }
+                    Err(e) => println!("failed: {}", e),
                 }
             }
         }
-        println!("Loaded {} gates", gates.len());
-        gates
+        println!("Loaded {} chips", chips.len());
+        chips
     }
 
-    /// Saves the current live_data to a file in RON format.
-    /// Serializes the entire live_data HashMap into RON and writes it to "saves/chip_data.chip".
-    pub fn save_to_chip_file(&self) {
-
-        // You may need to derive or implement Serialize for your types.
-        let pretty = PrettyConfig::new();
-        let ron_string = String::new();
-        for (item_id, item) in &self.live_data {
-            println!("Saving item with ID: {}", item_id);
+    /// The board as a chip, with the chips placed on it
+    pub fn to_chip(&self, name: &str) -> ChipDefenition {
+        let mut chip = ChipDefenition::from_live_data(&self.live_data, name.to_string());
+        chip.sub_chips = self.sub_chips.clone();
+        chip
+    }
 
+    /// Builds a chip from the current live_data and writes it to "saves/<name>.chip" in RON format.
+    /// The new chip is also added to the saved chips list.
+    pub fn save_to_chip_file(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        // the name becomes a file in the saves folder, it can't point anywhere else
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\', ':']) {
+            return Err(format!("\"{}\" can't be used as a chip name", name).into());
         }
+        let chip = self.to_chip(name);
+        chip.save_to_file(Data::chip_path(name))?;
+        println!("Saved live_data to {}.chip as RON", name);
 
-        let mut file = std::fs::File::create("saves/chip_data.chip")
-            .expect("Unable to create file");
-        file.write_all(ron_string.as_bytes())
-            .expect("Unable to write data to file");
-        println!("Saved live_data to chip_data.chip as RON");
+        self.saved_chips.retain(|c| c.name != chip.name);
+        self.saved_chips.push(chip);
+        Ok(())
     }
 
+    /// Removes a saved chip from the list and deletes its file
+    pub fn delete_chip_file(&mut self, index: usize) -> Result<(), Box<dyn Error>> {
+        let chip = self.saved_chips.remove(index);
+        std::fs::remove_file(Data::chip_path(&chip.name))?;
+        Ok(())
+    }
 
+    pub fn chip_path(name: &str) -> PathBuf {
+        PathBuf::from(SAVES_DIR).join(format!("{}.chip", name))
+    }
 
+    /// Replaces the current live_data with the contents of a chip
+    pub fn load_chip(&mut self, chip: &ChipDefenition) {
+        self.clear_probes();
+        self.clear_breakpoints();
+        self.history.clear();
+        self.oscillation = OscillationMonitor::default();
+        self.diagnostics.clear();
+        self.edit_errors.clear();
+        self.invalidate_netlist();
+        self.live_data = chip.to_live_data();
+        self.sub_chips = chip.sub_chips.clone();
+        // the saved ids are kept so they stay stable, new ones continue after them
+        self.ids.reset_past(self.live_data.keys().copied());
+
+        // files may come from older versions or other tools, so never trust their connections
+        let repaired = self.repair_graph();
+        if !repaired.is_empty() {
+            eprintln!("Repaired {} broken references in {}:", repaired.len(), chip.name);
+            for issue in &repaired {
+                eprintln!("  {}", issue);
+            }
+        }
+    }
 
-    ///should be run every frame to update the logical states of all gates and wires
-    ///this will collect all inputs, process all gates, and update outputs and wires accordingly
-    ///it will also request a repaint of the UI context
-    pub fn update_logicals(&mut self, ctx: &Context) {
-        // Update the logical states of all gates and wires
-
-        // Step 1: Collect all inputs and their current states
-        let input_states = self.collect_input_states();
-
-        // Step 2: Process all gates and collect their outputs
-        let gate_outputs = self.process_gates(&input_states);
+    /// Adds a copy of some items to the board, moved by `offset`
+    /// Every item gets a new id so nothing collides with what is already on the board
+    /// Returns the mapping from the old ids to the new ones
+    pub fn paste(&mut self, items: HashMap<usize, Box<dyn Logical>>, offset: egui::Vec2) -> HashMap<usize, usize> {
+        let (mut items, map) = remap_ids(items, &self.ids);
+        for item in items.values_mut() {
+            let any = item.as_any_mut();
+            if let Some(gate) = any.downcast_mut::<Gate>() {
+                gate.position.vec += offset;
+            } else if let Some(wire) = any.downcast_mut::<Wire>() {
+                for waypoint in &mut wire.line.waypoints {
+                    *waypoint += offset;
+                }
+            }
+        }
+        self.live_data.extend(items);
+        self.check_graph_after_edit();
+        map
+    }
 
-        // Step 3: Update outputs and propagate through wires
-        let wire_signals = self.update_outputs_and_wires(&gate_outputs);
+    /// Places a copy of a chip's contents on the board, centered on `pos`
+    pub fn place_chip(&mut self, chip: &ChipDefenition, pos: Pos2) -> HashMap<usize, usize> {
+        self.paste(chip.to_live_data(), chip_offset(chip, pos))
+    }
 
-        // Step 4: Apply wire signals to inputs
-        self.apply_wire_signals_to_inputs(&wire_signals);
+    /// Adds every circuit of a Logisim import to the saved chips and loads its main circuit onto the board
+    pub fn apply_logisim_import(&mut self, import: &LogisimImport) -> Result<(), Box<dyn Error>> {
+        for chip in &import.circuits {
+            chip.save_to_file(Data::chip_path(&chip.name))?;
+            self.saved_chips.retain(|c| c.name != chip.name);
+            self.saved_chips.push(chip.clone());
+        }
+        if let Some(main) = import.main_chip() {
+            self.load_chip(main);
+        }
+        Ok(())
+    }
 
-        ctx.request_repaint();
+    /// Creates a primitive gate (with its inputs and outputs) at a world position
+    /// Returns the id of the new gate
+    pub fn add_primitive(&mut self, kind: PrimitiveKind, pos: Pos2) -> usize {
+        let _ids = self.ids.enter();
+        self.invalidate_netlist();
+        let mut gate = Gate::create_gate_from_template(kind.get_gate_kind(), pos);
+        gate.create_io(&mut self.live_data);
+        let id = gate.id;
+        self.live_data.insert(id, Box::new(gate));
+        id
     }
 
+    /// Creates a gate standing for the chip `name` at a world position, flatten swaps it for the chip's contents
+    /// Returns the id of the new gate
+    pub fn add_custom_gate(&mut self, name: &str, n_in: usize, n_out: usize, pos: Pos2) -> usize {
+        let _ids = self.ids.enter();
+        self.invalidate_netlist();
+        let mut gate = Gate::generate(name.to_string(), n_in, n_out);
+        gate.kind = GateKind::Custom(name.to_string());
+        gate.position.vec = pos.to_vec2();
+        gate.create_io(&mut self.live_data);
+        let id = gate.id;
+        self.live_data.insert(id, Box::new(gate));
+        id
+    }
 
+    /// Connects an output to an input with a new wire, the same way the user does by clicking
+    /// Returns the id of the new wire
+    pub fn connect(&mut self, output_id: usize, input_id: usize) -> Result<usize, Box<dyn Error>> {
+        let _ids = self.ids.enter();
+        self.invalidate_netlist();
+        let input = self
+            .get_input_mut(input_id)
+            .ok_or(CircuitError::InvalidDowncast { id: input_id, expected: "Input" })?;
+        if let Some(wire_id) = input.source_wire_id {
+            return Err(InvalidOperationError(format!(
+                "Input {} already has wire {} connected",
+                input_id, wire_id
+            ))
+            .into());
+        }
 
+        let output = self
+            .get_output_mut(output_id)
+            .ok_or(CircuitError::InvalidDowncast { id: output_id, expected: "Output" })?;
+        let mut wire = Wire::from_io(output_id, Pos2::ZERO);
+        wire.dest = Some(input_id);
+        wire.connected = true;
+        let wire_id = wire.id;
+        output.out_wire_ids.push(wire_id);
+
+        if let Some(input) = self.get_input_mut(input_id) {
+            input.source_wire_id = Some(wire_id);
+        }
+        self.live_data.insert(wire_id, wire);
+        Ok(wire_id)
+    }
 
-    /// Collects the current states of all inputs in the live_data
-    /// Returns a HashMap where the key is the input ID and the value is the signal
-    fn collect_input_states(&self) -> HashMap<usize, bool> {
-        self.live_data
-            .iter()
-            .filter_map(|(id, item)| {
-                item.as_any()
-                    .downcast_ref::<Input>()
-                    .map(|input| (*id, input.signal))
-            })
-            .collect()
+    /// The output whose wire is plugged into an input
+    pub fn source_output(&self, input_id: usize) -> Option<usize> {
+        let wire_id = self.live_data.get(&input_id)?.as_any().downcast_ref::<Input>()?.source_wire_id?;
+        Some(self.live_data.get(&wire_id)?.as_any().downcast_ref::<Wire>()?.source_id)
     }
 
+    /// Moves every wire leaving the output `from` so it leaves the output `to` instead
+    pub fn move_fan_out(&mut self, from: usize, to: usize) {
+        let wires = self.get_output_mut(from).map(|output| std::mem::take(&mut output.out_wire_ids)).unwrap_or_default();
+        for wire_id in &wires {
+            if let Some(wire) = self.get_wire_mut(*wire_id) {
+                wire.source_id = to;
+            }
+        }
+        if let Some(output) = self.get_output_mut(to) {
+            output.out_wire_ids.extend(wires);
+        }
+    }
 
+    /// Removes a wire, unplugging it from its input and dropping it from its output
+    pub fn remove_wire(&mut self, wire_id: usize) {
+        self.invalidate_netlist();
+        let Some(item) = self.live_data.remove(&wire_id) else {
+            return;
+        };
+        let Some(wire) = item.as_any().downcast_ref::<Wire>() else {
+            self.live_data.insert(wire_id, item); // not a wire, leave it alone
+            return;
+        };
+        if let Some(input) = wire.dest.and_then(|dest| self.get_input_mut(dest))
+            && input.source_wire_id == Some(wire_id)
+        {
+            input.source_wire_id = None;
+        }
+        if let Some(output) = self.get_output_mut(wire.source_id) {
+            output.out_wire_ids.retain(|&x| x != wire_id);
+        }
+    }
 
+    /// Removes a gate together with its inputs, outputs and every wire attached to them
+    pub fn remove_gate(&mut self, gate_id: usize) {
+        self.invalidate_netlist();
+        let Some(gate) = self.live_data.get(&gate_id).and_then(|item| item.as_any().downcast_ref::<Gate>()) else {
+            return;
+        };
+        let pins: Vec<usize> = gate.ins.keys().chain(gate.outs.keys()).copied().collect();
+        for pin in pins {
+            let Some(item) = self.live_data.get(&pin) else {
+                continue;
+            };
+            let any = item.as_any();
+            let wires = match (any.downcast_ref::<Input>(), any.downcast_ref::<Output>()) {
+                (Some(input), _) => input.source_wire_id.into_iter().collect(),
+                (_, Some(output)) => output.out_wire_ids.clone(),
+                _ => Vec::new(),
+            };
+            for wire_id in wires {
+                self.remove_wire(wire_id);
+            }
+            self.live_data.remove(&pin);
+        }
+        self.live_data.remove(&gate_id);
+    }
 
-    /// Processes all gates in the live_data, gathering their inputs and producing outputs
-    /// Returns a HashMap where the key is the output ID and the value is the signal
-    /// This method assumes that each gate can be processed independently
-    /// and that the inputs for each gate are already available in the input_states HashMap.
-    fn process_gates(&mut self, input_states: &HashMap<usize, bool>) -> HashMap<usize, bool> {
-        let mut gate_outputs = HashMap::new();
+    /// Ids of a gate's inputs, ordered by their index on the gate
+    pub fn gate_inputs(&self, gate_id: usize) -> Vec<usize> {
+        let mut ins: Vec<(usize, usize)> = self
+            .live_data
+            .get(&gate_id)
+            .and_then(|item| item.as_any().downcast_ref::<Gate>())
+            .map(|gate| {
+                gate.ins
+                    .keys()
+                    .filter_map(|id| {
+                        let input = self.live_data.get(id)?.as_any().downcast_ref::<Input>()?;
+                        Some((input.index, *id))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        ins.sort();
+        ins.into_iter().map(|(_, id)| id).collect()
+    }
 
-        for (_gate_id, item) in self.live_data.iter_mut() {
-            if let Some(gate) = item.as_any_mut().downcast_mut::<Gate>() {
-                // Gather inputs for this specific gate
-                let gate_inputs: HashMap<usize, bool> = gate
-                    .ins
+    /// Ids of a gate's outputs, ordered by their index on the gate
+    pub fn gate_outputs(&self, gate_id: usize) -> Vec<usize> {
+        let mut outs: Vec<(usize, usize)> = self
+            .live_data
+            .get(&gate_id)
+            .and_then(|item| item.as_any().downcast_ref::<Gate>())
+            .map(|gate| {
+                gate.outs
                     .keys()
-                    .filter_map(|input_id| {
-                        input_states
-                            .get(input_id)
-                            .map(|&signal| (*input_id, signal))
+                    .filter_map(|id| {
+                        let output = self.live_data.get(id)?.as_any().downcast_ref::<Output>()?;
+                        Some((output.index, *id))
                     })
-                    .collect();
+                    .collect()
+            })
+            .unwrap_or_default();
+        outs.sort();
+        outs.into_iter().map(|(_, id)| id).collect()
+    }
 
-                // Process gate and collect outputs
-                if let Ok(outputs) = item.tick(gate_inputs) {
-                    gate_outputs.extend(outputs);
-                }
+
+
+
+    /// Runs every tick that came due since the last frame, then schedules the next repaint
+    pub fn update_logicals(&mut self, ctx: &Context) {
+        let now = ctx.input(|i| i.time);
+        for _ in 0..self.sim.ticks_due(now) {
+            self.tick();
+            self.watch_oscillation();
+            if !self.breakpoints.is_empty() && self.check_breakpoints() {
+                self.sim.pause();
+                break;
             }
         }
+        if let Some(wait) = self.sim.next_wakeup() {
+            ctx.request_repaint_after(wait);
+        }
+    }
+
+    /// Runs a single propagation step over every gate and wire, without touching the UI
+    pub fn tick(&mut self) {
+        self.propagate();
+
+        // Step 5: Record the watched signals
+        self.recorder.sample(&self.live_data);
+        self.sim.total_ticks += 1;
 
-        gate_outputs
+        // Step 6: Keep a snapshot for rewinding
+        if self.history.enabled
+            && let Some(netlist) = &self.netlist
+        {
+            self.history.record(self.sim.total_ticks, netlist);
+        }
     }
 
+    /// Steps 1 to 4 of a tick, updates the board without recording anything
+    fn propagate(&mut self) {
+        // Update the logical states of all gates and wires
+        // The simulation runs on the compiled netlist, live_data only gets the values that changed
 
+        // Step 1: Compile the board if it changed, and pick up pins that were clicked or set
+        self.netlist();
+        let netlist = self.netlist.as_mut().expect("compiled above");
+        netlist.pull_user_state(&self.live_data);
 
+        // Steps 2 to 4: Process all gates, drive their outputs and wires, and apply the wires to the inputs
+        let mut errors = Vec::new();
+        netlist.tick(self.sim.threads, &mut errors);
 
-    /// Updates the outputs and wires based on the gate outputs
-    /// Returns a HashMap where the key is the input ID and the value is the signal
-    /// This method updates the output signals and propagates them through the wires
-    /// connected to those outputs.
-    fn update_outputs_and_wires(
-        &mut self,
-        gate_outputs: &HashMap<usize, bool>,
-    ) -> HashMap<usize, bool> {
-        let mut input_signals = HashMap::new();
+        // Copy the changes back so the editor, probes and recorders see them
+        netlist.write_back(&mut self.live_data);
 
-        for (&output_id, &signal) in gate_outputs {
-            // Update the output signal
-            if let Some(output) = self.get_output_mut(output_id) {
-                output.signal = signal;
+        // only log errors when they change, the same ones usually come back every tick
+        if errors != self.diagnostics {
+            for error in &errors {
+                eprintln!("Simulation error: {}", error);
+            }
+        }
+        self.diagnostics = errors;
+    }
 
-                // Process all wires connected to this output
-                let wire_ids = output.out_wire_ids.clone();
-                for wire_id in wire_ids {
-                    if let Some(wire) = self.get_wire_mut(wire_id) {
-                        wire.set_signal(signal);
+    /// Records an error from an edit so the diagnostics panel can show it
+    pub fn report_edit_error(&mut self, error: CircuitError) {
+        eprintln!("Edit error: {}", error);
+        self.edit_errors.push(error);
+    }
 
-                        // If wire has a destination, record the signal for that input
-                        if let Some(dest_input_id) = wire.dest {
-                            input_signals.insert(dest_input_id, signal);
-                        }
-                    }
-                }
+    /// Ticks until no signal on the board changes anymore
+    /// Stops early and pauses the simulation when a breakpoint triggers, like a running simulation does
+    /// Returns false if the board was still changing after max_ticks or stopped at a breakpoint
+    pub fn settle(&mut self, max_ticks: usize) -> bool {
+        let mut last = self.signal_snapshot();
+        for _ in 0..max_ticks {
+            self.tick();
+            if !self.breakpoints.is_empty() && self.check_breakpoints() {
+                self.sim.pause();
+                return false;
+            }
+            let next = self.signal_snapshot();
+            if next == last {
+                return true;
             }
+            last = next;
         }
+        false
+    }
 
-        input_signals
+    /// Collects the signal of every input, output and wire, and the state of every gate
+    pub fn signal_snapshot(&self) -> HashMap<usize, bool> {
+        self.live_data
+            .iter()
+            .filter_map(|(id, item)| {
+                let any = item.as_any();
+                if let Some(gate) = any.downcast_ref::<Gate>() {
+                    Some((*id, gate.state))
+                } else if let Some(wire) = any.downcast_ref::<Wire>() {
+                    Some((*id, wire.get_signal()))
+                } else if let Some(input) = any.downcast_ref::<Input>() {
+                    Some((*id, input.signal))
+                } else {
+                    any.downcast_ref::<Output>().map(|output| (*id, output.signal))
+                }
+            })
+            .collect()
+    }
+
+
+
+
+    /// Finds the gates making up a named pin, least significant bit first.
+    /// A pin is a TOGGLE (input) or LIGHT (output) gate, matched by its name.
+    /// If no gate has the exact name, gates named "name[0]", "name[1]", ... are treated as a bus.
+    /// Gates sharing a name are all returned, ordered by id, `find_pin_bits` tells that apart
+    pub fn find_pin(&self, name: &str) -> Vec<usize> {
+        self.pin_candidates(name).into_iter().map(|(_, id)| id).collect()
+    }
+
+    /// Like `find_pin`, with the bit number of every gate, so buses with missing bits keep their values in place
+    /// Fails if two gates are the same pin or the same bit of a bus
+    pub fn find_pin_bits(&self, name: &str) -> Result<Vec<(usize, usize)>, CircuitError> {
+        let bits = self.pin_candidates(name);
+        for pair in bits.windows(2) {
+            if pair[0].0 == pair[1].0 {
+                let ids = bits.iter().filter(|(bit, _)| *bit == pair[0].0).map(|(_, id)| *id).collect();
+                // the gates' own name, `name` or one bit of it
+                let name = self
+                    .live_data
+                    .get(&pair[0].1)
+                    .and_then(|item| item.as_any().downcast_ref::<Gate>())
+                    .map_or_else(|| name.to_string(), |gate| gate.name.clone());
+                return Err(CircuitError::DuplicatePin { name, ids });
+            }
+        }
+        Ok(bits)
     }
 
+    /// (bit, gate id) of every pin gate answering to `name`, sorted
+    fn pin_candidates(&self, name: &str) -> Vec<(usize, usize)> {
+        let pins: Vec<&Gate> = self
+            .live_data
+            .values()
+            .filter_map(|item| item.as_any().downcast_ref::<Gate>())
+            .filter(|gate| {
+                matches!(
+                    gate.kind,
+                    GateKind::Primitive(PrimitiveKind::TOGGLE) | GateKind::Primitive(PrimitiveKind::LIGHT)
+                )
+            })
+            .collect();
+
+        let mut exact: Vec<(usize, usize)> = pins.iter().filter(|gate| gate.name == name).map(|gate| (0, gate.id)).collect();
+        if !exact.is_empty() {
+            exact.sort();
+            return exact;
+        }
 
+        let mut bits: Vec<(usize, usize)> = pins
+            .iter()
+            .filter_map(|gate| {
+                let index = gate.name.strip_prefix(name)?.strip_prefix('[')?.strip_suffix(']')?;
+                index.parse::<usize>().ok().map(|i| (i, gate.id))
+            })
+            .collect();
+        bits.sort();
+        bits
+    }
 
+    /// Sets the state of an input pin (or bus) to the given value, bit n of the value goes to `name[n]`
+    pub fn set_pin(&mut self, name: &str, value: u64) -> Result<(), Box<dyn Error>> {
+        let bits = self.find_pin_bits(name)?;
+        if bits.is_empty() {
+            return Err(format!("No pin named {} on this board", name).into());
+        }
+        for (bit, id) in bits {
+            if let Some(gate) = self.get_gate_mut(id) {
+                gate.state = bit < 64 && (value >> bit) & 1 == 1;
+            }
+        }
+        Ok(())
+    }
 
-    /// Applies the wire signals to the inputs in live_data
-    /// This method ensures that every input is updated with the latest signal from the wire signals
-    /// If a wire signal is not present, the input signal defaults to false
-    /// This is necessary to ensure that all inputs are in a consistent state
-    /// even if they are not connected to any wire.
-    fn apply_wire_signals_to_inputs(&mut self, wire_signals: &HashMap<usize, bool>) {
-        //every input must be updated, even if signals are not present in wire_signals
-        for (input_id, input) in self.live_data.iter_mut() {
-            if let Some(input) = input.as_any_mut().downcast_mut::<Input>() {
-                // Set the input signal based on wire signals or default to false
-                input.signal = wire_signals.get(&input_id).cloned().unwrap_or(false);
+    /// Reads the state of a pin (or bus) as an integer, `name[n]` is bit n of the value
+    pub fn read_pin(&self, name: &str) -> Result<u64, Box<dyn Error>> {
+        let bits = self.find_pin_bits(name)?;
+        if bits.is_empty() {
+            return Err(format!("No pin named {} on this board", name).into());
+        }
+        let mut value = 0;
+        for (bit, id) in bits.into_iter().filter(|(bit, _)| *bit < 64) {
+            let state = self
+                .live_data
+                .get(&id)
+                .and_then(|item| item.as_any().downcast_ref::<Gate>())
+                .is_some_and(|gate| gate.state);
+            if state {
+                value |= 1 << bit;
             }
         }
+        Ok(value)
     }
 
 
 
 
     // Helper methods for cleaner access
+    fn get_gate_mut(&mut self, id: usize) -> Option<&mut Gate> {
+        self.live_data
+            .get_mut(&id)?
+            .as_any_mut()
+            .downcast_mut::<Gate>()
+    }
+
     fn get_output_mut(&mut self, id: usize) -> Option<&mut Output> {
         self.live_data
             .get_mut(&id)?
@@ -333,4 +764,12 @@ impl Data{
 
 }
 
-    
\ No newline at end of file
+/// How far a chip's contents have to move to be centered on `pos`
+fn chip_offset(chip: &ChipDefenition, pos: Pos2) -> egui::Vec2 {
+    let positions: Vec<Pos2> = chip.sub_gates.values().map(|gate| gate.position.to_pos2()).collect();
+    if positions.is_empty() {
+        egui::Vec2::ZERO
+    } else {
+        pos - egui::Rect::from_points(&positions).center()
+    }
+}
@@ -0,0 +1,406 @@
+use std::collections::HashSet;
+use std::ops::Range;
+use std::sync::Arc;
+
+use super::*;
+
+/// Below this many gates per thread, starting the threads costs more than it saves
+pub const MIN_GATES_PER_THREAD: usize = 4096;
+
+/// A gate of the netlist, the ranges index into `Netlist::fan_in`
+#[derive(Debug, Clone)]
+pub struct NetGate {
+    pub id: usize,
+    pub kind: Result<PrimitiveKind, CircuitError>, // custom chips can't be simulated
+    pub state: bool,
+    pub fan_in: Range<usize>,
+    pub n_outs: usize,
+    pub output: Option<Result<usize, usize>>, // index of the first output, or its id if that is not an output
+    pub user_driven: bool, // TOGGLE and PULSE, whose state is also changed by clicking
+}
+
+/// The board compiled into flat arrays, so a tick is a few passes over vectors
+/// Gates, inputs, outputs and wires are numbered from 0 in id order, all references are those indices
+#[derive(Debug, Clone, Default)]
+pub struct Netlist {
+    pub gates: Vec<NetGate>,
+    pub fan_in: Vec<usize>, // input indices of every gate, one after the other
+
+    pub input_ids: Vec<usize>,
+    pub input_signals: Vec<bool>,
+
+    pub output_ids: Vec<usize>,
+    pub output_signals: Vec<bool>,
+    pub fan_out: Vec<Range<usize>>, // per output, into `wire_order`
+    pub wire_order: Vec<usize>,     // wire indices of every output, one after the other
+
+    pub wire_ids: Vec<usize>,
+    pub wire_signals: Vec<bool>,
+    pub wire_dest: Vec<Option<usize>>, // input index
+
+    /// Problems found while compiling, reported every tick like the board did
+    pub static_errors: Vec<Vec<CircuitError>>, // per gate
+    pub bad_wires: Vec<Vec<usize>>,            // per output, wire ids that are not wires
+    pub layout: Arc<SignalLayout>,             // ids in the order history snapshots store signals
+    pub changes: usize,                        // gate states and signals the last tick changed
+    edits: u64,                                // `Data::edits` when compiled
+
+    // reused between ticks
+    next_inputs: Vec<bool>,
+    changed_gates: Vec<usize>,
+    changed_inputs: Vec<usize>,
+    changed_outputs: Vec<usize>,
+    changed_wires: Vec<usize>,
+}
+
+impl Netlist {
+    pub fn compile(live_data: &HashMap<usize, Box<dyn Logical>>) -> Self {
+        let mut ids: Vec<usize> = live_data.keys().copied().collect();
+        ids.sort();
+
+        let mut netlist = Netlist::default();
+        let mut input_index = HashMap::new();
+        let mut output_index = HashMap::new();
+        let mut wire_index = HashMap::new();
+        for &id in &ids {
+            let any = live_data[&id].as_any();
+            if let Some(input) = any.downcast_ref::<Input>() {
+                input_index.insert(id, netlist.input_ids.len());
+                netlist.input_ids.push(id);
+                netlist.input_signals.push(input.signal);
+            } else if let Some(output) = any.downcast_ref::<Output>() {
+                output_index.insert(id, netlist.output_ids.len());
+                netlist.output_ids.push(id);
+                netlist.output_signals.push(output.signal);
+            } else if let Some(wire) = any.downcast_ref::<Wire>() {
+                wire_index.insert(id, netlist.wire_ids.len());
+                netlist.wire_ids.push(id);
+                netlist.wire_signals.push(wire.get_signal());
+            }
+        }
+
+        for &id in &netlist.wire_ids {
+            let wire = live_data[&id].as_any().downcast_ref::<Wire>().expect("indexed as a wire");
+            netlist.wire_dest.push(wire.dest.and_then(|dest| input_index.get(&dest).copied()));
+        }
+        for &id in &netlist.output_ids {
+            let output = live_data[&id].as_any().downcast_ref::<Output>().expect("indexed as an output");
+            let start = netlist.wire_order.len();
+            let mut bad = Vec::new();
+            for wire_id in &output.out_wire_ids {
+                match wire_index.get(wire_id) {
+                    Some(&wire) => netlist.wire_order.push(wire),
+                    None => bad.push(*wire_id),
+                }
+            }
+            netlist.fan_out.push(start..netlist.wire_order.len());
+            netlist.bad_wires.push(bad);
+        }
+
+        let wired: HashSet<usize> = netlist.wire_dest.iter().flatten().copied().collect();
+        for &id in &ids {
+            let Some(gate) = live_data[&id].as_any().downcast_ref::<Gate>() else {
+                continue;
+            };
+            let kind = match &gate.kind {
+                GateKind::Primitive(kind) => Ok(kind.clone()),
+                _ => Err(CircuitError::Unsupported {
+                    id: Some(id),
+                    message: format!("{} can't be simulated, flatten it first", gate.name),
+                }),
+            };
+            let start = netlist.fan_in.len();
+            let mut errors = Vec::new();
+            for input_id in gate.ins.keys() {
+                match input_index.get(input_id) {
+                    Some(&input) => {
+                        if !wired.contains(&input) {
+                            errors.push(CircuitError::UnwiredInput { id, input_id: *input_id });
+                        }
+                        netlist.fan_in.push(input);
+                    }
+                    None => errors.push(CircuitError::UnconnectedPin { id, pin_id: *input_id }),
+                }
+            }
+            let output = gate
+                .outs
+                .keys()
+                .next()
+                .map(|out_id| output_index.get(out_id).copied().ok_or(*out_id));
+            netlist.gates.push(NetGate {
+                id,
+                user_driven: matches!(gate.kind, GateKind::Primitive(PrimitiveKind::TOGGLE | PrimitiveKind::PULSE)),
+                kind,
+                state: gate.state,
+                fan_in: start..netlist.fan_in.len(),
+                n_outs: gate.outs.len(),
+                output,
+            });
+            netlist.static_errors.push(errors);
+        }
+        netlist.layout = Arc::new(SignalLayout {
+            gate_ids: netlist.gates.iter().map(|gate| gate.id).collect(),
+            input_ids: netlist.input_ids.clone(),
+            output_ids: netlist.output_ids.clone(),
+            wire_ids: netlist.wire_ids.clone(),
+        });
+        netlist
+    }
+
+    /// False once the board was edited after compiling, `edits` is the board's edit count
+    pub fn matches(&self, edits: u64) -> bool {
+        self.edits == edits
+    }
+
+    /// Picks up state changed from outside the simulation, TOGGLE and PULSE clicks and `set_pin`
+    pub fn pull_user_state(&mut self, live_data: &HashMap<usize, Box<dyn Logical>>) {
+        for gate in self.gates.iter_mut().filter(|gate| gate.user_driven) {
+            if let Some(live) = live_data.get(&gate.id).and_then(|item| item.as_any().downcast_ref::<Gate>()) {
+                gate.state = live.state;
+            }
+        }
+    }
+
+    /// One tick: every gate reads its inputs, drives its output and wires, and the wires drive the inputs
+    /// Inputs with no wire driving them read false
+    /// Every gate reads the signals of the previous tick, so the gates of a tick are independent of each other
+    /// and can be split over `threads` threads; their results are merged in gate order, so the outcome is
+    /// the same for any number of threads
+    pub fn tick(&mut self, threads: usize, errors: &mut Vec<CircuitError>) {
+        let pending = self.pending_changes();
+        let chunks = threads.min(self.gates.len() / MIN_GATES_PER_THREAD).max(1);
+        let chunk_size = self.gates.len().div_ceil(chunks).max(1);
+        let shared = Shared {
+            static_errors: &self.static_errors,
+            bad_wires: &self.bad_wires,
+            fan_in: &self.fan_in,
+            input_signals: &self.input_signals,
+        };
+
+        let results: Vec<GateResults> = if chunks == 1 {
+            vec![evaluate_gates(&mut self.gates, 0, &shared)]
+        } else {
+            crossbeam::scope(|scope| {
+                let handles: Vec<_> = self
+                    .gates
+                    .chunks_mut(chunk_size)
+                    .enumerate()
+                    .map(|(chunk, gates)| {
+                        let shared = &shared;
+                        scope.spawn(move |_| evaluate_gates(gates, chunk * chunk_size, shared))
+                    })
+                    .collect();
+                handles.into_iter().map(|handle| handle.join().expect("gate evaluation panicked")).collect()
+            })
+            .expect("gate evaluation panicked")
+        };
+
+        self.next_inputs.clear();
+        self.next_inputs.resize(self.input_ids.len(), false);
+        for result in results {
+            errors.extend(result.errors);
+            self.changed_gates.extend(result.changed_gates);
+            for (output, signal) in result.outputs {
+                if self.output_signals[output] != signal {
+                    self.output_signals[output] = signal;
+                    self.changed_outputs.push(output);
+                }
+                for &wire in &self.wire_order[self.fan_out[output].clone()] {
+                    if self.wire_signals[wire] != signal {
+                        self.wire_signals[wire] = signal;
+                        self.changed_wires.push(wire);
+                    }
+                    if let Some(dest) = self.wire_dest[wire] {
+                        self.next_inputs[dest] = signal;
+                    }
+                }
+            }
+        }
+
+        for (index, (signal, next)) in self.input_signals.iter_mut().zip(&self.next_inputs).enumerate() {
+            if signal != next {
+                *signal = *next;
+                self.changed_inputs.push(index);
+            }
+        }
+        self.changes = self.pending_changes() - pending;
+    }
+
+    /// Every gate state and signal, in the order of `layout`
+    pub fn signals(&self) -> impl Iterator<Item = bool> + '_ {
+        self.gates
+            .iter()
+            .map(|gate| gate.state)
+            .chain(self.input_signals.iter().copied())
+            .chain(self.output_signals.iter().copied())
+            .chain(self.wire_signals.iter().copied())
+    }
+
+    /// Forgets the changes waiting to be written back, for ticks that must not reach the board
+    pub fn discard_changes(&mut self) {
+        self.changed_gates.clear();
+        self.changed_inputs.clear();
+        self.changed_outputs.clear();
+        self.changed_wires.clear();
+    }
+
+    /// Changes waiting to be written back to the board
+    fn pending_changes(&self) -> usize {
+        self.changed_gates.len() + self.changed_inputs.len() + self.changed_outputs.len() + self.changed_wires.len()
+    }
+
+    /// Copies everything that changed since the last call back onto the board
+    pub fn write_back(&mut self, live_data: &mut HashMap<usize, Box<dyn Logical>>) {
+        for index in self.changed_gates.drain(..) {
+            let gate = &self.gates[index];
+            if let Some(live) = item_mut::<Gate>(live_data, gate.id) {
+                live.state = gate.state;
+            }
+        }
+        for index in self.changed_inputs.drain(..) {
+            if let Some(input) = item_mut::<Input>(live_data, self.input_ids[index]) {
+                input.signal = self.input_signals[index];
+            }
+        }
+        for index in self.changed_outputs.drain(..) {
+            if let Some(output) = item_mut::<Output>(live_data, self.output_ids[index]) {
+                output.signal = self.output_signals[index];
+            }
+        }
+        for index in self.changed_wires.drain(..) {
+            if let Some(wire) = item_mut::<Wire>(live_data, self.wire_ids[index]) {
+                wire.set_signal(self.wire_signals[index]);
+            }
+        }
+    }
+}
+
+/// The parts of the netlist every thread reads while evaluating gates
+struct Shared<'a> {
+    static_errors: &'a [Vec<CircuitError>],
+    bad_wires: &'a [Vec<usize>],
+    fan_in: &'a [usize],
+    input_signals: &'a [bool],
+}
+
+/// What evaluating a run of gates produced, in gate order
+#[derive(Default)]
+struct GateResults {
+    errors: Vec<CircuitError>,
+    changed_gates: Vec<usize>,
+    outputs: Vec<(usize, bool)>, // output index, signal
+}
+
+/// Evaluates `gates`, which start at gate number `first` of the netlist
+fn evaluate_gates(gates: &mut [NetGate], first: usize, shared: &Shared) -> GateResults {
+    let mut results = GateResults::default();
+    let mut ins = Vec::new();
+    for (offset, gate) in gates.iter_mut().enumerate() {
+        let index = first + offset;
+        results.errors.extend(shared.static_errors[index].iter().cloned());
+        let kind = match &gate.kind {
+            Ok(kind) => kind,
+            Err(e) => {
+                results.errors.push(e.clone());
+                continue;
+            }
+        };
+        ins.clear();
+        ins.extend(shared.fan_in[gate.fan_in.clone()].iter().map(|&input| shared.input_signals[input]));
+
+        let old_state = gate.state;
+        let result = kind.evaluate(gate.id, &mut gate.state, &ins, gate.n_outs);
+        if gate.state != old_state {
+            results.changed_gates.push(index);
+        }
+        let signal = match result {
+            Ok(Some(signal)) => signal,
+            Ok(None) => continue,
+            Err(e) => {
+                results.errors.push(e);
+                continue;
+            }
+        };
+        match gate.output {
+            Some(Ok(output)) => {
+                results.outputs.push((output, signal));
+                results.errors.extend(
+                    shared.bad_wires[output]
+                        .iter()
+                        .map(|&id| CircuitError::InvalidDowncast { id, expected: "Wire" }),
+                );
+            }
+            Some(Err(id)) => results.errors.push(CircuitError::InvalidDowncast { id, expected: "Output" }),
+            None => {}
+        }
+    }
+    results
+}
+
+fn item_mut<T: 'static>(live_data: &mut HashMap<usize, Box<dyn Logical>>, id: usize) -> Option<&mut T> {
+    live_data.get_mut(&id)?.as_any_mut().downcast_mut::<T>()
+}
+
+impl Data {
+    /// Counts an edit, so the netlist is compiled again the next time it is needed
+    /// Every method that adds, removes or rewires items calls this, directly or through `check_graph_after_edit`,
+    /// and so must code that edits `live_data` itself; restoring signals counts as an edit too
+    pub fn invalidate_netlist(&mut self) {
+        self.edits += 1;
+    }
+
+    /// The compiled form of the board, compiling it if the board was edited since
+    pub fn netlist(&mut self) -> &mut Netlist {
+        if self.netlist.as_ref().is_some_and(|netlist| !netlist.matches(self.edits)) {
+            self.netlist = None;
+        }
+        self.netlist.get_or_insert_with(|| Netlist {
+            edits: self.edits,
+            ..Netlist::compile(&self.live_data)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Rings of an XOR and two NOTs that never settle, each XOR also reads a ring far away
+    /// so signals cross from one thread's gates into another's
+    fn rings(count: usize) -> Data {
+        let mut data = Data::new();
+        let mut rings = Vec::new();
+        for _ in 0..count {
+            let ring = [PrimitiveKind::XOR, PrimitiveKind::NOT, PrimitiveKind::NOT];
+            rings.push(ring.map(|kind| data.add_primitive(kind, Pos2::ZERO)));
+        }
+        for (index, &[xor, first, second]) in rings.iter().enumerate() {
+            let far = rings[(index + count / 2) % count][1];
+            for (from, to, input) in [(xor, first, 0), (first, second, 0), (second, xor, 0), (far, xor, 1)] {
+                let output = data.gate_outputs(from)[0];
+                let input = data.gate_inputs(to)[input];
+                data.connect(output, input).expect("output to input");
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn threads_give_the_same_ticks() {
+        let data = rings(MIN_GATES_PER_THREAD + 100); // three gates per ring, so the gates are split three ways
+        let mut single = Netlist::compile(&data.live_data);
+        let mut split = single.clone();
+        for tick in 0..16 {
+            let (mut single_errors, mut split_errors) = (Vec::new(), Vec::new());
+            single.tick(1, &mut single_errors);
+            split.tick(4, &mut split_errors);
+            assert!(single.changes > 0, "the rings stopped changing at tick {}", tick);
+            assert_eq!(single_errors, split_errors, "errors of tick {}", tick);
+            let states = |netlist: &Netlist| netlist.gates.iter().map(|gate| gate.state).collect::<Vec<bool>>();
+            assert_eq!(states(&single), states(&split), "gate states after tick {}", tick);
+            assert_eq!(single.input_signals, split.input_signals, "inputs after tick {}", tick);
+            assert_eq!(single.output_signals, split.output_signals, "outputs after tick {}", tick);
+            assert_eq!(single.wire_signals, split.wire_signals, "wires after tick {}", tick);
+        }
+    }
+}
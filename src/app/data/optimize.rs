@@ -0,0 +1,516 @@
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+
+use super::*;
+
+/// Passes are repeated until none of them changes anything, or this many rounds ran
+const MAX_OPTIMIZE_ROUNDS: usize = 64;
+
+/// One of the optimizations `Data::optimize` runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum OptimizePass {
+    /// Gates fed by HI-SIGNAL or LO-SIGNAL (or nothing) are simplified or become constants themselves
+    ConstantPropagation,
+    /// NOT(NOT(x)) is replaced by x
+    DoubleNot,
+    /// BUFFER(x) is replaced by x
+    RedundantBuffer,
+    /// Gates that no LIGHT depends on are removed
+    DeadLogic,
+    /// Gates of the same kind with the same inputs are merged into one
+    CommonSubexpression,
+}
+
+impl OptimizePass {
+    pub const ALL: [OptimizePass; 5] = [
+        OptimizePass::ConstantPropagation,
+        OptimizePass::DoubleNot,
+        OptimizePass::RedundantBuffer,
+        OptimizePass::CommonSubexpression,
+        OptimizePass::DeadLogic,
+    ];
+}
+
+impl Display for OptimizePass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            OptimizePass::ConstantPropagation => "Constant propagation",
+            OptimizePass::DoubleNot => "Double NOT removal",
+            OptimizePass::RedundantBuffer => "Redundant BUFFER removal",
+            OptimizePass::DeadLogic => "Dead logic elimination",
+            OptimizePass::CommonSubexpression => "Common subexpression merging",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Gate counts before and after optimizing, and how many gates every pass changed or removed
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct OptimizeReport {
+    pub gates_before: usize,
+    pub gates_after: usize,
+    pub wires_before: usize,
+    pub wires_after: usize,
+    pub passes: Vec<(OptimizePass, usize)>,
+}
+
+impl Display for OptimizeReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Gates: {} -> {}", self.gates_before, self.gates_after)?;
+        writeln!(f, "Wires: {} -> {}", self.wires_before, self.wires_after)?;
+        for (pass, count) in &self.passes {
+            writeln!(f, "  {}: {}", pass, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// The value a gate input has no matter what the pins do, if it has one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Driver {
+    Constant(bool),
+    Output(usize),
+}
+
+impl Data {
+    /// Simplifies a flattened board without changing what its LIGHTs settle to
+    /// TOGGLE, PULSE and LIGHT pins are never touched, timing in ticks may change as gates disappear
+    pub fn optimize(&mut self) -> Result<OptimizeReport, CircuitError> {
+        if let Some(gate) = self.gates().find(|gate| matches!(gate.kind, GateKind::Custom(_))) {
+            return Err(CircuitError::Unsupported {
+                id: Some(gate.id),
+                message: format!("{} is a chip, flatten the board before optimizing", gate.name),
+            });
+        }
+        let _ids = self.ids.enter();
+        let gates_before = self.gates().count();
+        let wires_before = self.wire_count();
+
+        let mut passes: Vec<(OptimizePass, usize)> = OptimizePass::ALL.iter().map(|pass| (*pass, 0)).collect();
+        for _ in 0..MAX_OPTIMIZE_ROUNDS {
+            let mut changed = 0;
+            for (pass, count) in &mut passes {
+                let n = match pass {
+                    OptimizePass::ConstantPropagation => self.propagate_constants(),
+                    OptimizePass::DoubleNot => self.remove_double_nots(),
+                    OptimizePass::RedundantBuffer => self.remove_buffers(),
+                    OptimizePass::DeadLogic => self.remove_dead_logic(),
+                    OptimizePass::CommonSubexpression => self.merge_common_gates(),
+                };
+                *count += n;
+                changed += n;
+            }
+            if changed == 0 {
+                break;
+            }
+        }
+
+        self.check_graph_after_edit();
+        Ok(OptimizeReport {
+            gates_before,
+            gates_after: self.gates().count(),
+            wires_before,
+            wires_after: self.wire_count(),
+            passes,
+        })
+    }
+
+    fn gates(&self) -> impl Iterator<Item = &Gate> {
+        self.live_data.values().filter_map(|item| item.as_any().downcast_ref::<Gate>())
+    }
+
+    fn wire_count(&self) -> usize {
+        self.live_data.values().filter(|item| item.as_any().is::<Wire>()).count()
+    }
+
+    /// Ids of every gate of the given kinds, sorted so passes always run in the same order
+    fn gates_of_kind(&self, kinds: &[PrimitiveKind]) -> Vec<usize> {
+        let mut ids: Vec<usize> = self
+            .gates()
+            .filter(|gate| matches!(&gate.kind, GateKind::Primitive(kind) if kinds.contains(kind)))
+            .map(|gate| gate.id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    fn primitive_kind(&self, gate_id: usize) -> Option<PrimitiveKind> {
+        match &self.live_data.get(&gate_id)?.as_any().downcast_ref::<Gate>()?.kind {
+            GateKind::Primitive(kind) => Some(kind.clone()),
+            _ => None,
+        }
+    }
+
+    /// What feeds an input: an output, or a constant for HI-SIGNAL, LO-SIGNAL and unconnected inputs
+    fn driver(&self, input_id: usize) -> Driver {
+        let wire = self
+            .live_data
+            .get(&input_id)
+            .and_then(|item| item.as_any().downcast_ref::<Input>())
+            .and_then(|input| input.source_wire_id)
+            .and_then(|wire_id| self.live_data.get(&wire_id)?.as_any().downcast_ref::<Wire>());
+        let Some(wire) = wire else {
+            return Driver::Constant(false); // unconnected inputs read false
+        };
+        match self.output_gate(wire.source_id).and_then(|gate_id| self.primitive_kind(gate_id)) {
+            Some(PrimitiveKind::HISIGNAL) => Driver::Constant(true),
+            Some(PrimitiveKind::LOSIGNAL) => Driver::Constant(false),
+            _ => Driver::Output(wire.source_id),
+        }
+    }
+
+    /// Makes every gate that reads `from` read the output `to` instead, then removes the gate behind `from`
+    fn replace_gate(&mut self, gate_id: usize, from: usize, to: usize) {
+        self.move_fan_out(from, to);
+        self.remove_gate(gate_id);
+    }
+
+    /// Drops some inputs of a gate and renumbers the rest
+    fn drop_inputs(&mut self, gate_id: usize, dropped: &[usize]) {
+        for input_id in dropped {
+            let wire = self.get_input_mut(*input_id).and_then(|input| input.source_wire_id);
+            if let Some(wire_id) = wire {
+                self.remove_wire(wire_id);
+            }
+            self.live_data.remove(input_id);
+        }
+        let remaining = self.gate_inputs(gate_id);
+        for (index, input_id) in remaining.iter().enumerate() {
+            if let Some(input) = self.get_input_mut(*input_id) {
+                input.index = index;
+            }
+        }
+        if let Some(gate) = self.get_gate_mut(gate_id) {
+            gate.ins.retain(|id, _| !dropped.contains(id));
+            gate.n_in = remaining.len();
+        }
+    }
+
+    fn set_kind(&mut self, gate_id: usize, kind: PrimitiveKind) {
+        if let Some(gate) = self.get_gate_mut(gate_id) {
+            gate.name = kind.to_string();
+            gate.kind = GateKind::Primitive(kind);
+        }
+    }
+
+    /// Turns a gate into a HI-SIGNAL or LO-SIGNAL, keeping its output and the wires leaving it
+    fn make_constant(&mut self, gate_id: usize, value: bool) {
+        let inputs = self.gate_inputs(gate_id);
+        self.drop_inputs(gate_id, &inputs);
+        self.set_kind(gate_id, if value { PrimitiveKind::HISIGNAL } else { PrimitiveKind::LOSIGNAL });
+    }
+
+    fn propagate_constants(&mut self) -> usize {
+        use PrimitiveKind::{AND, BUFFER, NAND, NOR, NOT, OR, XOR};
+        let mut changed = 0;
+        for gate_id in self.gates_of_kind(&[BUFFER, NOT, AND, OR, XOR, NAND, NOR]) {
+            let Some(kind) = self.primitive_kind(gate_id) else {
+                continue;
+            };
+            let inputs = self.gate_inputs(gate_id);
+            let drivers: Vec<Driver> = inputs.iter().map(|id| self.driver(*id)).collect();
+            let constants: Vec<(usize, bool)> = inputs
+                .iter()
+                .zip(&drivers)
+                .filter_map(|(id, driver)| match driver {
+                    Driver::Constant(value) => Some((*id, *value)),
+                    Driver::Output(_) => None,
+                })
+                .collect();
+            if constants.is_empty() {
+                continue;
+            }
+
+            // every input known: evaluate the gate the way the simulator does
+            if constants.len() == inputs.len() {
+                let ins: Vec<bool> = constants.iter().map(|(_, value)| *value).collect();
+                let mut state = false;
+                if let Ok(Some(value)) = kind.evaluate(gate_id, &mut state, &ins, 1) {
+                    self.make_constant(gate_id, value);
+                    changed += 1;
+                }
+                continue;
+            }
+
+            // one input decides the result no matter what the others are
+            let controlling = match kind {
+                AND => Some((false, false)),
+                NAND => Some((false, true)),
+                OR => Some((true, true)),
+                NOR => Some((true, false)),
+                _ => None,
+            };
+            if let Some((input, output)) = controlling
+                && constants.iter().any(|(_, value)| *value == input)
+            {
+                self.make_constant(gate_id, output);
+                changed += 1;
+                continue;
+            }
+
+            // the rest don't change the result: 1s into AND and NAND, 0s into OR and NOR,
+            // and pairs of 1s or any 0 into XOR
+            let mut dropped: Vec<usize> = constants.iter().map(|(id, _)| *id).collect();
+            let mut invert = false;
+            if kind == XOR && constants.iter().filter(|(_, value)| *value).count() % 2 == 1 {
+                invert = true;
+            }
+            let remaining = inputs.len() - dropped.len();
+            if invert && remaining > 1 {
+                // keep one HI input to do the inverting, XOR has no inverted form with fewer inputs
+                let keep = constants.iter().position(|(_, value)| *value).expect("odd number of 1s");
+                dropped.remove(keep);
+                if dropped.is_empty() {
+                    continue;
+                }
+            }
+            self.drop_inputs(gate_id, &dropped);
+            if self.gate_inputs(gate_id).len() == 1 {
+                let single = match kind {
+                    AND | OR => BUFFER,
+                    XOR if invert => NOT,
+                    XOR => BUFFER,
+                    NAND | NOR => NOT,
+                    other => other,
+                };
+                self.set_kind(gate_id, single);
+            }
+            changed += 1;
+        }
+        changed
+    }
+
+    fn remove_double_nots(&mut self) -> usize {
+        let mut removed = 0;
+        for gate_id in self.gates_of_kind(&[PrimitiveKind::NOT]) {
+            let (Some(&input), Some(&output)) = (self.gate_inputs(gate_id).first(), self.gate_outputs(gate_id).first()) else {
+                continue;
+            };
+            let Driver::Output(inner_output) = self.driver(input) else {
+                continue;
+            };
+            let Some(inner) = self.output_gate(inner_output).filter(|id| self.primitive_kind(*id) == Some(PrimitiveKind::NOT)) else {
+                continue;
+            };
+            let Some(&inner_input) = self.gate_inputs(inner).first() else {
+                continue;
+            };
+            match self.driver(inner_input) {
+                // two NOTs feeding each other hold a value, they are a latch and not a double NOT
+                Driver::Output(source) if source != output => {
+                    self.replace_gate(gate_id, output, source);
+                    removed += 1;
+                }
+                _ => {}
+            }
+        }
+        removed
+    }
+
+    fn remove_buffers(&mut self) -> usize {
+        let mut removed = 0;
+        for gate_id in self.gates_of_kind(&[PrimitiveKind::BUFFER]) {
+            let (Some(&input), Some(&output)) = (self.gate_inputs(gate_id).first(), self.gate_outputs(gate_id).first()) else {
+                continue;
+            };
+            match self.driver(input) {
+                Driver::Output(source) if source != output => {
+                    self.replace_gate(gate_id, output, source);
+                    removed += 1;
+                }
+                _ => {}
+            }
+        }
+        removed
+    }
+
+    /// Removes every gate that no LIGHT reads from, directly or through other gates
+    fn remove_dead_logic(&mut self) -> usize {
+        use PrimitiveKind::{LIGHT, PULSE, TOGGLE};
+        let mut live: HashSet<usize> = HashSet::new();
+        let mut stack = self.gates_of_kind(&[LIGHT, TOGGLE, PULSE]);
+        while let Some(gate_id) = stack.pop() {
+            if !live.insert(gate_id) {
+                continue;
+            }
+            for input in self.gate_inputs(gate_id) {
+                let Driver::Output(output) = self.driver(input) else {
+                    // constants are kept while something reads them
+                    if let Some(source) = self.source_gate(input) {
+                        stack.push(source);
+                    }
+                    continue;
+                };
+                if let Some(parent) = self.output_gate(output) {
+                    stack.push(parent);
+                }
+            }
+        }
+
+        let mut dead: Vec<usize> = self.gates().map(|gate| gate.id).filter(|id| !live.contains(id)).collect();
+        dead.sort();
+        for gate_id in &dead {
+            self.remove_gate(*gate_id);
+        }
+        dead.len()
+    }
+
+    /// The gate driving an input, whatever kind it is
+    fn source_gate(&self, input_id: usize) -> Option<usize> {
+        let wire_id = self.live_data.get(&input_id)?.as_any().downcast_ref::<Input>()?.source_wire_id?;
+        let source = self.live_data.get(&wire_id)?.as_any().downcast_ref::<Wire>()?.source_id;
+        self.output_gate(source)
+    }
+
+    /// The gate an output belongs to
+    fn output_gate(&self, output_id: usize) -> Option<usize> {
+        self.live_data.get(&output_id)?.as_any().downcast_ref::<Output>()?.parent_id
+    }
+
+    /// Merges gates that compute the same thing from the same signals, keeping the one with the lowest id
+    fn merge_common_gates(&mut self) -> usize {
+        use PrimitiveKind::{AND, BUFFER, HISIGNAL, LOSIGNAL, NAND, NOR, NOT, OR, XOR};
+        let mut seen: HashMap<(PrimitiveKind, Vec<usize>), usize> = HashMap::new(); // -> output of the kept gate
+        let mut merged = 0;
+        for gate_id in self.gates_of_kind(&[HISIGNAL, LOSIGNAL, BUFFER, NOT, AND, OR, XOR, NAND, NOR]) {
+            let (Some(kind), Some(&output)) = (self.primitive_kind(gate_id), self.gate_outputs(gate_id).first()) else {
+                continue;
+            };
+            let sources: Option<Vec<usize>> = self
+                .gate_inputs(gate_id)
+                .into_iter()
+                .map(|input| match self.driver(input) {
+                    Driver::Output(source) => Some(source),
+                    // HI-SIGNAL and LO-SIGNAL outputs, unconnected inputs are left to constant propagation
+                    Driver::Constant(_) => self.source_gate(input).and_then(|gate| self.gate_outputs(gate).first().copied()),
+                })
+                .collect();
+            let Some(mut sources) = sources else {
+                continue;
+            };
+            // every kind with more than one input is commutative
+            sources.sort();
+            if sources.contains(&output) {
+                continue; // reads itself, merging it would change what it holds
+            }
+            match seen.get(&(kind.clone(), sources.clone())) {
+                Some(&kept) => {
+                    self.replace_gate(gate_id, output, kept);
+                    merged += 1;
+                }
+                None => {
+                    seen.insert((kind, sources), output);
+                }
+            }
+        }
+        merged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gate(data: &mut Data, kind: PrimitiveKind, name: &str) -> usize {
+        let id = data.add_primitive(kind, Pos2::ZERO);
+        data.get_gate_mut(id).expect("gate").name = name.to_string();
+        id
+    }
+
+    fn wire(data: &mut Data, from: usize, to: usize, input: usize) {
+        let output = data.gate_outputs(from)[0];
+        let input = data.gate_inputs(to)[input];
+        data.connect(output, input).expect("output to input");
+    }
+
+    fn count(report: &OptimizeReport, pass: OptimizePass) -> usize {
+        report.passes.iter().find(|(p, _)| *p == pass).map_or(0, |(_, count)| *count)
+    }
+
+    /// Sets the given pins, lets the board settle and reads the pin `light`
+    fn run(data: &mut Data, pins: &[(&str, u64)], light: &str) -> u64 {
+        for (name, value) in pins {
+            data.set_pin(name, *value).expect("input pin");
+        }
+        assert!(data.settle(64), "the board should settle");
+        data.read_pin(light).expect("output pin")
+    }
+
+    #[test]
+    fn double_nots_in_a_ring_are_removed() {
+        // y = a OR NOT(NOT(y)), once high it stays high
+        let mut data = Data::new();
+        let a = gate(&mut data, PrimitiveKind::TOGGLE, "a");
+        let or = gate(&mut data, PrimitiveKind::OR, "OR");
+        let [inner, outer] = [(); 2].map(|_| gate(&mut data, PrimitiveKind::NOT, "NOT"));
+        let y = gate(&mut data, PrimitiveKind::LIGHT, "y");
+        for (from, to, input) in [(a, or, 0), (outer, or, 1), (or, inner, 0), (inner, outer, 0), (or, y, 0)] {
+            wire(&mut data, from, to, input);
+        }
+
+        let report = data.optimize().expect("only primitives");
+        assert_eq!(count(&report, OptimizePass::DoubleNot), 1);
+        assert_eq!((report.gates_before, report.gates_after), (5, 3));
+        let or_output = data.gate_outputs(or)[0];
+        assert_eq!(data.driver(data.gate_inputs(or)[1]), Driver::Output(or_output));
+        assert_eq!(run(&mut data, &[("a", 1)], "y"), 1);
+        assert_eq!(run(&mut data, &[("a", 0)], "y"), 1);
+    }
+
+    #[test]
+    fn two_nots_feeding_each_other_are_kept() {
+        let mut data = Data::new();
+        let [first, second] = [(); 2].map(|_| gate(&mut data, PrimitiveKind::NOT, "NOT"));
+        let y = gate(&mut data, PrimitiveKind::LIGHT, "y");
+        for (from, to, input) in [(first, second, 0), (second, first, 0), (first, y, 0)] {
+            wire(&mut data, from, to, input);
+        }
+
+        let report = data.optimize().expect("only primitives");
+        assert_eq!(count(&report, OptimizePass::DoubleNot), 0);
+        assert_eq!(report.gates_after, 3);
+    }
+
+    #[test]
+    fn partially_constant_gates_are_reduced() {
+        // y = a XOR 1 and z = b NAND 1 are both NOTs
+        let mut data = Data::new();
+        let a = gate(&mut data, PrimitiveKind::TOGGLE, "a");
+        let b = gate(&mut data, PrimitiveKind::TOGGLE, "b");
+        let hi = gate(&mut data, PrimitiveKind::HISIGNAL, "HI");
+        let xor = gate(&mut data, PrimitiveKind::XOR, "XOR");
+        let nand = gate(&mut data, PrimitiveKind::NAND, "NAND");
+        let y = gate(&mut data, PrimitiveKind::LIGHT, "y");
+        let z = gate(&mut data, PrimitiveKind::LIGHT, "z");
+        for (from, to, input) in [(a, xor, 0), (hi, xor, 1), (b, nand, 0), (hi, nand, 1), (xor, y, 0), (nand, z, 0)] {
+            wire(&mut data, from, to, input);
+        }
+
+        let report = data.optimize().expect("only primitives");
+        assert_eq!(count(&report, OptimizePass::ConstantPropagation), 2);
+        for id in [xor, nand] {
+            assert_eq!(data.primitive_kind(id), Some(PrimitiveKind::NOT));
+            assert_eq!(data.gate_inputs(id).len(), 1);
+        }
+        assert!(data.gates_of_kind(&[PrimitiveKind::HISIGNAL]).is_empty(), "nothing reads HI anymore");
+        assert_eq!(run(&mut data, &[("a", 0)], "y"), 1);
+        assert_eq!(run(&mut data, &[("a", 1)], "y"), 0);
+        assert_eq!(run(&mut data, &[("b", 1)], "z"), 0);
+    }
+
+    #[test]
+    fn constant_gates_are_merged() {
+        let mut data = Data::new();
+        let [first, second] = [(); 2].map(|_| gate(&mut data, PrimitiveKind::HISIGNAL, "HI"));
+        let y = gate(&mut data, PrimitiveKind::LIGHT, "y");
+        let z = gate(&mut data, PrimitiveKind::LIGHT, "z");
+        wire(&mut data, first, y, 0);
+        wire(&mut data, second, z, 0);
+
+        let report = data.optimize().expect("only primitives");
+        assert_eq!(count(&report, OptimizePass::CommonSubexpression), 1);
+        assert_eq!(data.gates_of_kind(&[PrimitiveKind::HISIGNAL]), vec![first]);
+        assert_eq!(data.source_gate(data.gate_inputs(z)[0]), Some(first));
+        assert_eq!(run(&mut data, &[], "z"), 1);
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::HashSet;
+
+use super::*;
+
+/// A strongly connected group of gates, every gate in it feeds back into itself through the wires
+#[derive(Debug, Clone, Default)]
+pub struct FeedbackLoop {
+    pub gates: Vec<usize>,
+    pub wires: Vec<usize>,
+}
+
+impl FeedbackLoop {
+    pub fn ids(&self) -> impl Iterator<Item = usize> + '_ {
+        self.gates.iter().chain(self.wires.iter()).copied()
+    }
+}
+
+/// Loops that kept changing after the board should have settled
+#[derive(Debug, Clone)]
+pub struct OscillationReport {
+    pub tick: u64,
+    pub loops: Vec<FeedbackLoop>,
+}
+
+impl OscillationReport {
+    pub fn message(&self) -> String {
+        format!(
+            "{} feedback loop(s) still changing after tick {}. \
+             A loop with an odd number of inverting gates (like a NOT wired to itself) never settles, \
+             its signals flip every few ticks forever.",
+            self.loops.len(),
+            self.tick
+        )
+    }
+}
+
+/// Watches the running simulation for boards that don't settle
+#[derive(Debug, Clone, Default)]
+pub struct OscillationMonitor {
+    pub report: Option<OscillationReport>,
+    unsettled_ticks: usize,
+}
+
+/// Every wire that carries a signal from one gate to another, as (source gate, dest gate, wire)
+pub fn gate_edges(live_data: &HashMap<usize, Box<dyn Logical>>) -> Vec<(usize, usize, usize)> {
+    let parent_of = |id: usize| -> Option<usize> {
+        let any = live_data.get(&id)?.as_any();
+        if let Some(output) = any.downcast_ref::<Output>() {
+            output.parent_id
+        } else {
+            any.downcast_ref::<Input>()?.parent_id
+        }
+    };
+
+    live_data
+        .values()
+        .filter_map(|item| item.as_any().downcast_ref::<Wire>())
+        .filter_map(|wire| Some((parent_of(wire.source_id)?, parent_of(wire.dest?)?, wire.id)))
+        .collect()
+}
+
+/// Gates in an order where every gate comes after the gates it reads from (Kahn's algorithm)
+/// Gates in or behind a feedback loop are left out, lowest ids go first among gates that are ready
+pub fn gate_order(live_data: &HashMap<usize, Box<dyn Logical>>) -> Vec<usize> {
+    let mut gates: Vec<usize> = live_data
+        .values()
+        .filter_map(|item| item.as_any().downcast_ref::<Gate>())
+        .map(|gate| gate.id)
+        .collect();
+    gates.sort();
+    let mut waiting: HashMap<usize, usize> = HashMap::new();
+    let mut successors: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (from, to, _) in gate_edges(live_data) {
+        *waiting.entry(to).or_default() += 1;
+        successors.entry(from).or_default().push(to);
+    }
+
+    let mut ready: Vec<usize> = gates.iter().rev().filter(|id| !waiting.contains_key(id)).copied().collect();
+    let mut order = Vec::with_capacity(gates.len());
+    while let Some(gate) = ready.pop() {
+        order.push(gate);
+        for next in successors.get(&gate).into_iter().flatten() {
+            let count = waiting.get_mut(next).expect("counted above");
+            *count -= 1;
+            if *count == 0 {
+                ready.push(*next);
+            }
+        }
+    }
+    order
+}
+
+/// Finds every feedback loop on the board with Tarjan's strongly connected components
+pub fn feedback_loops(live_data: &HashMap<usize, Box<dyn Logical>>) -> Vec<FeedbackLoop> {
+    let edges = gate_edges(live_data);
+    let mut gates: Vec<usize> = live_data
+        .values()
+        .filter_map(|item| item.as_any().downcast_ref::<Gate>())
+        .map(|gate| gate.id)
+        .collect();
+    gates.sort();
+
+    let mut successors: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (from, to, _) in &edges {
+        successors.entry(*from).or_default().push(*to);
+    }
+
+    // iterative Tarjan so long chains of gates can't overflow the stack
+    let mut index_of: HashMap<usize, usize> = HashMap::new();
+    let mut low: HashMap<usize, usize> = HashMap::new();
+    let mut on_stack: HashSet<usize> = HashSet::new();
+    let mut stack: Vec<usize> = Vec::new();
+    let mut components: Vec<Vec<usize>> = Vec::new();
+    let mut next_index = 0;
+
+    for &root in &gates {
+        if index_of.contains_key(&root) {
+            continue;
+        }
+        let mut work: Vec<(usize, usize)> = vec![(root, 0)]; // (gate, next successor to visit)
+        while let Some(&(gate, next)) = work.last() {
+            if next == 0 && !index_of.contains_key(&gate) {
+                index_of.insert(gate, next_index);
+                low.insert(gate, next_index);
+                next_index += 1;
+                stack.push(gate);
+                on_stack.insert(gate);
+            }
+
+            let succ = successors.get(&gate).map(|s| s.as_slice()).unwrap_or(&[]);
+            if let Some(&child) = succ.get(next) {
+                if let Some(top) = work.last_mut() {
+                    top.1 += 1;
+                }
+                if !index_of.contains_key(&child) {
+                    work.push((child, 0));
+                } else if on_stack.contains(&child) {
+                    let child_index = index_of[&child];
+                    let l = low.get_mut(&gate).unwrap();
+                    *l = (*l).min(child_index);
+                }
+                continue;
+            }
+
+            work.pop();
+            if let Some(&(parent, _)) = work.last() {
+                let gate_low = low[&gate];
+                let l = low.get_mut(&parent).unwrap();
+                *l = (*l).min(gate_low);
+            }
+            if low[&gate] == index_of[&gate] {
+                let mut component = Vec::new();
+                while let Some(member) = stack.pop() {
+                    on_stack.remove(&member);
+                    component.push(member);
+                    if member == gate {
+                        break;
+                    }
+                }
+                components.push(component);
+            }
+        }
+    }
+
+    components
+        .into_iter()
+        .filter_map(|mut component| {
+            let members: HashSet<usize> = component.iter().copied().collect();
+            let mut wires: Vec<usize> = edges
+                .iter()
+                .filter(|(from, to, _)| members.contains(from) && members.contains(to))
+                .map(|(_, _, wire)| *wire)
+                .collect();
+            // a single gate is only a loop if it is wired to itself
+            if wires.is_empty() {
+                return None;
+            }
+            component.sort();
+            wires.sort();
+            Some(FeedbackLoop { gates: component, wires })
+        })
+        .collect()
+}
+
+impl Data {
+    /// How many ticks in a row the board may keep changing before it counts as oscillating
+    /// Without loops every signal settles within one tick per gate on the longest path
+    /// The gates are counted once per compiled netlist, so until the board is edited
+    pub fn settle_limit(&self) -> usize {
+        let gates = match &self.netlist {
+            Some(netlist) if netlist.matches(self.edits) => netlist.gates.len(),
+            _ => self
+                .live_data
+                .values()
+                .filter(|item| item.as_any().is::<Gate>())
+                .count(),
+        };
+        2 * gates + 16
+    }
+
+    /// Called after every tick of the running simulation, reports loops once the board fails to settle
+    /// The netlist counts what the tick changed, nothing has to be compared
+    pub fn watch_oscillation(&mut self) {
+        let changed = self.netlist.as_ref().is_some_and(|netlist| netlist.changes > 0);
+        let limit = self.settle_limit();
+        let monitor = &mut self.oscillation;
+        if !changed {
+            monitor.unsettled_ticks = 0;
+            monitor.report = None;
+            return;
+        }
+        monitor.unsettled_ticks += 1;
+        if monitor.unsettled_ticks == limit {
+            self.oscillation.report = self.find_oscillation();
+        }
+    }
+
+    /// Runs a copy of the netlist forward to see which feedback loops keep changing, the board is left as it is
+    /// Returns None if no loop is changing, the board may just have been edited a lot
+    pub fn find_oscillation(&mut self) -> Option<OscillationReport> {
+        let loops = feedback_loops(&self.live_data);
+        if loops.is_empty() {
+            return None;
+        }
+
+        let limit = self.settle_limit();
+        let threads = self.sim.threads;
+        let mut netlist = self.netlist().clone();
+        netlist.pull_user_state(&self.live_data);
+        netlist.discard_changes();
+        let mut last: Vec<bool> = netlist.signals().collect();
+        let mut changed = vec![false; last.len()];
+        let mut errors = Vec::new();
+        // a loop of n gates repeats itself every 2n ticks at most
+        for _ in 0..limit {
+            netlist.tick(threads, &mut errors);
+            netlist.discard_changes();
+            errors.clear();
+            if netlist.changes == 0 {
+                break;
+            }
+            for ((last, changed), signal) in last.iter_mut().zip(&mut changed).zip(netlist.signals()) {
+                *changed |= *last != signal;
+                *last = signal;
+            }
+        }
+
+        let layout = &netlist.layout;
+        let ids = layout.gate_ids.iter().chain(&layout.input_ids).chain(&layout.output_ids).chain(&layout.wire_ids);
+        let changing: HashSet<usize> = ids.zip(&changed).filter(|(_, changed)| **changed).map(|(id, _)| *id).collect();
+        let loops: Vec<FeedbackLoop> = loops
+            .into_iter()
+            .filter(|l| l.ids().any(|id| changing.contains(&id)))
+            .collect();
+        if loops.is_empty() {
+            return None;
+        }
+        Some(OscillationReport {
+            tick: self.sim.total_ticks,
+            loops,
+        })
+    }
+}
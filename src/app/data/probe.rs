@@ -0,0 +1,69 @@
+use std::error::Error;
+
+use super::{Data, Logical, signal_value};
+
+/// A named probe watching the signal of a wire, input or output without changing the circuit
+#[derive(Debug, Clone)]
+pub struct Probe {
+    pub name: String,
+    pub target_id: usize,
+}
+
+impl Probe {
+    /// The live value of the probed signal, None if the target no longer exists
+    pub fn value(&self, live_data: &std::collections::HashMap<usize, Box<dyn Logical>>) -> Option<bool> {
+        signal_value(self.target_id, live_data)
+    }
+}
+
+impl Data {
+    /// Attaches a probe to a wire, input or output and starts recording it
+    /// Returns the index of the new probe
+    pub fn add_probe(&mut self, target_id: usize) -> Result<usize, Box<dyn Error>> {
+        let kind = self
+            .live_data
+            .get(&target_id)
+            .ok_or_else(|| format!("Nothing with id {} to probe", target_id))?
+            .get_kind();
+        if !kind.is_wire() && !kind.is_io() {
+            return Err(format!("Only wires, inputs and outputs can be probed, not {:?}", kind).into());
+        }
+        if let Some(index) = self.probes.iter().position(|p| p.target_id == target_id) {
+            return Ok(index);
+        }
+
+        let mut n = self.probes.len();
+        let name = loop {
+            let name = format!("probe{}", n);
+            if !self.probes.iter().any(|p| p.name == name) {
+                break name;
+            }
+            n += 1;
+        };
+
+        self.recorder.unwatch(target_id);
+        self.recorder.watch_named(target_id, name.clone());
+        self.probes.push(Probe { name, target_id });
+        Ok(self.probes.len() - 1)
+    }
+
+    pub fn remove_probe(&mut self, index: usize) {
+        let probe = self.probes.remove(index);
+        self.recorder.unwatch(probe.target_id);
+    }
+
+    /// Renames a probe, its recorded history keeps the new name too
+    pub fn rename_probe(&mut self, index: usize, name: &str) {
+        let name: String = name.split_whitespace().collect::<Vec<_>>().join("_");
+        if let Some(probe) = self.probes.get_mut(index) {
+            probe.name = name.clone();
+            self.recorder.rename(probe.target_id, name);
+        }
+    }
+
+    pub fn clear_probes(&mut self) {
+        for probe in std::mem::take(&mut self.probes) {
+            self.recorder.unwatch(probe.target_id);
+        }
+    }
+}
@@ -0,0 +1,409 @@
+use std::collections::BinaryHeap;
+use std::ops::Not;
+
+/// Conflicts before the first restart, later restarts wait 1.5 times longer each
+const FIRST_RESTART: usize = 100;
+
+/// A variable or its negation, stored as variable * 2 plus 1 when negated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Lit(u32);
+
+impl Lit {
+    pub fn new(var: usize, negated: bool) -> Self {
+        Lit((var as u32) << 1 | negated as u32)
+    }
+
+    pub fn var(self) -> usize {
+        (self.0 >> 1) as usize
+    }
+
+    pub fn negated(self) -> bool {
+        self.0 & 1 == 1
+    }
+
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl Not for Lit {
+    type Output = Lit;
+
+    fn not(self) -> Lit {
+        Lit(self.0 ^ 1)
+    }
+}
+
+/// A small CDCL SAT solver: watched literals, first-UIP clause learning,
+/// activity-based branching with saved phases, and restarts
+/// `solve` finds an assignment that satisfies every clause added so far, or proves there is none
+#[derive(Debug, Default)]
+pub struct SatSolver {
+    clauses: Vec<Vec<Lit>>,
+    watches: Vec<Vec<usize>>, // per literal, the clauses with it as one of their first two literals
+    assigns: Vec<Option<bool>>,
+    level: Vec<usize>,
+    reason: Vec<Option<usize>>,
+    trail: Vec<Lit>,
+    trail_lim: Vec<usize>, // where every decision level starts on the trail
+    queue_head: usize,
+    activity: Vec<f64>,
+    activity_inc: f64,
+    order: BinaryHeap<(u64, usize)>, // (activity bits, var), entries go stale and are skipped
+    phase: Vec<bool>,
+    seen: Vec<bool>,
+    unsat: bool,
+    pub conflicts: usize,
+}
+
+fn value(assigns: &[Option<bool>], lit: Lit) -> Option<bool> {
+    assigns[lit.var()].map(|v| v != lit.negated())
+}
+
+impl SatSolver {
+    pub fn new() -> Self {
+        SatSolver { activity_inc: 1.0, ..Default::default() }
+    }
+
+    pub fn new_var(&mut self) -> usize {
+        let var = self.assigns.len();
+        self.assigns.push(None);
+        self.level.push(0);
+        self.reason.push(None);
+        self.activity.push(0.0);
+        self.phase.push(false);
+        self.seen.push(false);
+        self.watches.push(Vec::new());
+        self.watches.push(Vec::new());
+        self.order.push((0f64.to_bits(), var));
+        var
+    }
+
+    pub fn n_vars(&self) -> usize {
+        self.assigns.len()
+    }
+
+    /// Adds a clause, the disjunction of `lits`; an empty clause makes the problem unsatisfiable
+    pub fn add_clause(&mut self, lits: &[Lit]) {
+        if self.unsat {
+            return;
+        }
+        self.cancel_until(0); // clauses may come in between calls to `solve`
+        let mut clause: Vec<Lit> = Vec::with_capacity(lits.len());
+        for &lit in lits {
+            if clause.contains(&!lit) || value(&self.assigns, lit) == Some(true) {
+                return; // always true
+            }
+            if !clause.contains(&lit) && value(&self.assigns, lit) != Some(false) {
+                clause.push(lit);
+            }
+        }
+        match clause.len() {
+            0 => self.unsat = true,
+            1 => {
+                self.enqueue(clause[0], None);
+                if self.propagate().is_some() {
+                    self.unsat = true;
+                }
+            }
+            _ => {
+                self.attach(clause);
+            }
+        }
+    }
+
+    /// Returns the value of every variable if the clauses can all be satisfied
+    pub fn solve(&mut self) -> Option<Vec<bool>> {
+        if self.unsat || self.propagate().is_some() {
+            self.unsat = true;
+            return None;
+        }
+        let mut restart_at = FIRST_RESTART;
+        let mut since_restart = 0;
+        loop {
+            if let Some(conflict) = self.propagate() {
+                self.conflicts += 1;
+                since_restart += 1;
+                if self.trail_lim.is_empty() {
+                    self.unsat = true;
+                    return None;
+                }
+                let (learnt, back_to) = self.analyze(conflict);
+                self.cancel_until(back_to);
+                let first = learnt[0];
+                if learnt.len() == 1 {
+                    self.enqueue(first, None);
+                } else {
+                    let index = self.attach(learnt);
+                    self.enqueue(first, Some(index));
+                }
+                self.activity_inc /= 0.95;
+            } else if since_restart >= restart_at {
+                since_restart = 0;
+                restart_at += restart_at / 2;
+                self.cancel_until(0);
+            } else {
+                let Some(var) = self.pick_branch() else {
+                    return Some(self.assigns.iter().map(|v| v.unwrap_or(false)).collect());
+                };
+                self.trail_lim.push(self.trail.len());
+                self.enqueue(Lit::new(var, !self.phase[var]), None);
+            }
+        }
+    }
+
+    fn attach(&mut self, clause: Vec<Lit>) -> usize {
+        let index = self.clauses.len();
+        self.watches[clause[0].index()].push(index);
+        self.watches[clause[1].index()].push(index);
+        self.clauses.push(clause);
+        index
+    }
+
+    fn enqueue(&mut self, lit: Lit, reason: Option<usize>) {
+        let var = lit.var();
+        self.assigns[var] = Some(!lit.negated());
+        self.level[var] = self.trail_lim.len();
+        self.reason[var] = reason;
+        self.trail.push(lit);
+    }
+
+    /// Assigns everything the trail implies, returns a clause that became false if there is one
+    fn propagate(&mut self) -> Option<usize> {
+        while self.queue_head < self.trail.len() {
+            let false_lit = !self.trail[self.queue_head];
+            self.queue_head += 1;
+            let mut watching = std::mem::take(&mut self.watches[false_lit.index()]);
+            let mut i = 0;
+            while i < watching.len() {
+                let index = watching[i];
+                let clause = &mut self.clauses[index];
+                if clause[0] == false_lit {
+                    clause.swap(0, 1);
+                }
+                if value(&self.assigns, clause[0]) == Some(true) {
+                    i += 1;
+                    continue;
+                }
+                // look for another literal that is not false to watch instead
+                if let Some(k) = (2..clause.len()).find(|&k| value(&self.assigns, clause[k]) != Some(false)) {
+                    clause.swap(1, k);
+                    self.watches[clause[1].index()].push(index);
+                    watching.swap_remove(i);
+                    continue;
+                }
+                let first = clause[0];
+                if value(&self.assigns, first) == Some(false) {
+                    self.watches[false_lit.index()] = watching;
+                    return Some(index);
+                }
+                self.enqueue(first, Some(index));
+                i += 1;
+            }
+            self.watches[false_lit.index()] = watching;
+        }
+        None
+    }
+
+    /// Learns a clause from a conflict, returns it with its asserting literal first and the level to go back to
+    fn analyze(&mut self, conflict: usize) -> (Vec<Lit>, usize) {
+        let current = self.trail_lim.len();
+        let mut learnt = vec![Lit(0)]; // the asserting literal goes first
+        let mut pending = 0;
+        let mut resolved: Option<Lit> = None;
+        let mut index = self.trail.len();
+        let mut clause = conflict;
+        loop {
+            for k in 0..self.clauses[clause].len() {
+                let lit = self.clauses[clause][k];
+                let var = lit.var();
+                if Some(lit) == resolved || self.seen[var] || self.level[var] == 0 {
+                    continue;
+                }
+                self.seen[var] = true;
+                self.bump(var);
+                if self.level[var] == current {
+                    pending += 1;
+                } else {
+                    learnt.push(lit);
+                }
+            }
+            loop {
+                index -= 1;
+                if self.seen[self.trail[index].var()] {
+                    break;
+                }
+            }
+            let lit = self.trail[index];
+            self.seen[lit.var()] = false;
+            pending -= 1;
+            if pending == 0 {
+                learnt[0] = !lit;
+                break;
+            }
+            resolved = Some(lit);
+            clause = self.reason[lit.var()].expect("implied literals have a reason");
+        }
+        for lit in &learnt[1..] {
+            self.seen[lit.var()] = false;
+        }
+
+        // the second watch is the literal assigned last, that is where the clause becomes unit
+        let mut back_to = 0;
+        if learnt.len() > 1 {
+            let deepest = (1..learnt.len()).max_by_key(|&k| self.level[learnt[k].var()]).expect("not empty");
+            learnt.swap(1, deepest);
+            back_to = self.level[learnt[1].var()];
+        }
+        (learnt, back_to)
+    }
+
+    fn bump(&mut self, var: usize) {
+        self.activity[var] += self.activity_inc;
+        if self.activity[var] > 1e100 {
+            for activity in &mut self.activity {
+                *activity *= 1e-100;
+            }
+            self.activity_inc *= 1e-100;
+            self.order = (0..self.activity.len()).map(|v| (self.activity[v].to_bits(), v)).collect();
+        } else {
+            self.order.push((self.activity[var].to_bits(), var));
+        }
+    }
+
+    fn cancel_until(&mut self, level: usize) {
+        if self.trail_lim.len() <= level {
+            return;
+        }
+        let start = self.trail_lim[level];
+        for lit in self.trail.drain(start..) {
+            let var = lit.var();
+            self.phase[var] = !lit.negated();
+            self.assigns[var] = None;
+            self.reason[var] = None;
+            self.order.push((self.activity[var].to_bits(), var));
+        }
+        self.trail_lim.truncate(level);
+        self.queue_head = self.trail.len();
+    }
+
+    /// The unassigned variable with the highest activity
+    fn pick_branch(&mut self) -> Option<usize> {
+        while let Some((bits, var)) = self.order.pop() {
+            if self.assigns[var].is_none() && bits == self.activity[var].to_bits() {
+                return Some(var);
+            }
+        }
+        // stale entries only, look at every variable once
+        self.assigns.iter().position(|v| v.is_none())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn satisfies(model: &[bool], clauses: &[Vec<Lit>]) -> bool {
+        clauses.iter().all(|clause| clause.iter().any(|lit| model[lit.var()] != lit.negated()))
+    }
+
+    fn solver_with(vars: usize, clauses: &[Vec<Lit>]) -> SatSolver {
+        let mut solver = SatSolver::new();
+        for _ in 0..vars {
+            solver.new_var();
+        }
+        for clause in clauses {
+            solver.add_clause(clause);
+        }
+        solver
+    }
+
+    #[test]
+    fn literals() {
+        let lit = Lit::new(5, false);
+        assert_eq!((lit.var(), lit.negated()), (5, false));
+        assert_eq!(((!lit).var(), (!lit).negated()), (5, true));
+        assert_eq!(!!lit, lit);
+    }
+
+    #[test]
+    fn edge_cases() {
+        // no clauses at all, and no variables
+        assert_eq!(SatSolver::new().solve(), Some(Vec::new()));
+        // the empty clause can't be satisfied
+        assert_eq!(solver_with(1, &[Vec::new()]).solve(), None);
+        // a variable and its negation
+        let a = Lit::new(0, false);
+        assert_eq!(solver_with(1, &[vec![a], vec![!a]]).solve(), None);
+        // a clause with both polarities is always true
+        assert!(solver_with(1, &[vec![a, !a]]).solve().is_some());
+        // units decide everything before any search
+        let b = Lit::new(1, false);
+        assert_eq!(solver_with(2, &[vec![a], vec![!b]]).solve(), Some(vec![true, false]));
+    }
+
+    #[test]
+    fn only_model() {
+        let (a, b) = (Lit::new(0, false), Lit::new(1, false));
+        let clauses = [vec![a, b], vec![!a, b], vec![a, !b]];
+        assert_eq!(solver_with(2, &clauses).solve(), Some(vec![true, true]));
+    }
+
+    #[test]
+    fn pigeonhole_is_unsat() {
+        // 4 pigeons in 3 holes, variable pigeon * 3 + hole
+        let lit = |pigeon: usize, hole: usize| Lit::new(pigeon * 3 + hole, false);
+        let mut clauses: Vec<Vec<Lit>> = (0..4).map(|pigeon| (0..3).map(|hole| lit(pigeon, hole)).collect()).collect();
+        for hole in 0..3 {
+            for first in 0..4 {
+                for second in first + 1..4 {
+                    clauses.push(vec![!lit(first, hole), !lit(second, hole)]);
+                }
+            }
+        }
+        let mut solver = solver_with(12, &clauses);
+        assert_eq!(solver.solve(), None);
+        assert!(solver.conflicts > 0);
+    }
+
+    #[test]
+    fn clauses_added_after_solving() {
+        // blocking every model in turn finds all 2^3 of them, then nothing is left
+        let mut solver = solver_with(3, &[]);
+        let mut models = Vec::new();
+        while let Some(model) = solver.solve() {
+            let blocking: Vec<Lit> = model.iter().enumerate().map(|(var, value)| Lit::new(var, *value)).collect();
+            models.push(model);
+            solver.add_clause(&blocking);
+        }
+        models.sort();
+        models.dedup();
+        assert_eq!(models.len(), 8);
+    }
+
+    #[test]
+    fn random_3sat_matches_brute_force() {
+        let mut seed: u64 = 0x2545_F491_4F6C_DD1D;
+        let mut next = |below: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % below as u64) as usize
+        };
+        for _ in 0..200 {
+            let vars = 8;
+            // around 4.3 clauses per variable, so both satisfiable and unsatisfiable problems come up
+            let clauses: Vec<Vec<Lit>> = (0..34)
+                .map(|_| (0..3).map(|_| Lit::new(next(vars), next(2) == 1)).collect())
+                .collect();
+            let brute_force = (0..1u32 << vars).any(|bits| {
+                let model: Vec<bool> = (0..vars).map(|var| (bits >> var) & 1 == 1).collect();
+                satisfies(&model, &clauses)
+            });
+            match solver_with(vars, &clauses).solve() {
+                Some(model) => assert!(satisfies(&model, &clauses), "model breaks a clause of {:?}", clauses),
+                None => assert!(!brute_force, "satisfiable but reported unsat: {:?}", clauses),
+            }
+        }
+    }
+}
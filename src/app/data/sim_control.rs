@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+use super::*;
+
+/// Upper bound on ticks run in a single frame so fast-forward can't freeze the UI
+pub const MAX_TICKS_PER_FRAME: u64 = 100_000;
+
+/// Ticks tried by "step until stable" before giving up on an oscillating board
+pub const MAX_STABLE_TICKS: usize = 10_000;
+
+/// Decides how many ticks run each frame, independent of the frame rate
+#[derive(Debug, Clone)]
+pub struct SimControl {
+    pub running: bool,
+    pub ticks_per_second: f64,
+    pub queued_ticks: u64, // requested by step / run N, these run even while paused
+    pub total_ticks: u64,
+    pub threads: usize, // gates of large boards are split over this many threads, 1 runs everything on the caller
+
+    carry: f64, // fraction of a tick left over from the last frame
+    last_time: Option<f64>,
+}
+
+impl Default for SimControl {
+    fn default() -> Self {
+        SimControl {
+            running: true,
+            ticks_per_second: 60.0,
+            queued_ticks: 0,
+            total_ticks: 0,
+            threads: 1,
+            carry: 0.0,
+            last_time: None,
+        }
+    }
+}
+
+impl SimControl {
+    pub fn pause(&mut self) {
+        self.running = false;
+        self.queued_ticks = 0;
+    }
+
+    pub fn resume(&mut self) {
+        self.running = true;
+    }
+
+    /// Pauses and queues a number of ticks to run as fast as possible
+    pub fn step(&mut self, ticks: u64) {
+        self.running = false;
+        self.queued_ticks = self.queued_ticks.saturating_add(ticks);
+    }
+
+    /// Queues a number of ticks to run as fast as possible, without changing run/pause
+    pub fn run_ticks(&mut self, ticks: u64) {
+        self.queued_ticks = self.queued_ticks.saturating_add(ticks);
+    }
+
+    /// Works out how many ticks are due at time `now` (seconds), capped at MAX_TICKS_PER_FRAME
+    pub fn ticks_due(&mut self, now: f64) -> u64 {
+        let elapsed = self.last_time.map_or(0.0, |last| (now - last).max(0.0));
+        self.last_time = Some(now);
+
+        let mut due = 0;
+        if self.running && self.ticks_per_second > 0.0 {
+            self.carry += elapsed * self.ticks_per_second;
+            due = self.carry.floor() as u64;
+            self.carry -= due as f64;
+            if due > MAX_TICKS_PER_FRAME {
+                // we can't keep up, drop the backlog instead of letting it grow forever
+                due = MAX_TICKS_PER_FRAME;
+                self.carry = 0.0;
+            }
+        } else {
+            self.carry = 0.0;
+        }
+
+        let queued = self.queued_ticks.min(MAX_TICKS_PER_FRAME - due);
+        self.queued_ticks -= queued;
+        due + queued
+    }
+
+    /// How long the UI can sleep before the next tick is due, None if nothing is scheduled
+    pub fn next_wakeup(&self) -> Option<Duration> {
+        if self.queued_ticks > 0 {
+            Some(Duration::ZERO)
+        } else if self.running && self.ticks_per_second > 0.0 {
+            Some(Duration::from_secs_f64(
+                ((1.0 - self.carry) / self.ticks_per_second).max(0.0),
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+impl Data {
+    /// Ticks until the board stops changing, at most MAX_STABLE_TICKS
+    /// Returns the number of ticks it took, None if the board is still changing or a breakpoint stopped it
+    pub fn step_until_stable(&mut self) -> Option<u64> {
+        self.sim.pause();
+        self.break_hit = None;
+        let start = self.sim.total_ticks;
+        let stable = self.settle(MAX_STABLE_TICKS);
+        let ticks = self.sim.total_ticks - start;
+        let stopped = self.break_hit.is_some();
+        self.oscillation.report = if stable || stopped { None } else { self.find_oscillation() };
+        stable.then_some(ticks)
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+
+use super::*;
+
+/// Size and cost of a chip with every chip nested inside it expanded
+/// The pins of nested chips are only connections, they count as neither gates nor cost
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct ChipStats {
+    pub name: String,
+    pub pins: usize, // the chip's own TOGGLE and LIGHT gates
+    pub kinds: BTreeMap<String, usize>, // gates per PrimitiveKind, pins left out
+    pub gates: usize,
+    pub chips: usize, // nested chips that were expanded
+    pub nand_equivalent: usize, // 2-input NANDs needed to build the gates
+    pub transistors: usize, // estimate for static CMOS
+    pub wires: usize,
+    pub max_fan_out: usize, // most wires leaving a single output
+}
+
+impl ChipStats {
+    /// Counts one gate and its cost
+    fn add_gate(&mut self, kind: &PrimitiveKind, n_in: usize) {
+        *self.kinds.entry(kind.to_string()).or_default() += 1;
+        self.gates += 1;
+        self.nand_equivalent += nand_equivalent(kind, n_in);
+        self.transistors += transistors(kind, n_in);
+    }
+
+    /// Adds the contents of a nested chip
+    fn add(&mut self, other: &ChipStats) {
+        for (kind, count) in &other.kinds {
+            *self.kinds.entry(kind.clone()).or_default() += count;
+        }
+        self.gates += other.gates;
+        self.chips += other.chips + 1;
+        self.nand_equivalent += other.nand_equivalent;
+        self.transistors += other.transistors;
+        self.wires += other.wires;
+        self.max_fan_out = self.max_fan_out.max(other.max_fan_out);
+    }
+
+    /// Every figure as (label, value), in the order the statistics panel shows them
+    pub fn figures(&self) -> Vec<(String, usize)> {
+        let mut figures = vec![
+            ("Gates".to_string(), self.gates),
+            ("Pins".to_string(), self.pins),
+            ("Nested chips".to_string(), self.chips),
+            ("NAND equivalent".to_string(), self.nand_equivalent),
+            ("Transistors".to_string(), self.transistors),
+            ("Wires".to_string(), self.wires),
+            ("Max fan-out".to_string(), self.max_fan_out),
+        ];
+        figures.extend(self.kinds.iter().map(|(kind, count)| (kind.clone(), *count)));
+        figures
+    }
+
+    /// Every figure of either chip as (label, this chip's value, the other's value), 0 where a chip has no such gates
+    pub fn compare(&self, other: &ChipStats) -> Vec<(String, usize, usize)> {
+        let (mine, theirs) = (self.figures(), other.figures());
+        let value = |figures: &[(String, usize)], label: &str| {
+            figures.iter().find(|(other, _)| other == label).map_or(0, |(_, value)| *value)
+        };
+        let mut labels: Vec<&String> = mine.iter().map(|(label, _)| label).collect();
+        labels.extend(theirs.iter().map(|(label, _)| label).filter(|label| !mine.iter().any(|(other, _)| other == *label)));
+        labels.into_iter().map(|label| (label.clone(), value(&mine, label), value(&theirs, label))).collect()
+    }
+}
+
+impl Display for ChipStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.name)?;
+        for (label, value) in self.figures() {
+            writeln!(f, "  {}: {}", label, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// 2-input NANDs needed for a gate: AND is NAND then NOT, OR is NAND of inverted inputs,
+/// and wider gates are chains of 2-input ones
+fn nand_equivalent(kind: &PrimitiveKind, n_in: usize) -> usize {
+    let stages = n_in.max(2) - 1;
+    match kind {
+        PrimitiveKind::NOT => 1,
+        PrimitiveKind::BUFFER => 2,
+        PrimitiveKind::NAND => 2 * stages - 1,
+        PrimitiveKind::AND => 2 * stages,
+        PrimitiveKind::OR => n_in + 2 * stages - 1,
+        PrimitiveKind::NOR => n_in + 2 * stages,
+        PrimitiveKind::XOR => 4 * stages,
+        _ => 0, // pins and constant signals
+    }
+}
+
+/// Transistors for a gate in static CMOS: 2 per input for NAND and NOR, plus an inverter for AND and OR
+fn transistors(kind: &PrimitiveKind, n_in: usize) -> usize {
+    match kind {
+        PrimitiveKind::NOT => 2,
+        PrimitiveKind::BUFFER => 4,
+        PrimitiveKind::NAND | PrimitiveKind::NOR => 2 * n_in,
+        PrimitiveKind::AND | PrimitiveKind::OR => 2 * n_in + 2,
+        PrimitiveKind::XOR => 12 * (n_in.max(2) - 1),
+        _ => 0,
+    }
+}
+
+/// What is known while counting: definitions of nested chips, and the stats of those already counted
+#[derive(Default)]
+struct Counting {
+    definitions: Vec<ChipDefenition>,
+    counted: HashMap<String, ChipStats>,
+}
+
+/// Counts the gates of a chip and everything nested in it
+/// Custom gates are looked up by name among the sub-chips seen so far, then through `lookup`
+pub fn chip_stats<F>(chip: &ChipDefenition, mut lookup: F) -> Result<ChipStats, CircuitError>
+where
+    F: FnMut(&str) -> Option<ChipDefenition>,
+{
+    let mut stats = count_chip(chip, 0, &mut Counting::default(), &mut lookup)?;
+    stats.pins = chip
+        .sub_gates
+        .values()
+        .filter(|gate| matches!(gate.kind, GateKind::Primitive(PrimitiveKind::TOGGLE | PrimitiveKind::LIGHT)))
+        .count();
+    Ok(stats)
+}
+
+fn count_chip<F>(chip: &ChipDefenition, depth: usize, state: &mut Counting, lookup: &mut F) -> Result<ChipStats, CircuitError>
+where
+    F: FnMut(&str) -> Option<ChipDefenition>,
+{
+    if depth > MAX_CHIP_DEPTH {
+        return Err(CircuitError::Unsupported {
+            id: Some(chip.id),
+            message: format!("{} is nested more than {} chips deep, does it contain itself?", chip.name, MAX_CHIP_DEPTH),
+        });
+    }
+    let mut stats = ChipStats { name: chip.name.clone(), wires: chip.sub_wires.len(), ..Default::default() };
+
+    let mut fan_out: HashMap<usize, usize> = HashMap::new();
+    for wire in chip.sub_wires.values() {
+        *fan_out.entry(wire.source_id).or_default() += 1;
+    }
+    stats.max_fan_out = fan_out.values().max().copied().unwrap_or(0);
+
+    let mut sub_chips: Vec<&ChipDefenition> = chip.sub_chips.values().collect();
+    sub_chips.sort_by_key(|sub_chip| sub_chip.id);
+    for sub_chip in sub_chips {
+        state.definitions.push(sub_chip.clone());
+        let nested = count_chip(sub_chip, depth + 1, state, lookup)?;
+        stats.add(&nested);
+    }
+
+    let mut gates: Vec<&Gate> = chip.sub_gates.values().collect();
+    gates.sort_by_key(|gate| gate.id);
+    for gate in gates {
+        match &gate.kind {
+            GateKind::None | GateKind::Primitive(PrimitiveKind::TOGGLE | PrimitiveKind::LIGHT) => {}
+            GateKind::Primitive(kind) => stats.add_gate(kind, gate.n_in),
+            GateKind::Custom(name) => {
+                let nested = match state.counted.get(name) {
+                    Some(nested) => nested.clone(),
+                    None => {
+                        let definition = state
+                            .definitions
+                            .iter()
+                            .find(|definition| &definition.name == name)
+                            .cloned()
+                            .or_else(|| lookup(name))
+                            .ok_or_else(|| CircuitError::Unsupported {
+                                id: Some(gate.id),
+                                message: format!("No chip named {} to count", name),
+                            })?;
+                        let nested = count_chip(&definition, depth + 1, state, lookup)?;
+                        state.counted.insert(name.clone(), nested.clone());
+                        nested
+                    }
+                };
+                stats.add(&nested);
+            }
+        }
+    }
+    Ok(stats)
+}
@@ -0,0 +1,694 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use super::{ChipDefenition, Data, Gate, GateKind, LANES, PrimitiveKind, SAVES_DIR, VectorSim, feedback_loops};
+
+/// How many ticks `eval` may take before we give up waiting for the board to settle
+const MAX_SETTLE_TICKS: usize = 1000;
+
+/// Name of the input pin driven by `tick` and `tock`
+const CLOCK_PIN: &str = "clk";
+
+/// Scripts with more evals than this are run one eval at a time
+const MAX_PREPARED_EVALS: usize = 1 << 20;
+
+/// A parsed nand2tetris test script (.tst)
+/// Supports `load`, `output-file`, `compare-to`, `output-list`, `set`, `eval`, `output`,
+/// `tick`, `tock`, `echo` and `repeat` blocks
+#[derive(Debug, Clone, Default)]
+pub struct TestScript {
+    pub load: Option<String>,
+    pub output_file: Option<String>,
+    pub compare_to: Option<String>,
+    pub output_list: Vec<OutputColumn>,
+    pub commands: Vec<TestCommand>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TestCommand {
+    Set(String, u64),
+    Eval,
+    Output,
+    Tick,
+    Tock,
+    Echo(String),
+    Repeat(Option<usize>, Vec<TestCommand>),
+}
+
+/// One column of the output list, written as `name%F left.width.right`
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputColumn {
+    pub name: String,
+    pub format: char,
+    pub pad_left: usize,
+    pub width: usize,
+    pub pad_right: usize,
+}
+
+/// The result of running a script: every output line plus the first row that did not match the .cmp file
+#[derive(Debug, Clone, Default)]
+pub struct TestReport {
+    pub chip_name: String,
+    pub lines: Vec<String>,
+    pub compared: bool,
+    pub mismatch: Option<TestMismatch>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TestMismatch {
+    pub row: usize, // 0 is the header row
+    pub expected: String,
+    pub actual: String,
+}
+
+impl TestReport {
+    pub fn passed(&self) -> bool {
+        self.mismatch.is_none()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Comma,
+    Semicolon,
+    Open,
+    Close,
+}
+
+impl TestScript {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let content = std::fs::read_to_string(path)?;
+        TestScript::parse(&content)
+    }
+
+    pub fn parse(src: &str) -> Result<Self, Box<dyn Error>> {
+        let tokens = tokenize(src)?;
+        let mut script = TestScript::default();
+        let mut pos = 0;
+        script.commands = script.parse_block(&tokens, &mut pos, false)?;
+        Ok(script)
+    }
+
+    /// Parses statements until the end of input, or until a closing brace when inside a repeat block
+    fn parse_block(
+        &mut self,
+        tokens: &[Token],
+        pos: &mut usize,
+        nested: bool,
+    ) -> Result<Vec<TestCommand>, Box<dyn Error>> {
+        let mut commands = Vec::new();
+        while *pos < tokens.len() {
+            let words: Vec<String> = match &tokens[*pos] {
+                Token::Close if nested => {
+                    *pos += 1;
+                    return Ok(commands);
+                }
+                Token::Comma | Token::Semicolon => {
+                    *pos += 1;
+                    continue;
+                }
+                Token::Word(_) => {
+                    let mut words = Vec::new();
+                    while let Some(Token::Word(w)) = tokens.get(*pos) {
+                        words.push(w.clone());
+                        *pos += 1;
+                    }
+                    words
+                }
+                other => return Err(format!("Unexpected {:?} in test script", other).into()),
+            };
+
+            match words[0].as_str() {
+                "repeat" => {
+                    let count = match words.get(1) {
+                        Some(n) => Some(n.parse::<usize>().map_err(|_| format!("Invalid repeat count {}", n))?),
+                        None => None,
+                    };
+                    if tokens.get(*pos) != Some(&Token::Open) {
+                        return Err("Expected { after repeat".into());
+                    }
+                    *pos += 1;
+                    let body = self.parse_block(tokens, pos, true)?;
+                    commands.push(TestCommand::Repeat(count, body));
+                }
+                "load" => self.load = Some(argument(&words)?),
+                "output-file" => self.output_file = Some(argument(&words)?),
+                "compare-to" => self.compare_to = Some(argument(&words)?),
+                "output-list" => {
+                    self.output_list = words[1..]
+                        .iter()
+                        .map(|w| OutputColumn::parse(w))
+                        .collect::<Result<_, _>>()?;
+                }
+                "set" => {
+                    if words.len() != 3 {
+                        return Err(format!("set expects a pin and a value, got: {}", words.join(" ")).into());
+                    }
+                    commands.push(TestCommand::Set(words[1].clone(), parse_value(&words[2])?));
+                }
+                "eval" => commands.push(TestCommand::Eval),
+                "output" => commands.push(TestCommand::Output),
+                "tick" => commands.push(TestCommand::Tick),
+                "tock" => commands.push(TestCommand::Tock),
+                "echo" => commands.push(TestCommand::Echo(words[1..].join(" ").trim_matches('"').to_string())),
+                other => return Err(format!("Unsupported test script command: {}", other).into()),
+            }
+        }
+        if nested {
+            return Err("Missing } at end of repeat block".into());
+        }
+        Ok(commands)
+    }
+
+    /// Runs the script against whatever board is loaded in `data` and returns the output lines
+    pub fn run(&self, data: &mut Data) -> Result<Vec<String>, Box<dyn Error>> {
+        let prepared = Prepared::new(data, &self.commands);
+        let mut runner = Runner {
+            data,
+            columns: &self.output_list,
+            lines: vec![header_line(&self.output_list)],
+            time: 0,
+            tocked: true,
+            prepared,
+            evals: 0,
+        };
+        runner.run_commands(&self.commands)?;
+        if runner.prepared.is_some() {
+            // only the pins were kept up to date, bring the rest of the board in line with them
+            runner.data.invalidate_netlist();
+            runner.data.settle(MAX_SETTLE_TICKS);
+        }
+        Ok(runner.lines)
+    }
+
+    /// Loads the chip named by the script, runs it and compares the output with the .cmp file if one is given.
+    /// Relative paths are resolved from the script's directory, chips are also looked up in the saves directory.
+    /// The board is simulated on `threads` threads, see `SimControl::threads`.
+    pub fn run_file<P: AsRef<Path>>(path: P, threads: usize) -> Result<TestReport, Box<dyn Error>> {
+        let path = path.as_ref();
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let script = TestScript::from_file(path)?;
+
+        let load = script
+            .load
+            .as_ref()
+            .ok_or("Test script does not load a chip")?;
+        let chip_name = Path::new(load)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| load.clone());
+        let chip_file = [dir.to_path_buf(), PathBuf::from(SAVES_DIR)]
+            .iter()
+            .map(|d| d.join(format!("{}.chip", chip_name)))
+            .find(|p| p.is_file())
+            .ok_or_else(|| format!("Could not find {}.chip next to the script or in the saves directory", chip_name))?;
+        let chip = ChipDefenition::load_from_file(&chip_file)?;
+
+        let mut data = Data::new();
+        data.load_chip(&chip);
+        data.sim.threads = threads;
+        let lines = script.run(&mut data)?;
+
+        if let Some(out) = &script.output_file {
+            std::fs::write(dir.join(out), lines.join("\n") + "\n")?;
+        }
+
+        let mut report = TestReport {
+            chip_name,
+            lines,
+            compared: false,
+            mismatch: None,
+        };
+        if let Some(cmp) = &script.compare_to {
+            let expected = std::fs::read_to_string(dir.join(cmp))?;
+            report.compared = true;
+            report.mismatch = compare_lines(&report.lines, &expected);
+        }
+        Ok(report)
+    }
+}
+
+struct Runner<'a> {
+    data: &'a mut Data,
+    columns: &'a [OutputColumn],
+    lines: Vec<String>,
+    time: usize,
+    tocked: bool,
+    prepared: Option<Prepared>,
+    evals: usize, // evals done so far, the index into `prepared`
+}
+
+impl Runner<'_> {
+    fn run_commands(&mut self, commands: &[TestCommand]) -> Result<(), Box<dyn Error>> {
+        for command in commands {
+            match command {
+                TestCommand::Set(pin, value) => self.data.set_pin(pin, *value)?,
+                TestCommand::Eval => self.eval()?,
+                TestCommand::Output => {
+                    let line = self.output_line()?;
+                    self.lines.push(line);
+                }
+                TestCommand::Tick => {
+                    if !self.data.find_pin(CLOCK_PIN).is_empty() {
+                        self.data.set_pin(CLOCK_PIN, 1)?;
+                    }
+                    self.eval()?;
+                    self.tocked = false;
+                }
+                TestCommand::Tock => {
+                    if !self.data.find_pin(CLOCK_PIN).is_empty() {
+                        self.data.set_pin(CLOCK_PIN, 0)?;
+                    }
+                    self.eval()?;
+                    self.time += 1;
+                    self.tocked = true;
+                }
+                TestCommand::Echo(msg) => println!("{}", msg),
+                TestCommand::Repeat(Some(n), body) => {
+                    for _ in 0..*n {
+                        self.run_commands(body)?;
+                    }
+                }
+                TestCommand::Repeat(None, _) => {
+                    return Err("repeat without a count would never finish".into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn eval(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(prepared) = &self.prepared {
+            let (block, lane) = (self.evals / LANES, self.evals % LANES);
+            self.evals += 1;
+            if (prepared.settled[block] >> lane) & 1 == 0 {
+                return Err(format!("Board did not settle within {} ticks", MAX_SETTLE_TICKS).into());
+            }
+            for (light, lanes) in prepared.lights.iter().zip(&prepared.states[block]) {
+                if let Some(gate) = self.data.get_gate_mut(*light) {
+                    gate.state = (lanes >> lane) & 1 == 1;
+                }
+            }
+            return Ok(());
+        }
+        if self.data.settle(MAX_SETTLE_TICKS) {
+            Ok(())
+        } else {
+            Err(format!("Board did not settle within {} ticks", MAX_SETTLE_TICKS).into())
+        }
+    }
+
+    fn output_line(&self) -> Result<String, Box<dyn Error>> {
+        let mut line = String::from("|");
+        for column in self.columns {
+            let value = if column.name == "time" {
+                format!("{}{}", self.time, if self.tocked { "" } else { "+" })
+            } else {
+                let bits = self.data.find_pin_bits(&column.name)?.last().map_or(1, |(bit, _)| bit + 1);
+                column.format_value(self.data.read_pin(&column.name)?, bits)
+            };
+            line.push_str(&" ".repeat(column.pad_left));
+            line.push_str(&format!("{:>width$}", value, width = column.width));
+            line.push_str(&" ".repeat(column.pad_right));
+            line.push('|');
+        }
+        Ok(line)
+    }
+}
+
+impl OutputColumn {
+    fn parse(word: &str) -> Result<Self, Box<dyn Error>> {
+        let Some((name, spec)) = word.split_once('%') else {
+            return Ok(OutputColumn {
+                name: word.to_string(),
+                format: 'B',
+                pad_left: 1,
+                width: 1,
+                pad_right: 1,
+            });
+        };
+        let mut chars = spec.chars();
+        let format = chars.next().ok_or_else(|| format!("Missing format in {}", word))?;
+        if !"BDXS".contains(format) {
+            return Err(format!("Unknown output format {} in {}", format, word).into());
+        }
+        let sizes: Vec<usize> = chars
+            .as_str()
+            .split('.')
+            .map(|n| n.parse::<usize>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("Invalid column size in {}", word))?;
+        if sizes.len() != 3 {
+            return Err(format!("Column size must be left.width.right in {}", word).into());
+        }
+        Ok(OutputColumn {
+            name: name.to_string(),
+            format,
+            pad_left: sizes[0],
+            width: sizes[1],
+            pad_right: sizes[2],
+        })
+    }
+
+    /// Formats the value of a pin that is `bits` bits wide
+    /// Decimals are two's complement like in nand2tetris, where every value is a 16-bit word:
+    /// pins of up to 16 bits are negative when bit 15 is set, wider ones when their top bit is
+    fn format_value(&self, value: u64, bits: usize) -> String {
+        match self.format {
+            'B' => {
+                let digits = format!("{:0width$b}", value, width = self.width);
+                digits[digits.len() - self.width..].to_string()
+            }
+            'X' => {
+                let digits = format!("{:0width$X}", value, width = self.width);
+                digits[digits.len() - self.width..].to_string()
+            }
+            _ => {
+                let unused = 64 - bits.clamp(16, 64) as u32;
+                (((value << unused) as i64) >> unused).to_string()
+            }
+        }
+    }
+
+    fn total_width(&self) -> usize {
+        self.pad_left + self.width + self.pad_right
+    }
+}
+
+/// The outcome of every eval of a script, simulated up front 64 evals at a time
+/// Only done for boards without feedback loops: their settled outputs depend on the inputs alone,
+/// so every eval can start from the same state
+struct Prepared {
+    lights: Vec<usize>,
+    states: Vec<Vec<u64>>, // per block of 64 evals, the lanes of every light
+    settled: Vec<u64>,     // per block of 64 evals
+}
+
+impl Prepared {
+    /// None when the board can't be simulated ahead, or the script can't be followed without running it
+    /// (an unknown pin, a repeat without a count); the script then runs one eval at a time
+    fn new(data: &mut Data, commands: &[TestCommand]) -> Option<Prepared> {
+        let pins: Vec<&Gate> = data
+            .live_data
+            .values()
+            .filter_map(|item| item.as_any().downcast_ref::<Gate>())
+            .collect();
+        let of_kind = |kind: PrimitiveKind| {
+            let kind = GateKind::Primitive(kind);
+            let mut gates: Vec<&Gate> = pins.iter().copied().filter(|gate| gate.kind == kind).collect();
+            gates.sort_by_key(|gate| gate.id);
+            gates
+        };
+        let pulses = !of_kind(PrimitiveKind::PULSE).is_empty();
+        if !data.breakpoints.is_empty() || pulses || !feedback_loops(&data.live_data).is_empty() {
+            return None;
+        }
+        let toggles = of_kind(PrimitiveKind::TOGGLE);
+        let lights: Vec<usize> = of_kind(PrimitiveKind::LIGHT).iter().map(|gate| gate.id).collect();
+
+        let mut inputs = EvalInputs {
+            toggles: toggles.iter().enumerate().map(|(index, gate)| (gate.id, index)).collect(),
+            state: toggles.iter().map(|gate| gate.state).collect(),
+            blocks: Vec::new(),
+            evals: 0,
+        };
+        inputs.walk(data, commands)?;
+
+        let toggle_ids: Vec<usize> = toggles.iter().map(|gate| gate.id).collect();
+        let start = VectorSim::new(data.netlist().clone());
+        let outputs = [lights.clone()];
+        let mut prepared = Prepared { lights, states: Vec::new(), settled: Vec::new() };
+        for (block, lanes) in inputs.blocks.iter().enumerate() {
+            let mut sim = start.clone();
+            for (gate_id, lanes) in toggle_ids.iter().zip(lanes) {
+                sim.set_gate(*gate_id, *lanes);
+            }
+            let used = inputs.evals - block * LANES;
+            let used = if used >= LANES { u64::MAX } else { (1 << used) - 1 };
+            let (mut states, settled) = sim.sweep_block(0, used, &[], &outputs, MAX_SETTLE_TICKS);
+            prepared.states.push(states.pop().expect("one output list"));
+            prepared.settled.push(settled);
+        }
+        Some(prepared)
+    }
+}
+
+/// Follows a script without running it, collecting the TOGGLE states of every eval
+struct EvalInputs {
+    toggles: HashMap<usize, usize>, // gate id -> index into `state`
+    state: Vec<bool>,
+    blocks: Vec<Vec<u64>>, // per block of 64 evals, the lanes of every toggle
+    evals: usize,
+}
+
+impl EvalInputs {
+    fn walk(&mut self, data: &Data, commands: &[TestCommand]) -> Option<()> {
+        for command in commands {
+            match command {
+                TestCommand::Set(pin, value) => self.set(data, pin, *value)?,
+                TestCommand::Eval => self.eval()?,
+                TestCommand::Tick | TestCommand::Tock => {
+                    if !data.find_pin(CLOCK_PIN).is_empty() {
+                        self.set(data, CLOCK_PIN, matches!(command, TestCommand::Tick) as u64)?;
+                    }
+                    self.eval()?;
+                }
+                TestCommand::Output | TestCommand::Echo(_) => {}
+                TestCommand::Repeat(Some(n), body) => {
+                    for _ in 0..*n {
+                        self.walk(data, body)?;
+                    }
+                }
+                TestCommand::Repeat(None, _) => return None,
+            }
+        }
+        Some(())
+    }
+
+    /// Same bits as `Data::set_pin`, pins that aren't TOGGLEs are worked out by the simulation
+    fn set(&mut self, data: &Data, pin: &str, value: u64) -> Option<()> {
+        let bits = data.find_pin_bits(pin).ok()?;
+        if bits.is_empty() {
+            return None;
+        }
+        for (bit, id) in bits {
+            if let Some(&index) = self.toggles.get(&id) {
+                self.state[index] = bit < 64 && (value >> bit) & 1 == 1;
+            }
+        }
+        Some(())
+    }
+
+    fn eval(&mut self) -> Option<()> {
+        if self.evals >= MAX_PREPARED_EVALS {
+            return None;
+        }
+        let lane = self.evals % LANES;
+        if lane == 0 {
+            self.blocks.push(vec![0; self.state.len()]);
+        }
+        let block = self.blocks.last_mut().expect("pushed above");
+        for (lanes, state) in block.iter_mut().zip(&self.state) {
+            *lanes |= (*state as u64) << lane;
+        }
+        self.evals += 1;
+        Some(())
+    }
+}
+
+/// The header row holds every column name centered in its column
+fn header_line(columns: &[OutputColumn]) -> String {
+    let mut line = String::from("|");
+    for column in columns {
+        let total = column.total_width();
+        let name: String = column.name.chars().take(total).collect();
+        let width = name.chars().count();
+        let left = (total - width) / 2;
+        line.push_str(&" ".repeat(left));
+        line.push_str(&name);
+        line.push_str(&" ".repeat(total - left - width));
+        line.push('|');
+    }
+    line
+}
+
+/// Compares output rows with the rows of a .cmp file, cell by cell
+/// A cell of only `*` in the .cmp file matches anything
+fn compare_lines(actual: &[String], expected: &str) -> Option<TestMismatch> {
+    let expected: Vec<&str> = expected.lines().filter(|l| !l.trim().is_empty()).collect();
+    for row in 0..actual.len().max(expected.len()) {
+        let a = actual.get(row).map(String::as_str).unwrap_or("");
+        let e = expected.get(row).copied().unwrap_or("");
+        let a_cells: Vec<&str> = a.split('|').map(str::trim).collect();
+        let e_cells: Vec<&str> = e.split('|').map(str::trim).collect();
+        let matches = a_cells.len() == e_cells.len()
+            && a_cells
+                .iter()
+                .zip(&e_cells)
+                .all(|(a, e)| a == e || (!e.is_empty() && e.chars().all(|c| c == '*')));
+        if !matches {
+            return Some(TestMismatch {
+                row,
+                expected: e.to_string(),
+                actual: a.to_string(),
+            });
+        }
+    }
+    None
+}
+
+fn argument(words: &[String]) -> Result<String, Box<dyn Error>> {
+    words
+        .get(1)
+        .cloned()
+        .ok_or_else(|| format!("{} expects an argument", words[0]).into())
+}
+
+/// Parses `%B0101`, `%XFF`, `%D12` or a plain decimal number
+fn parse_value(word: &str) -> Result<u64, Box<dyn Error>> {
+    let parsed = if let Some(bits) = word.strip_prefix("%B") {
+        u64::from_str_radix(bits, 2).ok()
+    } else if let Some(hex) = word.strip_prefix("%X") {
+        u64::from_str_radix(hex, 16).ok()
+    } else if let Some(dec) = word.strip_prefix("%D") {
+        dec.parse::<i64>().ok().map(|v| v as u64)
+    } else {
+        word.parse::<i64>().ok().map(|v| v as u64)
+    };
+    parsed.ok_or_else(|| format!("Invalid value {}", word).into())
+}
+
+/// Splits a script into words and punctuation, dropping `//` and `/* */` comments
+fn tokenize(src: &str) -> Result<Vec<Token>, Box<dyn Error>> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let mut chars = src.chars().peekable();
+
+    fn flush(word: &mut String, tokens: &mut Vec<Token>) {
+        if !word.is_empty() {
+            tokens.push(Token::Word(std::mem::take(word)));
+        }
+    }
+
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'/') => {
+                flush(&mut word, &mut tokens);
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                flush(&mut word, &mut tokens);
+                chars.next();
+                let mut last = ' ';
+                loop {
+                    match chars.next() {
+                        Some('/') if last == '*' => break,
+                        Some(c) => last = c,
+                        None => return Err("Unterminated /* comment in test script".into()),
+                    }
+                }
+            }
+            '"' => {
+                flush(&mut word, &mut tokens);
+                let quoted: String = chars.by_ref().take_while(|&c| c != '"').collect();
+                tokens.push(Token::Word(format!("\"{}\"", quoted)));
+            }
+            ',' | ';' | '{' | '}' => {
+                flush(&mut word, &mut tokens);
+                tokens.push(match c {
+                    ',' => Token::Comma,
+                    ';' => Token::Semicolon,
+                    '{' => Token::Open,
+                    _ => Token::Close,
+                });
+            }
+            c if c.is_whitespace() => flush(&mut word, &mut tokens),
+            c => word.push(c),
+        }
+    }
+    flush(&mut word, &mut tokens);
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eframe::egui::Pos2;
+
+    /// A 16-bit NOT, `out[i] = !in[i]`
+    fn not16() -> Data {
+        let mut data = Data::new();
+        for bit in 0..16 {
+            let kinds = [PrimitiveKind::TOGGLE, PrimitiveKind::NOT, PrimitiveKind::LIGHT];
+            let [toggle, not, light] = kinds.map(|kind| data.add_primitive(kind, Pos2::ZERO));
+            data.get_gate_mut(toggle).expect("toggle").name = format!("in[{}]", bit);
+            data.get_gate_mut(light).expect("light").name = format!("out[{}]", bit);
+            for (from, to) in [(toggle, not), (not, light)] {
+                let (output, input) = (data.gate_outputs(from)[0], data.gate_inputs(to)[0]);
+                data.connect(output, input).expect("output to input");
+            }
+        }
+        data
+    }
+
+    const SCRIPT: &str = "// negative values go in and come out as 16-bit two's complement
+load Not16.hdl, output-file Not16.out, compare-to Not16.cmp,
+output-list in%D1.6.1 out%D1.6.1 out%X1.2.1 out%B1.4.1;
+set in 0, eval, output;
+set in -1, eval, output;
+set in %B0000000011110000, eval, output;
+repeat 2 { set in 21845, eval, output; }
+";
+
+    const COMPARE: &str = "|   in   |  out   |out |  out   |
+|      0 |     -1 | FF | 1111 |
+|     -1 |      0 | 00 | 0000 |
+|    240 |   -241 | 0F | 1111 |
+|  21845 | -21846 | AA | 1010 |
+|  21845 | -21846 | AA | 1010 |
+";
+
+    #[test]
+    fn script_matches_its_compare_file() {
+        let dir = std::env::temp_dir().join(format!("gates-test-script-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        not16().to_chip("Not16").save_to_file(dir.join("Not16.chip")).expect("chip saved");
+        std::fs::write(dir.join("Not16.tst"), SCRIPT).expect("script saved");
+        std::fs::write(dir.join("Not16.cmp"), COMPARE).expect("compare file saved");
+
+        let report = TestScript::run_file(dir.join("Not16.tst"), 1);
+        let written = std::fs::read_to_string(dir.join("Not16.out"));
+        std::fs::remove_dir_all(&dir).expect("temp dir removed");
+
+        let report = report.expect("script runs");
+        assert!(report.compared);
+        assert!(report.passed(), "{:?}\n{}", report.mismatch, report.lines.join("\n"));
+        assert_eq!(written.expect("output file written"), report.lines.join("\n") + "\n");
+    }
+
+    #[test]
+    fn mismatches_are_reported_by_row() {
+        let mut data = not16();
+        let script = TestScript::parse("output-list in%D1.6.1 out%D1.6.1; set in 1, eval, output;").expect("parses");
+        let lines = script.run(&mut data).expect("runs");
+        let mismatch = compare_lines(&lines, "| in | out |\n| 1 | -1 |\n").expect("out is -2");
+        assert_eq!((mismatch.row, mismatch.actual.split('|').nth(2).map(str::trim)), (1, Some("-2")));
+    }
+
+    #[test]
+    fn decimals_are_twos_complement() {
+        let column = OutputColumn::parse("x%D1.6.1").expect("parses");
+        assert_eq!(column.format_value(parse_value("-1").expect("value") & 0xFFFF, 16), "-1");
+        assert_eq!(column.format_value(0x8000, 16), "-32768");
+        assert_eq!(column.format_value(7, 3), "7"); // narrow buses are never negative
+        assert_eq!(column.format_value(u64::MAX, 64), "-1");
+        let hex = OutputColumn::parse("x%X1.2.1").expect("parses");
+        assert_eq!(hex.format_value(0x1234, 16), "34");
+    }
+}
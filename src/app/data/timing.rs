@@ -0,0 +1,163 @@
+use std::fmt::{self, Display, Formatter};
+
+use super::*;
+
+/// The kinds of gate that take time to switch, pins and constant signals take none
+pub const DELAY_KINDS: [PrimitiveKind; 7] = [
+    PrimitiveKind::BUFFER,
+    PrimitiveKind::NOT,
+    PrimitiveKind::AND,
+    PrimitiveKind::OR,
+    PrimitiveKind::XOR,
+    PrimitiveKind::NAND,
+    PrimitiveKind::NOR,
+];
+
+/// Parses "KIND=DELAY", e.g. "XOR=2.5"
+pub fn parse_gate_delay(text: &str) -> Result<(PrimitiveKind, f64), String> {
+    let (name, delay) = text.split_once('=').ok_or_else(|| format!("expected KIND=DELAY, got {}", text))?;
+    let kind = DELAY_KINDS
+        .iter()
+        .find(|kind| kind.to_string().eq_ignore_ascii_case(name.trim()))
+        .cloned()
+        .ok_or_else(|| format!("no delay for {}, gates with a delay are {:?}", name, DELAY_KINDS))?;
+    match delay.trim().parse::<f64>() {
+        Ok(delay) if delay >= 0.0 && delay.is_finite() => Ok((kind, delay)),
+        _ => Err(format!("invalid delay {}", delay)),
+    }
+}
+
+/// The slowest way a signal gets from one input pin to one output pin
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PinPath {
+    pub from: String,
+    pub to: String,
+    pub depth: usize, // most gates a signal passes through
+    pub delay: f64,   // largest sum of gate delays, equal to depth when every delay is 1
+    #[serde(skip)]
+    pub ids: Vec<usize>, // gates and wires along the slowest path, from input to output
+}
+
+impl Display for PinPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} -> {}: depth {}, delay {}", self.from, self.to, self.depth, self.delay)
+    }
+}
+
+/// Timing of every connected pair of input and output pins, slowest first
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct TimingReport {
+    pub paths: Vec<PinPath>,
+}
+
+impl TimingReport {
+    /// The slowest path on the board
+    pub fn critical(&self) -> Option<&PinPath> {
+        self.paths.first()
+    }
+}
+
+impl Display for TimingReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let Some(critical) = self.critical() else {
+            return writeln!(f, "No output pin depends on an input pin");
+        };
+        writeln!(f, "Critical path {}", critical)?;
+        for path in &self.paths {
+            writeln!(f, "  {}", path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Data {
+    /// How long a gate of this kind takes to switch, 1 unless set in `gate_delays`
+    pub fn gate_delay(&self, kind: &PrimitiveKind) -> f64 {
+        match DELAY_KINDS.contains(kind) {
+            true => self.gate_delays.get(kind).copied().unwrap_or(1.0),
+            false => 0.0,
+        }
+    }
+
+    /// Logic depth and total delay from every input pin to every output pin it reaches
+    /// Each gate between the pins counts once towards the depth and adds its `gate_delay`
+    /// The board must be flattened and free of feedback loops
+    pub fn timing(&self) -> Result<TimingReport, CircuitError> {
+        if let Some(feedback) = feedback_loops(&self.live_data).first() {
+            return Err(CircuitError::Unsupported {
+                id: feedback.gates.iter().min().copied(),
+                message: "this gate is part of a feedback loop, timing needs a combinational board".to_string(),
+            });
+        }
+        let mut kinds: HashMap<usize, PrimitiveKind> = HashMap::new();
+        for gate in self.live_data.values().filter_map(|item| item.as_any().downcast_ref::<Gate>()) {
+            match &gate.kind {
+                GateKind::Primitive(kind) => kinds.insert(gate.id, kind.clone()),
+                _ => {
+                    return Err(CircuitError::Unsupported {
+                        id: Some(gate.id),
+                        message: format!("{} has no timing, flatten it first", gate.name),
+                    });
+                }
+            };
+        }
+
+        let order = gate_order(&self.live_data);
+        let mut successors: HashMap<usize, Vec<(usize, usize)>> = HashMap::new();
+        for (from, to, wire) in gate_edges(&self.live_data) {
+            successors.entry(from).or_default().push((to, wire));
+        }
+        let (inputs, outputs) = self.pin_groups();
+        let outputs: Vec<(String, Vec<usize>)> = outputs.iter().map(|pin| (pin.name.clone(), self.find_pin(&pin.name))).collect();
+
+        let mut paths = Vec::new();
+        for input in &inputs {
+            // longest depth and delay to reach every gate's output, and where the slowest one came from
+            let mut depth: HashMap<usize, usize> = HashMap::new();
+            let mut delay: HashMap<usize, f64> = HashMap::new();
+            let mut came_from: HashMap<usize, (usize, usize)> = HashMap::new(); // gate -> (gate, wire)
+            for pin in self.find_pin(&input.name) {
+                depth.insert(pin, 0);
+                delay.insert(pin, 0.0);
+            }
+            for &gate in &order {
+                let (Some(&gate_depth), Some(&gate_delay)) = (depth.get(&gate), delay.get(&gate)) else {
+                    continue;
+                };
+                for &(next, wire) in successors.get(&gate).into_iter().flatten() {
+                    let kind = &kinds[&next];
+                    let counts = DELAY_KINDS.contains(kind) as usize;
+                    let next_depth = depth.entry(next).or_default();
+                    *next_depth = (*next_depth).max(gate_depth + counts);
+                    let next_delay = gate_delay + self.gate_delay(kind);
+                    if delay.get(&next).is_none_or(|&known| next_delay > known) {
+                        delay.insert(next, next_delay);
+                        came_from.insert(next, (gate, wire));
+                    }
+                }
+            }
+
+            for (name, pins) in &outputs {
+                let Some(&slowest) = pins.iter().filter(|pin| delay.contains_key(pin)).max_by(|a, b| delay[a].total_cmp(&delay[b])) else {
+                    continue;
+                };
+                let mut ids = vec![slowest];
+                let mut at = slowest;
+                while let Some(&(gate, wire)) = came_from.get(&at) {
+                    ids.extend([wire, gate]);
+                    at = gate;
+                }
+                ids.reverse();
+                paths.push(PinPath {
+                    from: input.name.clone(),
+                    to: name.clone(),
+                    depth: pins.iter().filter_map(|pin| depth.get(pin)).max().copied().unwrap_or(0),
+                    delay: delay[&slowest],
+                    ids,
+                });
+            }
+        }
+        paths.sort_by(|a, b| b.delay.total_cmp(&a.delay).then(b.depth.cmp(&a.depth)));
+        Ok(TimingReport { paths })
+    }
+}
@@ -0,0 +1,149 @@
+use super::*;
+
+/// Truth tables are limited to this many input bits (65536 rows)
+pub const MAX_TRUTH_TABLE_BITS: usize = 16;
+
+/// An input or output pin of the board, buses (`a[0]`, `a[1]`, ...) count as one pin
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct PinGroup {
+    pub name: String,
+    pub width: usize,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TruthRow {
+    pub inputs: Vec<u64>,
+    pub outputs: Vec<u64>,
+    pub stable: bool, // false if the outputs were still changing, the values are then the last ones seen
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TruthTable {
+    pub inputs: Vec<PinGroup>,
+    pub outputs: Vec<PinGroup>,
+    pub rows: Vec<TruthRow>,
+}
+
+impl TruthTable {
+    /// Plain text table, one column per pin, bus values in hex
+    pub fn to_text(&self) -> String {
+        let format_value = |value: u64, width: usize| {
+            if width == 1 {
+                value.to_string()
+            } else {
+                format!("0x{:X}", value)
+            }
+        };
+        let header: Vec<&str> = self
+            .inputs
+            .iter()
+            .chain(self.outputs.iter())
+            .map(|pin| pin.name.as_str())
+            .collect();
+        let widths: Vec<usize> = self
+            .inputs
+            .iter()
+            .chain(self.outputs.iter())
+            .map(|pin| pin.name.len().max(2 + pin.width.div_ceil(4)))
+            .collect();
+
+        let mut text = String::new();
+        let line = |cells: Vec<String>| {
+            let cells: Vec<String> = cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:^width$}", cell, width = width))
+                .collect();
+            format!(
+                "| {} | {} |\n",
+                cells[..self.inputs.len()].join(" | "),
+                cells[self.inputs.len()..].join(" | ")
+            )
+        };
+        text.push_str(&line(header.iter().map(|h| h.to_string()).collect()));
+        for row in &self.rows {
+            let cells = row
+                .inputs
+                .iter()
+                .zip(&self.inputs)
+                .chain(row.outputs.iter().zip(&self.outputs))
+                .map(|(value, pin)| format_value(*value, pin.width))
+                .collect();
+            let mut row_line = line(cells);
+            if !row.stable {
+                row_line.insert_str(row_line.len() - 1, " (unstable)");
+            }
+            text.push_str(&row_line);
+        }
+        text
+    }
+}
+
+impl Data {
+    /// The TOGGLE (input) and LIGHT (output) pins of the board, sorted by name
+    pub fn pin_groups(&self) -> (Vec<PinGroup>, Vec<PinGroup>) {
+        let mut inputs: HashMap<String, usize> = HashMap::new();
+        let mut outputs: HashMap<String, usize> = HashMap::new();
+        for gate in self.live_data.values().filter_map(|item| item.as_any().downcast_ref::<Gate>()) {
+            let groups = match gate.kind {
+                GateKind::Primitive(PrimitiveKind::TOGGLE) => &mut inputs,
+                GateKind::Primitive(PrimitiveKind::LIGHT) => &mut outputs,
+                _ => continue,
+            };
+            let (name, width) = match split_bus_name(&gate.name) {
+                Some((base, bit)) => (base.to_string(), bit + 1),
+                None => (gate.name.clone(), 1),
+            };
+            let entry = groups.entry(name).or_insert(0);
+            *entry = (*entry).max(width);
+        }
+
+        let sorted = |groups: HashMap<String, usize>| {
+            let mut groups: Vec<PinGroup> = groups
+                .into_iter()
+                .map(|(name, width)| PinGroup { name, width })
+                .collect();
+            groups.sort_by(|a, b| a.name.cmp(&b.name));
+            groups
+        };
+        (sorted(inputs), sorted(outputs))
+    }
+
+    /// Sets the inputs to every combination and records the settled outputs
+    /// The first input is the most significant, so rows count up like a binary number
+    /// Every row starts from the board's current state, 64 rows are simulated at once
+    pub fn truth_table(&mut self) -> Result<TruthTable, Box<dyn Error>> {
+        let (inputs, outputs) = self.pin_groups();
+        let bits: usize = inputs.iter().map(|pin| pin.width).sum();
+        if bits > MAX_TRUTH_TABLE_BITS {
+            return Err(format!(
+                "{} input bits is too many for a truth table, at most {} are supported",
+                bits, MAX_TRUTH_TABLE_BITS
+            )
+            .into());
+        }
+        if outputs.is_empty() {
+            return Err("The board has no LIGHT outputs".into());
+        }
+
+        let max_ticks = self.settle_limit();
+        let mut rows = Vec::with_capacity(1 << bits);
+        self.sweep_inputs(&inputs, &outputs, max_ticks, |combination, values, stable| {
+            let mut shift = bits;
+            let inputs = inputs
+                .iter()
+                .map(|pin| {
+                    shift -= pin.width;
+                    (combination >> shift) & ((1u64 << pin.width) - 1)
+                })
+                .collect();
+            rows.push(TruthRow {
+                inputs,
+                outputs: values.to_vec(),
+                stable,
+            });
+        })?;
+
+        Ok(TruthTable { inputs, outputs, rows })
+    }
+}
@@ -0,0 +1,302 @@
+use super::*;
+
+/// Number of input vectors simulated in one pass, one per bit of a u64
+pub const LANES: usize = 64;
+
+/// Bit k of the lane number, for every lane: lane n has bit n set if bit k of n is 1
+const LANE_BITS: [u64; 6] = [
+    0xAAAA_AAAA_AAAA_AAAA,
+    0xCCCC_CCCC_CCCC_CCCC,
+    0xF0F0_F0F0_F0F0_F0F0,
+    0xFF00_FF00_FF00_FF00,
+    0xFFFF_0000_FFFF_0000,
+    0xFFFF_FFFF_0000_0000,
+];
+
+/// Runs 64 copies of a board side by side on its netlist, every signal is a u64 with one bit per copy
+/// Used for truth tables and exhaustive tests, it does not touch live_data and reports no errors
+/// (gates that would raise one are skipped, like in a normal tick)
+#[derive(Debug, Clone)]
+pub struct VectorSim {
+    netlist: Netlist,
+    gate_index: HashMap<usize, usize>,
+    pub gate_states: Vec<u64>,
+    pub input_signals: Vec<u64>,
+    pub output_signals: Vec<u64>,
+    next_inputs: Vec<u64>,
+}
+
+impl VectorSim {
+    /// Every lane starts in the state the netlist is in
+    pub fn new(netlist: Netlist) -> Self {
+        let lane = |signal: &bool| if *signal { u64::MAX } else { 0 };
+        VectorSim {
+            gate_index: netlist.gates.iter().enumerate().map(|(index, gate)| (gate.id, index)).collect(),
+            gate_states: netlist.gates.iter().map(|gate| lane(&gate.state)).collect(),
+            input_signals: netlist.input_signals.iter().map(lane).collect(),
+            output_signals: netlist.output_signals.iter().map(lane).collect(),
+            next_inputs: vec![0; netlist.input_ids.len()],
+            netlist,
+        }
+    }
+
+    /// State of a gate in every lane, for LIGHT pins this is the value they show
+    pub fn gate(&self, gate_id: usize) -> u64 {
+        self.gate_index.get(&gate_id).map_or(0, |&index| self.gate_states[index])
+    }
+
+    /// Sets the state of a gate in every lane, how TOGGLE pins are driven
+    pub fn set_gate(&mut self, gate_id: usize, lanes: u64) {
+        if let Some(&index) = self.gate_index.get(&gate_id) {
+            self.gate_states[index] = lanes;
+        }
+    }
+
+    /// One tick in every lane, returns the lanes in which anything changed
+    pub fn tick(&mut self) -> u64 {
+        let netlist = &self.netlist;
+        self.next_inputs.iter_mut().for_each(|signal| *signal = 0);
+        let mut changed = 0;
+        let mut ins = Vec::new();
+
+        for (index, gate) in netlist.gates.iter().enumerate() {
+            let Ok(kind) = &gate.kind else {
+                continue;
+            };
+            ins.clear();
+            ins.extend(netlist.fan_in[gate.fan_in.clone()].iter().map(|&input| self.input_signals[input]));
+
+            let state = &mut self.gate_states[index];
+            let old_state = *state;
+            let result = kind.evaluate_wide(gate.id, state, &ins, gate.n_outs);
+            changed |= old_state ^ *state;
+            let (Ok(Some(signal)), Some(Ok(output))) = (result, gate.output) else {
+                continue;
+            };
+
+            changed |= self.output_signals[output] ^ signal;
+            self.output_signals[output] = signal;
+            for &wire in &netlist.wire_order[netlist.fan_out[output].clone()] {
+                if let Some(dest) = netlist.wire_dest[wire] {
+                    self.next_inputs[dest] = signal;
+                }
+            }
+        }
+
+        for (signal, next) in self.input_signals.iter_mut().zip(&self.next_inputs) {
+            changed |= *signal ^ next;
+            *signal = *next;
+        }
+        changed
+    }
+
+    /// Ticks until the lanes in `lanes` stop changing, or `max_ticks` ran out
+    /// Returns the lanes that settled, the others show the values of the last tick
+    pub fn settle(&mut self, max_ticks: usize, lanes: u64) -> u64 {
+        let mut settled = 0;
+        for _ in 0..max_ticks {
+            // a lane that did not change will never change again, nothing feeds in from outside
+            settled |= !self.tick();
+            if settled & lanes == lanes {
+                break;
+            }
+        }
+        settled & lanes
+    }
+}
+
+/// Lanes in which bit `k` of the combination is set, for the 64 combinations starting at `first`
+/// The low 6 bits follow the fixed lane patterns, the bits above are the same in every lane
+fn combination_lanes(first: u64, k: usize) -> u64 {
+    match LANE_BITS.get(k) {
+        Some(pattern) => *pattern,
+        None if k < 64 && (first >> k) & 1 == 1 => u64::MAX,
+        None => 0,
+    }
+}
+
+impl VectorSim {
+    /// Simulates the 64 combinations starting at `first` (the ones in `lanes`) from this state
+    /// `inputs` are (combination bit, gate) pairs, `outputs` the gates of every output pin
+    /// Returns the state of every output gate, per pin, and the lanes that settled
+    pub fn sweep_block(
+        &self,
+        first: u64,
+        lanes: u64,
+        inputs: &[(usize, usize)],
+        outputs: &[Vec<usize>],
+        max_ticks: usize,
+    ) -> (Vec<Vec<u64>>, u64) {
+        let mut sim = self.clone();
+        for &(bit, gate_id) in inputs {
+            sim.set_gate(gate_id, combination_lanes(first, bit));
+        }
+        let settled = sim.settle(max_ticks, lanes);
+        let states = outputs
+            .iter()
+            .map(|gates| gates.iter().map(|gate_id| sim.gate(*gate_id)).collect())
+            .collect();
+        (states, settled)
+    }
+}
+
+/// Splits `total` combinations into blocks of 64, with the lanes each block uses
+pub fn sweep_blocks(total: u64) -> Vec<(u64, u64)> {
+    (0..total)
+        .step_by(LANES)
+        .map(|first| {
+            let count = (total - first).min(LANES as u64);
+            (first, if count == LANES as u64 { u64::MAX } else { (1 << count) - 1 })
+        })
+        .collect()
+}
+
+impl Data {
+    /// Simulates the board for every combination of the given input pins, 64 combinations per pass
+    /// The first input is the most significant bit of the combination, like in a truth table
+    /// Bit n of a bus is bit n of its value, also when some bits of the bus are missing
+    /// Each combination starts from the board's current state, the board itself is not changed
+    /// With `sim.threads` above 1 the passes are spread over threads, `visit` still sees them in order
+    /// `visit` gets the combination, the output values in the order of `outputs`, and whether they settled
+    pub fn sweep_inputs<F>(
+        &mut self,
+        inputs: &[PinGroup],
+        outputs: &[PinGroup],
+        max_ticks: usize,
+        mut visit: F,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        F: FnMut(u64, &[u64], bool),
+    {
+        let bits: usize = inputs.iter().map(|pin| pin.width).sum();
+        // the gate of every input bit, with its bit in the combination
+        let mut shift = bits;
+        let mut input_gates = Vec::new();
+        for pin in inputs {
+            shift -= pin.width;
+            for (bit, gate_id) in self.find_pin_bits(&pin.name)? {
+                if bit < pin.width {
+                    input_gates.push((shift + bit, gate_id));
+                }
+            }
+        }
+        let mut output_bits: Vec<Vec<usize>> = Vec::new();
+        let mut output_gates: Vec<Vec<usize>> = Vec::new();
+        for pin in outputs {
+            let (bits, gates) = self.find_pin_bits(&pin.name)?.into_iter().filter(|(bit, _)| *bit < 64).unzip();
+            output_bits.push(bits);
+            output_gates.push(gates);
+        }
+        let start = VectorSim::new(self.netlist().clone());
+
+        let blocks = sweep_blocks(1u64 << bits);
+        let run = |&(first, lanes): &(u64, u64)| start.sweep_block(first, lanes, &input_gates, &output_gates, max_ticks);
+
+        let threads = self.sim.threads.min(blocks.len()).max(1);
+        let results: Vec<(Vec<Vec<u64>>, u64)> = if threads == 1 {
+            Vec::new() // run one block at a time below, nothing needs to be kept
+        } else {
+            let per_thread = blocks.len().div_ceil(threads);
+            crossbeam::scope(|scope| {
+                let handles: Vec<_> = blocks
+                    .chunks(per_thread)
+                    .map(|chunk| scope.spawn(move |_| chunk.iter().map(run).collect::<Vec<_>>()))
+                    .collect();
+                handles.into_iter().flat_map(|handle| handle.join().expect("sweep panicked")).collect()
+            })
+            .expect("sweep panicked")
+        };
+
+        let mut values = vec![0; outputs.len()];
+        for (index, block) in blocks.iter().enumerate() {
+            let (states, settled) = match results.get(index) {
+                Some(result) => result.clone(),
+                None => run(block),
+            };
+            let (first, lanes) = *block;
+            for lane in 0..lanes.count_ones() as usize {
+                for ((value, states), bits) in values.iter_mut().zip(&states).zip(&output_bits) {
+                    *value = states
+                        .iter()
+                        .zip(bits)
+                        .fold(0, |acc, (state, bit)| acc | (((state >> lane) & 1) << bit));
+                }
+                visit(first + lane as u64, &values, (settled >> lane) & 1 == 1);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An SR latch made of NORs with its outputs on LIGHTs, and an XOR of the inputs
+    /// Returns the board, its TOGGLEs (s, r) and every gate in the order they were added
+    fn latch() -> (Data, [usize; 2], Vec<usize>) {
+        let mut data = Data::new();
+        let mut add = |kind| data.add_primitive(kind, Pos2::ZERO);
+        let gates = vec![
+            add(PrimitiveKind::TOGGLE),
+            add(PrimitiveKind::TOGGLE),
+            add(PrimitiveKind::NOR),
+            add(PrimitiveKind::NOR),
+            add(PrimitiveKind::XOR),
+            add(PrimitiveKind::LIGHT),
+            add(PrimitiveKind::LIGHT),
+            add(PrimitiveKind::LIGHT),
+        ];
+        let [s, r, q, not_q, xor, light_q, light_not_q, light_xor] = gates[..] else {
+            unreachable!()
+        };
+        for (from, to, input) in [
+            (r, q, 0),
+            (not_q, q, 1),
+            (s, not_q, 0),
+            (q, not_q, 1),
+            (s, xor, 0),
+            (r, xor, 1),
+            (q, light_q, 0),
+            (not_q, light_not_q, 0),
+            (xor, light_xor, 0),
+        ] {
+            let output = data.gate_outputs(from)[0];
+            let input = data.gate_inputs(to)[input];
+            data.connect(output, input).expect("output to input");
+        }
+        (data, [s, r], gates)
+    }
+
+    #[test]
+    fn lanes_match_scalar_ticks() {
+        let (mut data, toggles, gates) = latch();
+        let mut sim = VectorSim::new(data.netlist().clone());
+        // lane n runs combination n, bit k of it drives toggle k
+        for (k, toggle) in toggles.iter().enumerate() {
+            sim.set_gate(*toggle, LANE_BITS[k]);
+        }
+
+        let mut boards: Vec<(Data, Vec<usize>)> = (0..4)
+            .map(|combination| {
+                let (mut data, toggles, gates) = latch();
+                for (k, toggle) in toggles.iter().enumerate() {
+                    data.get_gate_mut(*toggle).expect("toggle").state = (combination >> k) & 1 == 1;
+                }
+                (data, gates)
+            })
+            .collect();
+        // s = r = 0 from all zeros oscillates, so this also compares the ticks of a board that never settles
+        for tick in 0..8 {
+            sim.tick();
+            for (lane, (board, board_gates)) in boards.iter_mut().enumerate() {
+                board.tick();
+                for (&id, &board_id) in gates.iter().zip(board_gates.iter()) {
+                    let state = board.live_data[&board_id].as_any().downcast_ref::<Gate>().expect("gate").state;
+                    let lane_state = (sim.gate(id) >> lane) & 1 == 1;
+                    assert_eq!(lane_state, state, "gate {} in lane {} after tick {}", id, lane, tick);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::fmt::Write;
+
+use super::*;
+
+/// Turns a gate or chip name into a legal Verilog identifier
+pub fn verilog_identifier(name: &str) -> String {
+    let mut id: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect();
+    if !id.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        id.insert(0, '_');
+    }
+    id
+}
+
+impl Data {
+    /// Exports the board as a structural Verilog module made of continuous assignments
+    /// TOGGLE pins become inputs and LIGHT pins outputs, buses become vectors
+    pub fn to_verilog(&self, module_name: &str) -> Result<String, Box<dyn Error>> {
+        let (inputs, outputs) = self.pin_groups();
+
+        let mut gates: Vec<&Gate> = self
+            .live_data
+            .values()
+            .filter_map(|item| item.as_any().downcast_ref::<Gate>())
+            .collect();
+        gates.sort_by_key(|gate| gate.id);
+
+        // the Verilog expression for a pin name, `a[0]` stays a bit select of vector `a`
+        let pin_expr = |name: &str| match split_bus_name(name) {
+            Some((base, bit)) => format!("{}[{}]", verilog_identifier(base), bit),
+            None => verilog_identifier(name),
+        };
+
+        // every gate output gets a net named after its id, pins use their own name
+        let net_of_output = |output_id: usize| -> Result<String, Box<dyn Error>> {
+            let output = self
+                .live_data
+                .get(&output_id)
+                .and_then(|item| item.as_any().downcast_ref::<Output>())
+                .ok_or_else(|| format!("Wire source {} is not an output", output_id))?;
+            let parent = output
+                .parent_id
+                .and_then(|id| self.live_data.get(&id))
+                .and_then(|item| item.as_any().downcast_ref::<Gate>());
+            Ok(match parent {
+                Some(gate) if gate.kind == GateKind::Primitive(PrimitiveKind::TOGGLE) => pin_expr(&gate.name),
+                _ => format!("n{}", output_id),
+            })
+        };
+        let net_of_input = |input_id: usize| -> Result<String, Box<dyn Error>> {
+            let source = self
+                .live_data
+                .get(&input_id)
+                .and_then(|item| item.as_any().downcast_ref::<Input>())
+                .and_then(|input| input.source_wire_id)
+                .and_then(|wire_id| self.live_data.get(&wire_id))
+                .and_then(|item| item.as_any().downcast_ref::<Wire>());
+            match source {
+                Some(wire) => net_of_output(wire.source_id),
+                None => Ok("1'b0".to_string()), // unconnected inputs read 0 in the simulator too
+            }
+        };
+
+        let mut ports = Vec::new();
+        let mut body = String::new();
+        for (direction, pins) in [("input", &inputs), ("output", &outputs)] {
+            for pin in pins {
+                let id = verilog_identifier(&pin.name);
+                ports.push(id.clone());
+                if pin.width > 1 {
+                    writeln!(body, "    {} [{}:0] {};", direction, pin.width - 1, id)?;
+                } else {
+                    writeln!(body, "    {} {};", direction, id)?;
+                }
+            }
+        }
+
+        let mut assigns = String::new();
+        let mut nets = Vec::new();
+        for gate in &gates {
+            let kind = match &gate.kind {
+                GateKind::Primitive(kind) => kind.clone(),
+                GateKind::None => continue,
+                GateKind::Custom(name) => {
+                    return Err(format!("Custom chip {} can't be exported yet, flatten it first", name).into());
+                }
+            };
+            let mut ins = self
+                .gate_inputs(gate.id)
+                .into_iter()
+                .map(net_of_input)
+                .collect::<Result<Vec<String>, _>>()?;
+            if ins.is_empty() {
+                ins.push("1'b0".to_string());
+            }
+            let join = |op: &str| ins.join(&format!(" {} ", op));
+            let expr = match kind {
+                PrimitiveKind::TOGGLE | PrimitiveKind::None => continue,
+                PrimitiveKind::LIGHT => {
+                    let source = ins.first().cloned().unwrap_or_else(|| "1'b0".to_string());
+                    writeln!(assigns, "    assign {} = {};", pin_expr(&gate.name), source)?;
+                    continue;
+                }
+                PrimitiveKind::PULSE => {
+                    return Err(format!("PULSE {} has no Verilog equivalent, use a TOGGLE", gate.id).into());
+                }
+                PrimitiveKind::HISIGNAL => "1'b1".to_string(),
+                PrimitiveKind::LOSIGNAL => "1'b0".to_string(),
+                PrimitiveKind::BUFFER => join("&"),
+                PrimitiveKind::NOT => format!("~{}", join("&")),
+                PrimitiveKind::AND => join("&"),
+                PrimitiveKind::OR => join("|"),
+                PrimitiveKind::XOR => join("^"),
+                PrimitiveKind::NAND => format!("~({})", join("&")),
+                PrimitiveKind::NOR => format!("~({})", join("|")),
+            };
+            for output_id in self.gate_outputs(gate.id) {
+                nets.push(format!("n{}", output_id));
+                writeln!(assigns, "    assign n{} = {}; // {} {}", output_id, expr, gate.name, gate.id)?;
+            }
+        }
+
+        let mut verilog = String::new();
+        writeln!(verilog, "// Exported from Gates")?;
+        writeln!(verilog, "module {}({});", verilog_identifier(module_name), ports.join(", "))?;
+        verilog.push_str(&body);
+        if !nets.is_empty() {
+            writeln!(verilog, "    wire {};", nets.join(", "))?;
+        }
+        writeln!(verilog)?;
+        verilog.push_str(&assigns);
+        writeln!(verilog, "endmodule")?;
+        Ok(verilog)
+    }
+}
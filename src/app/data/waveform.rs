@@ -0,0 +1,458 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+use super::{Gate, GateKind, Input, Logical, Output, PrimitiveKind, Wire};
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeUnit {
+    S,
+    Ms,
+    Us,
+    #[default]
+    Ns,
+    Ps,
+    Fs,
+}
+
+impl TimeUnit {
+    pub const ALL: [TimeUnit; 6] = [TimeUnit::S, TimeUnit::Ms, TimeUnit::Us, TimeUnit::Ns, TimeUnit::Ps, TimeUnit::Fs];
+}
+
+impl Display for TimeUnit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimeUnit::S => write!(f, "s"),
+            TimeUnit::Ms => write!(f, "ms"),
+            TimeUnit::Us => write!(f, "us"),
+            TimeUnit::Ns => write!(f, "ns"),
+            TimeUnit::Ps => write!(f, "ps"),
+            TimeUnit::Fs => write!(f, "fs"),
+        }
+    }
+}
+
+/// How much time one simulation tick stands for in the dump, VCD allows 1, 10 or 100 of a unit
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timescale {
+    pub magnitude: u32,
+    pub unit: TimeUnit,
+}
+
+impl Default for Timescale {
+    fn default() -> Self {
+        Timescale {
+            magnitude: 1,
+            unit: TimeUnit::Ns,
+        }
+    }
+}
+
+impl Display for Timescale {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.magnitude, self.unit)
+    }
+}
+
+/// The history of a single signal: its value when recording started and every change after that
+#[derive(Debug, Clone)]
+pub struct RecordedSignal {
+    pub id: usize,
+    pub name: String,
+    pub changes: Vec<(u64, bool)>, // (tick, new value)
+}
+
+impl RecordedSignal {
+    /// The value of this signal at a given tick, false before it was first sampled
+    pub fn value_at(&self, tick: u64) -> bool {
+        match self.changes.partition_point(|(t, _)| *t <= tick) {
+            0 => false,
+            i => self.changes[i - 1].1,
+        }
+    }
+}
+
+/// Records the values of watched wires, pins and gates every simulation tick
+#[derive(Debug, Clone, Default)]
+pub struct SignalRecorder {
+    pub recording: bool,
+    pub timescale: Timescale,
+    pub signals: Vec<RecordedSignal>,
+    pub tick: u64, // ticks recorded since recording started
+}
+
+impl SignalRecorder {
+    pub fn is_watching(&self, id: usize) -> bool {
+        self.signals.iter().any(|s| s.id == id)
+    }
+
+    /// Starts watching a wire, input, output or gate
+    pub fn watch(&mut self, id: usize, live_data: &HashMap<usize, Box<dyn Logical>>) -> Result<(), Box<dyn Error>> {
+        if self.is_watching(id) {
+            return Ok(());
+        }
+        let name = signal_name(id, live_data).ok_or_else(|| format!("{} has no signal to record", id))?;
+        self.signals.push(RecordedSignal {
+            id,
+            name,
+            changes: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// Starts watching a signal under a name of our choosing, used by probes
+    pub fn watch_named(&mut self, id: usize, name: String) {
+        if !self.is_watching(id) {
+            self.signals.push(RecordedSignal {
+                id,
+                name,
+                changes: Vec::new(),
+            });
+        }
+    }
+
+    pub fn rename(&mut self, id: usize, name: String) {
+        if let Some(signal) = self.signals.iter_mut().find(|s| s.id == id) {
+            signal.name = name;
+        }
+    }
+
+    pub fn unwatch(&mut self, id: usize) {
+        self.signals.retain(|s| s.id != id);
+    }
+
+    /// Watches every LIGHT and TOGGLE gate on the board, ordered by name
+    pub fn watch_all_pins(&mut self, live_data: &HashMap<usize, Box<dyn Logical>>) {
+        let mut pins: Vec<(&str, usize)> = live_data
+            .values()
+            .filter_map(|item| item.as_any().downcast_ref::<Gate>())
+            .filter(|gate| is_pin(gate))
+            .map(|gate| (gate.name.as_str(), gate.id))
+            .collect();
+        pins.sort();
+        for (_, id) in pins {
+            let _ = self.watch(id, live_data);
+        }
+    }
+
+    /// Clears the recorded history and starts recording from tick 0
+    pub fn start(&mut self) {
+        for signal in &mut self.signals {
+            signal.changes.clear();
+        }
+        self.tick = 0;
+        self.recording = true;
+    }
+
+    pub fn stop(&mut self) {
+        self.recording = false;
+    }
+
+    /// Should be called once after every simulation tick
+    pub fn sample(&mut self, live_data: &HashMap<usize, Box<dyn Logical>>) {
+        if !self.recording {
+            return;
+        }
+        for signal in &mut self.signals {
+            let Some(value) = signal_value(signal.id, live_data) else {
+                continue; // the item was deleted, keep its history as it is
+            };
+            if signal.changes.last().map(|(_, v)| *v) != Some(value) {
+                signal.changes.push((self.tick, value));
+            }
+        }
+        self.tick += 1;
+    }
+
+    /// Writes the recorded history as a Value Change Dump
+    /// Signals named `bus[0]`, `bus[1]`, ... are written as one vector named `bus`, bits that were not recorded are x
+    /// Viewers merge variables with the same name, so repeated names get the id of their (lowest) signal appended
+    pub fn to_vcd(&self) -> String {
+        let vars = self.groups();
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for var in &vars {
+            *counts.entry(var.name.as_str()).or_default() += 1;
+        }
+        let mut out = String::new();
+        out.push_str("$version Gates $end\n");
+        out.push_str(&format!("$timescale {} $end\n", self.timescale));
+        out.push_str("$scope module board $end\n");
+        for (index, var) in vars.iter().enumerate() {
+            let (msb, lsb) = var.bit_range();
+            let lowest = &self.signals[var.bits[var.bits.len() - 1].1];
+            let mut name = var.name.clone();
+            if counts[var.name.as_str()] > 1 {
+                name.push_str(&format!("_{}", lowest.id));
+            }
+            if lowest.name != var.name {
+                // recorded from a bus, declare which bits it covers
+                if msb == lsb {
+                    name.push_str(&format!(" [{}]", msb));
+                } else {
+                    name.push_str(&format!(" [{}:{}]", msb, lsb));
+                }
+            }
+            out.push_str(&format!(
+                "$var wire {} {} {} $end\n",
+                msb - lsb + 1,
+                vcd_identifier(index),
+                name
+            ));
+        }
+        out.push_str("$upscope $end\n$enddefinitions $end\n");
+
+        let mut times: Vec<u64> = self
+            .signals
+            .iter()
+            .flat_map(|s| s.changes.iter().map(|(t, _)| *t))
+            .collect();
+        times.push(0);
+        times.sort();
+        times.dedup();
+
+        let mut last: Vec<Option<String>> = vec![None; vars.len()];
+        for time in times {
+            let mut block = String::new();
+            for (index, var) in vars.iter().enumerate() {
+                let value = var.bits_at(&self.signals, time);
+                if last[index].as_ref() != Some(&value) {
+                    if value.len() == 1 {
+                        block.push_str(&format!("{}{}\n", value, vcd_identifier(index)));
+                    } else {
+                        block.push_str(&format!("b{} {}\n", value, vcd_identifier(index)));
+                    }
+                    last[index] = Some(value);
+                }
+            }
+            if time == 0 {
+                out.push_str(&format!("#0\n$dumpvars\n{}$end\n", block));
+            } else if !block.is_empty() {
+                out.push_str(&format!("#{}\n{}", time, block));
+            }
+        }
+        if self.tick > 0 {
+            out.push_str(&format!("#{}\n", self.tick));
+        }
+        out
+    }
+
+    pub fn write_vcd<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        std::fs::write(path, self.to_vcd())?;
+        Ok(())
+    }
+
+    /// Groups the recorded signals for display and export
+    /// Signals named `bus[0]`, `bus[1]`, ... become a single group named `bus`
+    pub fn groups(&self) -> Vec<SignalGroup> {
+        let mut groups: Vec<SignalGroup> = Vec::new();
+        let mut buses: HashMap<String, usize> = HashMap::new();
+        for (index, signal) in self.signals.iter().enumerate() {
+            if let Some((base, bit)) = split_bus_name(&signal.name) {
+                // a bit that is already in the bus starts another bus with the same name
+                if let Some(&group) = buses.get(base)
+                    && groups[group].bits.iter().all(|(b, _)| *b != bit)
+                {
+                    groups[group].add_bit(bit, index);
+                    continue;
+                }
+                buses.insert(base.to_string(), groups.len());
+                groups.push(SignalGroup {
+                    name: base.to_string(),
+                    bits: vec![(bit, index)],
+                });
+            } else {
+                groups.push(SignalGroup {
+                    name: signal.name.clone(),
+                    bits: vec![(0, index)],
+                });
+            }
+        }
+        groups
+    }
+}
+
+/// A single signal, or several recorded signals that together form a bus
+#[derive(Debug, Clone)]
+pub struct SignalGroup {
+    pub name: String,
+    pub bits: Vec<(usize, usize)>, // (bit number, index into the recorded signals), most significant first
+}
+
+impl SignalGroup {
+    fn add_bit(&mut self, bit: usize, index: usize) {
+        self.bits.push((bit, index));
+        self.bits.sort_by_key(|(bit, _)| std::cmp::Reverse(*bit));
+    }
+
+    pub fn is_bus(&self) -> bool {
+        self.bits.len() > 1
+    }
+
+    /// The highest and lowest bit number in the group
+    pub fn bit_range(&self) -> (usize, usize) {
+        let bit = |entry: Option<&(usize, usize)>| entry.map_or(0, |(bit, _)| *bit);
+        (bit(self.bits.first()), bit(self.bits.last()))
+    }
+
+    /// The value of the group at a tick as a binary string, most significant bit first
+    /// Bits between the lowest and highest one that were not recorded are x
+    pub fn bits_at(&self, signals: &[RecordedSignal], tick: u64) -> String {
+        let (msb, lsb) = self.bit_range();
+        (lsb..=msb)
+            .rev()
+            .map(|bit| match self.bits.iter().find(|(b, _)| *b == bit) {
+                Some((_, index)) if signals[*index].value_at(tick) => '1',
+                Some(_) => '0',
+                None => 'x',
+            })
+            .collect()
+    }
+
+    /// The value of the group at a tick as an integer
+    pub fn value_at(&self, signals: &[RecordedSignal], tick: u64) -> u64 {
+        self.bits
+            .iter()
+            .filter(|(bit, index)| *bit < 64 && signals[*index].value_at(tick))
+            .fold(0, |value, (bit, _)| value | (1 << bit))
+    }
+
+    /// Every tick at which any bit of the group changes, in order
+    pub fn edges(&self, signals: &[RecordedSignal]) -> Vec<u64> {
+        let mut edges: Vec<u64> = self
+            .bits
+            .iter()
+            .flat_map(|(_, index)| signals[*index].changes.iter().map(|(t, _)| *t))
+            .collect();
+        edges.sort();
+        edges.dedup();
+        edges
+    }
+}
+
+/// Splits `name[3]` into ("name", 3)
+pub fn split_bus_name(name: &str) -> Option<(&str, usize)> {
+    let (base, rest) = name.split_once('[')?;
+    let bit = rest.strip_suffix(']')?.parse().ok()?;
+    Some((base, bit))
+}
+
+/// VCD identifiers are short strings of printable ASCII characters
+fn vcd_identifier(mut index: usize) -> String {
+    let mut id = String::new();
+    loop {
+        id.push((b'!' + (index % 94) as u8) as char);
+        index /= 94;
+        if index == 0 {
+            return id;
+        }
+        index -= 1;
+    }
+}
+
+fn is_pin(gate: &Gate) -> bool {
+    matches!(
+        gate.kind,
+        GateKind::Primitive(PrimitiveKind::TOGGLE) | GateKind::Primitive(PrimitiveKind::LIGHT)
+    )
+}
+
+/// The current value of a wire, input, output or gate
+pub fn signal_value(id: usize, live_data: &HashMap<usize, Box<dyn Logical>>) -> Option<bool> {
+    let any = live_data.get(&id)?.as_any();
+    if let Some(wire) = any.downcast_ref::<Wire>() {
+        Some(wire.get_signal())
+    } else if let Some(input) = any.downcast_ref::<Input>() {
+        Some(input.signal)
+    } else if let Some(output) = any.downcast_ref::<Output>() {
+        Some(output.signal)
+    } else {
+        any.downcast_ref::<Gate>().map(|gate| gate.state)
+    }
+}
+
+/// A readable name for a signal, without spaces so it can be used in a VCD file
+/// Pins use their gate's name, other gates also get their id since names like "AND" repeat
+pub fn signal_name(id: usize, live_data: &HashMap<usize, Box<dyn Logical>>) -> Option<String> {
+    let gate_name = |gate_id: Option<usize>| {
+        gate_id
+            .and_then(|id| live_data.get(&id))
+            .and_then(|item| item.as_any().downcast_ref::<Gate>())
+            .map(|gate| format!("{}_{}", gate.name, gate.id))
+            .unwrap_or_else(|| "board".to_string())
+    };
+
+    let any = live_data.get(&id)?.as_any();
+    let name = if let Some(gate) = any.downcast_ref::<Gate>() {
+        if is_pin(gate) {
+            gate.name.clone()
+        } else {
+            format!("{}_{}", gate.name, gate.id)
+        }
+    } else if let Some(input) = any.downcast_ref::<Input>() {
+        input
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("{}.in{}", gate_name(input.parent_id), input.index))
+    } else if let Some(output) = any.downcast_ref::<Output>() {
+        output
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("{}.out{}", gate_name(output.parent_id), output.index))
+    } else if let Some(wire) = any.downcast_ref::<Wire>() {
+        // a wire carries the signal of the output it comes from
+        match live_data.get(&wire.source_id) {
+            Some(_) => format!("{}.w{}", signal_name(wire.source_id, live_data)?, id),
+            None => format!("wire_{}", id),
+        }
+    } else {
+        return None;
+    };
+    Some(name.split_whitespace().collect::<Vec<_>>().join("_"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signal(id: usize, name: &str, changes: &[(u64, bool)]) -> RecordedSignal {
+        RecordedSignal { id, name: name.to_string(), changes: changes.to_vec() }
+    }
+
+    #[test]
+    fn vcd_of_buses_with_gaps_and_repeated_names() {
+        let recorder = SignalRecorder {
+            recording: false,
+            timescale: Timescale::default(),
+            signals: vec![
+                signal(1, "clk", &[(0, false), (2, true)]),
+                signal(2, "bus[0]", &[(0, true)]),
+                signal(3, "bus[2]", &[(0, false), (3, true)]),
+                signal(4, "clk", &[(0, true)]),
+            ],
+            tick: 5,
+        };
+        let expected = "\
+$version Gates $end
+$timescale 1ns $end
+$scope module board $end
+$var wire 1 ! clk_1 $end
+$var wire 3 \" bus [2:0] $end
+$var wire 1 # clk_4 $end
+$upscope $end
+$enddefinitions $end
+#0
+$dumpvars
+0!
+b0x1 \"
+1#
+$end
+#2
+1!
+#3
+b1x1 \"
+#5
+";
+        assert_eq!(recorder.to_vcd(), expected);
+    }
+}
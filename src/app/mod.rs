@@ -6,6 +6,9 @@ pub use ui_util::ClickItem;
 mod pan_area;
 use pan_area::PanArea;
 
+mod waveform_panel;
+use waveform_panel::WaveformPanel;
+
 mod data;
 pub use data::*;
 
@@ -24,7 +27,6 @@ use serde;
 const TITLE_BAR_HEIGHT: f32 = 30.0;
 const SIDE_PANEL_WIDTH: f32 = 200.0;
 
-static mut NEXT_ID: usize = 0; // static variable to generate unique ids for gates and wires
 
 #[derive(serde::Deserialize, serde::Serialize)]
 #[serde(default)] // if we add new fields, give them default values when deserializing old state
@@ -43,6 +45,88 @@ pub struct MyApp {
 
     #[serde(skip)]
     trying_save: bool,
+    save_name: String,
+
+    #[serde(skip)]
+    renaming_gate: Option<usize>,
+    #[serde(skip)]
+    rename_buffer: String,
+
+    show_test_runner: bool,
+    test_script_path: String,
+    #[serde(skip)]
+    test_result: Option<Result<TestReport, String>>,
+
+    run_ticks: u64, // how many ticks the "Run" button queues
+    #[serde(skip)]
+    sim_status: Option<String>,
+
+    show_history: bool,
+    show_design_check: bool,
+    #[serde(skip)]
+    design_problems: Option<Vec<Problem>>, // None until the check has run
+    #[serde(skip)]
+    selected_problem: Option<usize>,
+    #[serde(skip)]
+    graph_issues: Option<Vec<GraphIssue>>, // Some while the integrity window is open
+    show_diagnostics: bool,
+    show_optimize: bool,
+    #[serde(skip)]
+    optimize_log: Vec<String>, // results of flattening and optimizing, newest last
+    map_layout: bool, // lay the board out again after mapping it to NAND or NOR
+    show_equivalence: bool,
+    equivalence_chip: String, // the saved chip the board is compared against
+    #[serde(skip)]
+    equivalence_result: Option<String>,
+    show_timing: bool,
+    #[serde(skip)]
+    timing: Option<Result<TimingReport, String>>, // None until analyzed
+    #[serde(skip)]
+    selected_path: usize, // index into the timing report's paths, the critical path first
+    show_stats: bool,
+    #[serde(skip)]
+    stats: Vec<(String, Result<ChipStats, String>)>, // the board first, then every saved chip
+    #[serde(skip)]
+    stats_compare: (usize, usize), // indexes into stats
+    show_kmap: bool,
+    #[serde(skip)]
+    kmap_table: Option<Result<TruthTable, String>>, // the board's truth table the maps are made from
+    #[serde(skip)]
+    kmap_output: usize, // index into the output bits of kmap_table
+    #[serde(skip)]
+    kmap: Option<KarnaughMap>,
+    #[serde(skip)]
+    kmap_primes: Vec<Implicant>,
+    #[serde(skip)]
+    kmap_cover: Vec<Implicant>,
+    #[serde(skip)]
+    kmap_status: Option<String>,
+    show_fsm: bool,
+    fsm: StateMachine,
+    fsm_encoding: StateEncoding,
+    fsm_pins: (String, String), // inputs and outputs as typed, separated by commas
+    fsm_path: String,
+    #[serde(skip)]
+    fsm_result: Option<Result<String, String>>, // what was built, or why it could not be
+    show_breakpoints: bool,
+    breakpoint_target: String,
+    breakpoint_condition: usize, // index into BreakCondition::NAMES
+    breakpoint_value: String,
+    #[serde(skip)]
+    breakpoint_error: Option<String>,
+
+    waveform_panel: WaveformPanel,
+    probe_tool: bool, // while active, clicking a wire, input or output attaches a probe to it
+    show_watch_list: bool,
+    show_waveform_recorder: bool,
+    vcd_path: String,
+    #[serde(skip)]
+    vcd_status: Option<String>,
+
+    show_logisim_import: bool,
+    logisim_path: String,
+    #[serde(skip)]
+    logisim_result: Option<Result<Vec<String>, String>>, // untranslated components, or why the import failed
 
     pub dragging_gate: Option<usize>,
     pub dragging_kind: Option<LogicalKind>, // kind of primitive we are dragging, if any
@@ -77,6 +161,65 @@ impl Default for MyApp {
             pan_area_rect: None,
 
             trying_save: false,
+            save_name: "New Chip".to_string(),
+
+            renaming_gate: None,
+            rename_buffer: String::new(),
+
+            show_test_runner: false,
+            test_script_path: String::new(),
+            test_result: None,
+
+            run_ticks: 100,
+            sim_status: None,
+
+            show_history: false,
+            show_design_check: false,
+            design_problems: None,
+            selected_problem: None,
+            graph_issues: None,
+            show_diagnostics: false,
+            show_optimize: false,
+            optimize_log: Vec::new(),
+            map_layout: true,
+            show_equivalence: false,
+            equivalence_chip: String::new(),
+            equivalence_result: None,
+            show_timing: false,
+            timing: None,
+            selected_path: 0,
+            show_stats: false,
+            stats: Vec::new(),
+            stats_compare: (0, 1),
+            show_kmap: false,
+            kmap_table: None,
+            kmap_output: 0,
+            kmap: None,
+            kmap_primes: Vec::new(),
+            kmap_cover: Vec::new(),
+            kmap_status: None,
+            show_fsm: false,
+            fsm: StateMachine { name: "Machine".to_string(), ..Default::default() },
+            fsm_encoding: StateEncoding::Binary,
+            fsm_pins: (String::new(), String::new()),
+            fsm_path: "machine.fsm".to_string(),
+            fsm_result: None,
+            show_breakpoints: false,
+            breakpoint_target: String::new(),
+            breakpoint_condition: 0,
+            breakpoint_value: "0x0".to_string(),
+            breakpoint_error: None,
+
+            waveform_panel: WaveformPanel::default(),
+            probe_tool: false,
+            show_watch_list: false,
+            show_waveform_recorder: false,
+            vcd_path: "waves.vcd".to_string(),
+            vcd_status: None,
+
+            show_logisim_import: false,
+            logisim_path: String::new(),
+            logisim_result: None,
 
             dragging_gate: None,
             dragging_kind: None, // No primitive kind being dragged initially
@@ -103,6 +246,7 @@ impl MyApp {
         new.event_sender = event_sender;
         new.event_receiver = event_receiver;
         new.data.load_app_data();
+        new.data.history.enabled = true; // only the editor can scrub through time
         new
     }
 
@@ -115,13 +259,6 @@ impl MyApp {
 
     
 
-    pub fn next_id() -> usize {
-        //generate new id incrementally from a static variable
-        let id = unsafe { NEXT_ID };
-        unsafe { NEXT_ID += 1 };
-        id
-    }
-
     fn update_wire_positions(&mut self, ui: &mut Ui, pan_center: Pos2) {
         //loop live data and collect all inputs and outputs into one HashMap and Wires into another
         // iterate all gates' inputs and outputs and collect their (id, positions)
@@ -167,20 +304,1395 @@ impl MyApp {
                             .unwrap_or(source_pos_moved);
                         dest_pos_moved = dest_pos_moved - pan_center.to_vec2();
                         w.set_positions(source_pos_moved, dest_pos_moved);
+                        w.set_pan_offset(pan_center.to_vec2());
+                    }
+                }
+            }
+        }
+    }
+
+    fn show_rename_window(&mut self, ctx: &Context) {
+        let Some(id) = self.renaming_gate else {
+            return;
+        };
+        let mut open = true;
+        let mut done = false;
+        egui::Window::new("Rename Gate")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let response = ui.text_edit_singleline(&mut self.rename_buffer);
+                let entered = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                if ui.button("Rename").clicked() || entered {
+                    if let Some(item) = self.data.live_data.get_mut(&id) {
+                        if let Some(gate) = item.as_any_mut().downcast_mut::<Gate>() {
+                            gate.name = self.rename_buffer.trim().to_string();
+                        }
+                    }
+                    done = true;
+                }
+            });
+        if !open || done {
+            self.renaming_gate = None;
+        }
+    }
+
+    fn show_test_runner(&mut self, ctx: &Context) {
+        let mut open = self.show_test_runner;
+        egui::Window::new("Test Script")
+            .open(&mut open)
+            .default_width(500.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(".tst file:");
+                    ui.text_edit_singleline(&mut self.test_script_path);
+                    if ui.button("Run").clicked() {
+                        self.test_result = Some(
+                            TestScript::run_file(self.test_script_path.trim(), self.data.sim.threads).map_err(|e| e.to_string()),
+                        );
+                    }
+                });
+                ui.separator();
+
+                match &self.test_result {
+                    None => {
+                        ui.label("Runs a nand2tetris test script against a saved chip");
+                    }
+                    Some(Err(e)) => {
+                        ui.colored_label(ui.visuals().error_fg_color, format!("Error: {}", e));
+                    }
+                    Some(Ok(report)) => {
+                        if let Some(mismatch) = &report.mismatch {
+                            ui.colored_label(
+                                ui.visuals().error_fg_color,
+                                format!("{}: mismatch at row {}", report.chip_name, mismatch.row),
+                            );
+                            ui.monospace(format!("expected: {}", mismatch.expected));
+                            ui.monospace(format!("actual:   {}", mismatch.actual));
+                        } else if report.compared {
+                            ui.label(format!("{}: all {} rows match", report.chip_name, report.lines.len()));
+                        } else {
+                            ui.label(format!("{}: ran without a compare file", report.chip_name));
+                        }
+                        ui.separator();
+                        egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                            for (row, line) in report.lines.iter().enumerate() {
+                                if report.mismatch.as_ref().is_some_and(|m| m.row == row) {
+                                    ui.colored_label(ui.visuals().error_fg_color, egui::RichText::new(line).monospace());
+                                } else {
+                                    ui.monospace(line);
+                                }
+                            }
+                        });
+                    }
+                }
+            });
+        self.show_test_runner = open;
+    }
+
+    fn show_waveform_recorder(&mut self, ctx: &Context) {
+        let mut open = self.show_waveform_recorder;
+        egui::Window::new("Record Waveforms")
+            .open(&mut open)
+            .default_width(350.0)
+            .show(ctx, |ui| {
+                let recorder = &mut self.data.recorder;
+                ui.horizontal(|ui| {
+                    if recorder.recording {
+                        if ui.button("Stop").clicked() {
+                            recorder.stop();
+                        }
+                    } else if ui.button("Record").clicked() {
+                        recorder.start();
+                    }
+                    ui.label(format!("{} ticks recorded", recorder.tick));
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Timescale:");
+                    egui::ComboBox::from_id_salt("timescale_magnitude")
+                        .selected_text(recorder.timescale.magnitude.to_string())
+                        .show_ui(ui, |ui| {
+                            for magnitude in [1, 10, 100] {
+                                ui.selectable_value(&mut recorder.timescale.magnitude, magnitude, magnitude.to_string());
+                            }
+                        });
+                    egui::ComboBox::from_id_salt("timescale_unit")
+                        .selected_text(recorder.timescale.unit.to_string())
+                        .show_ui(ui, |ui| {
+                            for unit in TimeUnit::ALL {
+                                ui.selectable_value(&mut recorder.timescale.unit, unit, unit.to_string());
+                            }
+                        });
+                });
+                ui.separator();
+
+                // Pick the signals to record: pins first, then every wire
+                ui.horizontal(|ui| {
+                    if ui.button("Watch all pins").clicked() {
+                        recorder.watch_all_pins(&self.data.live_data);
+                    }
+                    if ui.button("Clear").clicked() {
+                        recorder.signals.clear();
+                    }
+                });
+                let mut candidates: Vec<(bool, String, usize)> = self
+                    .data
+                    .live_data
+                    .iter()
+                    .filter_map(|(id, item)| {
+                        let kind = item.get_kind();
+                        let is_pin = kind.is_primitive_kind(PrimitiveKind::TOGGLE)
+                            || kind.is_primitive_kind(PrimitiveKind::LIGHT);
+                        if is_pin || kind.is_wire() {
+                            Some((!is_pin, signal_name(*id, &self.data.live_data)?, *id))
+                        } else {
+                            None
+                        }
+                    })
+                    .collect();
+                candidates.sort();
+                egui::ScrollArea::vertical().max_height(250.0).show(ui, |ui| {
+                    for (_, name, id) in candidates {
+                        let mut watched = recorder.is_watching(id);
+                        if ui.checkbox(&mut watched, name).changed() {
+                            if watched {
+                                let _ = recorder.watch(id, &self.data.live_data);
+                            } else {
+                                recorder.unwatch(id);
+                            }
+                        }
+                    }
+                });
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.vcd_path);
+                    if ui.button("Export VCD").clicked() {
+                        self.vcd_status = Some(match recorder.write_vcd(self.vcd_path.trim()) {
+                            Ok(()) => format!("Wrote {} signals to {}", recorder.signals.len(), self.vcd_path.trim()),
+                            Err(e) => format!("Error: {}", e),
+                        });
+                    }
+                });
+                if let Some(status) = &self.vcd_status {
+                    ui.label(status);
+                }
+            });
+        self.show_waveform_recorder = open;
+    }
+
+    /// Toolbar with run/pause, stepping and the tick rate of the simulation
+    fn show_sim_toolbar(&mut self, ctx: &Context) {
+        egui::TopBottomPanel::top("Simulation Controls").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let sim = &mut self.data.sim;
+                if sim.running {
+                    if ui.button("Pause").clicked() {
+                        sim.pause();
+                    }
+                } else if ui.button("Run").clicked() {
+                    sim.resume();
+                    self.data.break_hit = None;
+                }
+                if ui.button("Step").on_hover_text("Pause and run a single tick").clicked() {
+                    sim.step(1);
+                    self.sim_status = None;
+                }
+                if ui.button("Step Until Stable").clicked() {
+                    self.sim_status = Some(match self.data.step_until_stable() {
+                        Some(ticks) => format!("Stable after {} ticks", ticks),
+                        None if self.data.break_hit.is_some() => "Stopped at a breakpoint".to_string(),
+                        None => format!("Still changing after {} ticks", MAX_STABLE_TICKS),
+                    });
+                }
+
+                ui.separator();
+                let sim = &mut self.data.sim;
+                if ui.button("Run Ticks").on_hover_text("Run this many ticks as fast as possible").clicked() {
+                    sim.run_ticks(self.run_ticks);
+                    self.sim_status = None;
+                }
+                ui.add(egui::DragValue::new(&mut self.run_ticks).range(1..=u32::MAX as u64));
+
+                ui.separator();
+                ui.label("Ticks/sec");
+                ui.add(
+                    egui::Slider::new(&mut sim.ticks_per_second, 1.0..=1_000_000.0)
+                        .logarithmic(true)
+                        .max_decimals(1),
+                )
+                .on_hover_text(format!(
+                    "Independent of the frame rate, at most {} ticks run per frame",
+                    MAX_TICKS_PER_FRAME
+                ));
+
+                ui.separator();
+                ui.label("Threads");
+                ui.add(egui::DragValue::new(&mut sim.threads).range(1..=256)).on_hover_text(format!(
+                    "Gates are split over threads on boards with more than {} gates per thread",
+                    MIN_GATES_PER_THREAD
+                ));
+
+                ui.separator();
+                ui.label(format!("Tick {}", sim.total_ticks));
+                if sim.queued_ticks > 0 {
+                    ui.label(format!("({} queued)", sim.queued_ticks));
+                }
+                if let Some(status) = &self.sim_status {
+                    ui.label(status);
+                }
+                if let Some(report) = &self.data.oscillation.report {
+                    ui.separator();
+                    ui.colored_label(ui.visuals().error_fg_color, "Oscillation detected")
+                        .on_hover_text(report.message());
+                }
+                let errors = self.data.diagnostics.len() + self.data.edit_errors.len();
+                if errors > 0 {
+                    ui.separator();
+                    let text = egui::RichText::new(format!("{} errors", errors)).color(ui.visuals().error_fg_color);
+                    if ui.button(text).clicked() {
+                        self.show_diagnostics = true;
+                    }
+                }
+            });
+        });
+    }
+
+    /// Slider over the recorded ticks, moving it repaints the board as it was on that tick
+    fn show_history(&mut self, ctx: &Context) {
+        let mut open = self.show_history;
+        egui::Window::new("Time Travel")
+            .open(&mut open)
+            .default_width(400.0)
+            .show(ctx, |ui| {
+                let history = &mut self.data.history;
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut history.enabled, "Record history");
+                    ui.label("Keep");
+                    ui.add(egui::DragValue::new(&mut history.capacity).range(1..=1_000_000));
+                    ui.label("ticks");
+                    if ui.button("Clear").clicked() {
+                        history.clear();
+                    }
+                });
+                ui.separator();
+
+                let Some(position) = history.position() else {
+                    ui.label("No ticks recorded yet");
+                    return;
+                };
+                let last = history.snapshots.len() - 1;
+                let first_tick = history.snapshots[0].tick;
+                let shown_tick = history.snapshots[position].tick;
+
+                let mut index = position;
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(index > 0, egui::Button::new("<")).clicked() {
+                        index -= 1;
+                    }
+                    ui.spacing_mut().slider_width = 300.0;
+                    ui.add(
+                        egui::Slider::new(&mut index, 0..=last)
+                            .show_value(false)
+                            .text(format!("tick {}", shown_tick)),
+                    );
+                    if ui.add_enabled(index < last, egui::Button::new(">")).clicked() {
+                        index += 1;
+                    }
+                });
+                if index != position
+                    && let Err(e) = self.data.rewind_to(index)
+                {
+                    println!("Failed to rewind: {}", e);
+                }
+
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "Recorded ticks {} to {}",
+                        first_tick, self.data.history.snapshots[last].tick
+                    ));
+                    if self.data.history.cursor.is_some() {
+                        if ui.button("Back to Latest").clicked() {
+                            self.data.return_to_latest();
+                        }
+                        ui.label("(running or stepping from here discards the later ticks)");
+                    }
+                });
+            });
+        self.show_history = open;
+    }
+
+    /// Clickable list of design rule problems, selecting one pans the board to it
+    fn show_design_check(&mut self, ctx: &Context) {
+        let mut open = self.show_design_check;
+        egui::Window::new("Design Check")
+            .open(&mut open)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                if ui.button("Check Design").clicked() {
+                    self.design_problems = Some(self.data.check_design());
+                    self.selected_problem = None;
+                }
+                ui.separator();
+
+                let Some(problems) = &self.design_problems else {
+                    ui.label("Run the check to list problems on the board");
+                    return;
+                };
+                if problems.is_empty() {
+                    ui.label("No problems found");
+                    return;
+                }
+
+                let mut clicked = None;
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    for (index, problem) in problems.iter().enumerate() {
+                        let selected = self.selected_problem == Some(index);
+                        let text = if problem.kind.is_warning() { format!("(warning) {}", problem) } else { problem.to_string() };
+                        if ui.selectable_label(selected, text).clicked() {
+                            clicked = Some(index);
+                        }
+                    }
+                });
+
+                if let Some(index) = clicked {
+                    self.selected_problem = Some(index);
+                    if let Some(world_pos) = problems[index].position(&self.data.live_data) {
+                        // put the problem in the middle of the board
+                        let center = self.pan_area_rect.map(|r| r.center()).unwrap_or_default();
+                        self.pan_center = world_pos - center.to_vec2();
+                    }
+                }
+            });
+        self.show_design_check = open;
+        if !open {
+            self.selected_problem = None;
+        }
+    }
+
+    /// Lists connections that are only recorded on one end, and repairs them on request
+    fn show_graph_integrity(&mut self, ctx: &Context) {
+        let Some(issues) = &self.graph_issues else {
+            return;
+        };
+        let mut open = true;
+        let mut repair = false;
+        egui::Window::new("Graph Integrity")
+            .open(&mut open)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                if issues.is_empty() {
+                    ui.label("Every connection is recorded on both ends");
+                } else {
+                    ui.label(format!("{} broken references", issues.len()));
+                    egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                        for issue in issues {
+                            ui.label(issue.to_string());
+                        }
+                    });
+                    repair = ui.button("Repair").clicked();
+                }
+                ui.separator();
+                ui.checkbox(&mut self.data.auto_repair_graph, "Repair after edits instead of panicking (debug builds)");
+            });
+        if repair {
+            let fixed = self.data.repair_graph();
+            println!("Repaired {} broken references", fixed.len());
+            self.graph_issues = Some(self.data.validate_graph());
+        }
+        if !open {
+            self.graph_issues = None;
+        }
+    }
+
+    /// Lists the errors of the last tick and of editing, clicking one centers its component
+    fn show_diagnostics(&mut self, ctx: &Context) {
+        let mut open = self.show_diagnostics;
+        let mut clicked = None;
+        let mut clear = false;
+        egui::Window::new("Diagnostics")
+            .open(&mut open)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                let mut list = |ui: &mut Ui, heading: &str, errors: &[CircuitError]| {
+                    ui.strong(heading);
+                    if errors.is_empty() {
+                        ui.label("None");
+                    }
+                    for error in errors {
+                        let label = ui.selectable_label(false, error.to_string());
+                        if label.clicked() {
+                            clicked = error.component_id();
+                        }
+                    }
+                };
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    list(ui, "Last tick", &self.data.diagnostics);
+                    ui.separator();
+                    list(ui, "Editing", &self.data.edit_errors);
+                });
+                clear = ui.button("Clear").clicked();
+            });
+        if clear {
+            self.data.diagnostics.clear();
+            self.data.edit_errors.clear();
+        }
+        if let Some(world_pos) = clicked.and_then(|id| component_position(id, &self.data.live_data)) {
+            let center = self.pan_area_rect.map(|r| r.center()).unwrap_or_default();
+            self.pan_center = world_pos - center.to_vec2();
+        }
+        self.show_diagnostics = open;
+    }
+
+    /// Flattens the chips on the board into plain gates and runs the optimization passes
+    fn show_optimize(&mut self, ctx: &Context) {
+        let mut open = self.show_optimize;
+        let mut flatten = false;
+        let mut optimize = false;
+        let mut map_to = None;
+        egui::Window::new("Flatten & Optimize")
+            .open(&mut open)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                ui.label("Optimizing keeps what the LIGHTs settle to, but may change how many ticks that takes");
+                ui.horizontal(|ui| {
+                    flatten = ui.button("Flatten Chips").on_hover_text("Replace every chip with the gates inside it").clicked();
+                    optimize = ui.button("Optimize").on_hover_text("Flatten, then simplify the gates").clicked();
+                });
+                ui.horizontal(|ui| {
+                    for target in UniversalGate::ALL {
+                        if ui.button(format!("{} only", target)).on_hover_text(format!("Flatten, then build every gate from {}s", target)).clicked() {
+                            map_to = Some(target);
+                        }
+                    }
+                    ui.checkbox(&mut self.map_layout, "Lay out again");
+                });
+                ui.separator();
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    for entry in &self.optimize_log {
+                        ui.label(entry);
+                    }
+                });
+            });
+
+        if flatten || optimize || map_to.is_some() {
+            let chips = self.data.saved_chips.clone();
+            let result = self
+                .data
+                .flatten(|name| chips.iter().find(|chip| chip.name == name).cloned())
+                .and_then(|expanded| {
+                    let mut entry = format!("Expanded {} chips", expanded);
+                    if optimize {
+                        entry = format!("{}\n{}", entry, self.data.optimize()?);
+                    }
+                    if let Some(target) = map_to {
+                        entry = format!("{}\n{}", entry, self.data.map_to_universal(target, self.map_layout)?);
+                    }
+                    Ok(entry)
+                });
+            match result {
+                Ok(entry) => self.optimize_log.push(entry.trim_end().to_string()),
+                Err(e) => {
+                    self.optimize_log.push(format!("Failed: {}", e));
+                    self.data.report_edit_error(e);
+                }
+            }
+        }
+        self.show_optimize = open;
+    }
+
+    /// Proves the board computes the same as a saved chip, or shows inputs where they differ
+    fn show_equivalence(&mut self, ctx: &Context) {
+        let mut open = self.show_equivalence;
+        let mut check = false;
+        egui::Window::new("Check Equivalence")
+            .open(&mut open)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                ui.label("Compares what the LIGHTs settle to for every input, pins are matched by name");
+                ui.horizontal(|ui| {
+                    ui.label("Board vs");
+                    egui::ComboBox::from_id_salt("equivalence_chip")
+                        .selected_text(&self.equivalence_chip)
+                        .show_ui(ui, |ui| {
+                            for chip in &self.data.saved_chips {
+                                ui.selectable_value(&mut self.equivalence_chip, chip.name.clone(), &chip.name);
+                            }
+                        });
+                    check = ui.button("Check").clicked();
+                });
+                if let Some(result) = &self.equivalence_result {
+                    ui.separator();
+                    ui.label(result);
+                }
+            });
+
+        if check {
+            let chips = self.data.saved_chips.clone();
+            let result = match chips.iter().find(|chip| chip.name == self.equivalence_chip) {
+                None => "Pick a saved chip to compare against".to_string(),
+                Some(other) => {
+                    let board = self.data.to_chip("board");
+                    let lookup = |name: &str| chips.iter().find(|chip| chip.name == name).cloned();
+                    match check_chip_equivalence(&board, other, lookup) {
+                        Ok(Equivalence::Equivalent) => format!("The board and {} are equivalent", other.name),
+                        Ok(Equivalence::Different(counterexample)) => format!("They differ at {}", counterexample),
+                        Err(e) => format!("Failed: {}", e),
+                    }
+                }
+            };
+            self.equivalence_result = Some(result);
+        }
+        self.show_equivalence = open;
+    }
+
+    /// Logic depth and delay between the pins, the selected path is highlighted on the board
+    fn show_timing(&mut self, ctx: &Context) {
+        let mut open = self.show_timing;
+        egui::Window::new("Timing")
+            .open(&mut open)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                ui.label("Gate delays");
+                egui::Grid::new("gate_delays").show(ui, |ui| {
+                    for (index, kind) in DELAY_KINDS.iter().enumerate() {
+                        let mut delay = self.data.gate_delay(kind);
+                        ui.label(kind.to_string());
+                        if ui.add(egui::DragValue::new(&mut delay).speed(0.1).range(0.0..=1000.0)).changed() {
+                            self.data.gate_delays.insert(kind.clone(), delay);
+                        }
+                        if index % 2 == 1 {
+                            ui.end_row();
+                        }
+                    }
+                });
+                if ui.button("Analyze").on_hover_text("Chips on the board have to be flattened first").clicked() {
+                    self.timing = Some(self.data.timing().map_err(|e| e.to_string()));
+                    self.selected_path = 0;
+                }
+                ui.separator();
+
+                match &self.timing {
+                    None => {
+                        ui.label("Analyze to find the slowest path between each pair of pins");
+                    }
+                    Some(Err(e)) => {
+                        ui.colored_label(ui.visuals().error_fg_color, e);
+                    }
+                    Some(Ok(report)) => {
+                        let Some(critical) = report.critical() else {
+                            ui.label("No output pin depends on an input pin");
+                            return;
+                        };
+                        ui.label(format!("Critical path {}", critical));
+                        egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                            for (index, path) in report.paths.iter().enumerate() {
+                                if ui.selectable_label(self.selected_path == index, path.to_string()).clicked() {
+                                    self.selected_path = index;
+                                }
+                            }
+                        });
+                    }
+                }
+            });
+        self.show_timing = open;
+    }
+
+    /// Counts the gates and cost of the board and of every saved chip
+    fn refresh_stats(&mut self) {
+        let chips = self.data.saved_chips.clone();
+        let lookup = |name: &str| chips.iter().find(|chip| chip.name == name).cloned();
+        let board = self.data.to_chip("Board");
+        self.stats = std::iter::once(&board)
+            .chain(&chips)
+            .map(|chip| (chip.name.clone(), chip_stats(chip, lookup).map_err(|e| e.to_string())))
+            .collect();
+    }
+
+    /// Gate counts and cost per chip, and two of them side by side
+    fn show_stats(&mut self, ctx: &Context) {
+        let mut open = self.show_stats;
+        let mut refresh = false;
+        egui::Window::new("Statistics")
+            .open(&mut open)
+            .default_width(480.0)
+            .show(ctx, |ui| {
+                refresh = ui.button("Refresh").on_hover_text("Count the board and the saved chips again").clicked();
+                ui.label("Nested chips are expanded, their pins are not counted");
+                ui.separator();
+
+                egui::ScrollArea::vertical().id_salt("stats_overview").max_height(240.0).show(ui, |ui| {
+                    egui::Grid::new("stats_overview").striped(true).show(ui, |ui| {
+                        for header in ["Chip", "Gates", "NAND eq.", "Transistors", "Wires", "Max fan-out"] {
+                            ui.strong(header);
+                        }
+                        ui.end_row();
+                        for (name, stats) in &self.stats {
+                            ui.label(name);
+                            match stats {
+                                Ok(stats) => {
+                                    for value in [stats.gates, stats.nand_equivalent, stats.transistors, stats.wires, stats.max_fan_out] {
+                                        ui.label(value.to_string());
+                                    }
+                                }
+                                Err(e) => {
+                                    ui.colored_label(ui.visuals().error_fg_color, e);
+                                }
+                            }
+                            ui.end_row();
+                        }
+                    });
+                });
+                if self.stats.is_empty() {
+                    return;
+                }
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.label("Compare");
+                    for (salt, index) in [("stats_first", &mut self.stats_compare.0), ("stats_second", &mut self.stats_compare.1)] {
+                        *index = (*index).min(self.stats.len() - 1);
+                        egui::ComboBox::from_id_salt(salt).selected_text(&self.stats[*index].0).show_ui(ui, |ui| {
+                            for (option, (name, _)) in self.stats.iter().enumerate() {
+                                ui.selectable_value(index, option, name);
+                            }
+                        });
+                    }
+                });
+                let (Ok(first), Ok(second)) = (&self.stats[self.stats_compare.0].1, &self.stats[self.stats_compare.1].1) else {
+                    ui.label("Both chips need to be counted without errors to compare them");
+                    return;
+                };
+                let rows = first.compare(second);
+                egui::ScrollArea::vertical().id_salt("stats_compare").max_height(300.0).show(ui, |ui| {
+                    egui::Grid::new("stats_compare").striped(true).show(ui, |ui| {
+                        for header in ["", &self.stats[self.stats_compare.0].0, &self.stats[self.stats_compare.1].0, "Difference"] {
+                            ui.strong(header);
+                        }
+                        ui.end_row();
+                        for (label, a, b) in rows {
+                            ui.label(label);
+                            ui.label(a.to_string());
+                            ui.label(b.to_string());
+                            ui.label(format!("{:+}", b as i64 - a as i64));
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+        if refresh {
+            self.refresh_stats();
+        }
+        self.show_stats = open;
+    }
+
+    /// Takes the truth table of the board again and shows the map of the picked output
+    fn load_kmap(&mut self) {
+        self.kmap_table = Some(self.data.truth_table().map_err(|e| e.to_string()));
+        self.kmap_status = None;
+        self.select_kmap_output(self.kmap_output);
+    }
+
+    /// Makes the map of one output bit of the loaded truth table, dropping any edits
+    fn select_kmap_output(&mut self, output: usize) {
+        self.kmap = None;
+        let Some(Ok(table)) = &self.kmap_table else {
+            return;
+        };
+        let outputs = pin_bit_names(&table.outputs).len();
+        self.kmap_output = output.min(outputs.saturating_sub(1));
+        match KarnaughMap::from_truth_table(table, self.kmap_output) {
+            Ok(map) => self.kmap = Some(map),
+            Err(e) => self.kmap_table = Some(Err(e)),
+        }
+        self.minimize_kmap();
+    }
+
+    /// Finds the groupings and the minimal expression after the map changed
+    fn minimize_kmap(&mut self) {
+        let Some(map) = &self.kmap else {
+            return;
+        };
+        self.kmap_primes = map.prime_implicants();
+        self.kmap_cover = map.minimal_cover();
+    }
+
+    /// Karnaugh map of one output, cells can be edited and the result built on the board
+    fn show_kmap(&mut self, ctx: &Context) {
+        let mut open = self.show_kmap;
+        let mut load = false;
+        let mut output = None;
+        let mut clicked = None;
+        let mut replace = false;
+        egui::Window::new("Karnaugh Map")
+            .open(&mut open)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    load = ui.button("Load from Board").on_hover_text("Take the truth table of the board again, every row starts from the board's current state").clicked();
+                    if let Some(Ok(table)) = &self.kmap_table {
+                        let names = pin_bit_names(&table.outputs);
+                        let selected = names.get(self.kmap_output).cloned().unwrap_or_default();
+                        egui::ComboBox::from_id_salt("kmap_output").selected_text(selected).show_ui(ui, |ui| {
+                            for (index, name) in names.iter().enumerate() {
+                                if ui.selectable_label(self.kmap_output == index, name).clicked() {
+                                    output = Some(index);
+                                }
+                            }
+                        });
                     }
+                });
+                ui.label(format!("Boards with up to {} input bits, click a cell to cycle 0, 1 and X (don't care)", MAX_KMAP_INPUTS));
+                ui.separator();
+
+                if let Some(Err(e)) = &self.kmap_table {
+                    ui.colored_label(ui.visuals().error_fg_color, e);
+                    return;
                 }
+                let Some(map) = &self.kmap else {
+                    ui.label("Load the board to make its map");
+                    return;
+                };
+
+                let row_inputs = map.row_inputs();
+                let column_inputs = map.inputs.len() - row_inputs;
+                let colors = [
+                    Color32::from_rgb(230, 80, 80),
+                    Color32::from_rgb(80, 160, 230),
+                    Color32::from_rgb(90, 190, 90),
+                    Color32::from_rgb(220, 170, 40),
+                    Color32::from_rgb(180, 100, 220),
+                    Color32::from_rgb(40, 190, 190),
+                ];
+                let size = egui::vec2(36.0, 36.0);
+                egui::Grid::new("kmap").spacing(egui::vec2(2.0, 2.0)).show(ui, |ui| {
+                    ui.label(format!("{} \\ {}", map.inputs[..row_inputs].join(","), map.inputs[row_inputs..].join(",")));
+                    for column in 0..map.columns() {
+                        ui.strong(map.label(column, column_inputs));
+                    }
+                    ui.end_row();
+                    for row in 0..map.rows() {
+                        ui.strong(map.label(row, row_inputs));
+                        for column in 0..map.columns() {
+                            let minterm = map.minterm(row, column);
+                            let response = ui.add_sized(size, egui::Button::new(map.cells[minterm].to_string()));
+                            if response.clicked() {
+                                clicked = Some(minterm);
+                            }
+                            // one inset outline per group covering the cell, a group keeps its color everywhere
+                            for (index, _) in self.kmap_cover.iter().enumerate().filter(|(_, term)| term.covers(minterm)) {
+                                let stroke = egui::Stroke::new(2.0, colors[index % colors.len()]);
+                                let rect = response.rect.shrink(2.0 + 3.0 * (index % 4) as f32);
+                                ui.painter().rect_stroke(rect, 3.0, stroke, egui::StrokeKind::Inside);
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} =", map.output));
+                    ui.strong(map.expression(&self.kmap_cover));
+                });
+                egui::CollapsingHeader::new(format!("{} prime implicants", self.kmap_primes.len())).show(ui, |ui| {
+                    for prime in &self.kmap_primes {
+                        let used = self.kmap_cover.iter().position(|term| term == prime);
+                        match used {
+                            Some(index) => ui.colored_label(colors[index % colors.len()], map.term(prime)),
+                            None => ui.label(map.term(prime)),
+                        };
+                    }
+                });
+                replace = ui
+                    .button("Replace Logic on Board")
+                    .on_hover_text(format!("Rebuild the gates driving {} from the minimal expression", map.output))
+                    .clicked();
+                if let Some(status) = &self.kmap_status {
+                    ui.label(status);
+                }
+            });
+
+        if load {
+            self.load_kmap();
+        }
+        if let Some(output) = output {
+            self.kmap_status = None;
+            self.select_kmap_output(output);
+        }
+        if let (Some(minterm), Some(map)) = (clicked, &mut self.kmap) {
+            map.cells[minterm] = map.cells[minterm].next();
+            self.minimize_kmap();
+        }
+        if replace && let Some(map) = &self.kmap {
+            self.kmap_status = Some(match self.data.replace_with_minimized(map) {
+                Ok(added) => format!("Built {} from {} gates", map.output, added),
+                Err(e) => {
+                    let status = format!("Failed: {}", e);
+                    self.data.report_edit_error(e);
+                    status
+                }
+            });
+        }
+        self.show_kmap = open;
+    }
+
+    /// Draws the state diagram, states can be dragged around
+    fn fsm_diagram(&mut self, ui: &mut Ui) {
+        const RADIUS: f32 = 24.0;
+        let (response, painter) = ui.allocate_painter(egui::vec2(ui.available_width().max(420.0), 280.0), egui::Sense::hover());
+        let rect = response.rect;
+        painter.rect_filled(rect, 4.0, ui.visuals().extreme_bg_color);
+        let text_color = ui.visuals().text_color();
+        let stroke = egui::Stroke::new(1.5, text_color);
+        let font = egui::FontId::proportional(12.0);
+        let bits = |values: &[bool]| values.iter().map(|value| if *value { '1' } else { '0' }).collect::<String>();
+
+        for (index, state) in self.fsm.states.iter_mut().enumerate() {
+            let center = rect.min + state.position.to_vec2();
+            let drag = ui.interact(egui::Rect::from_center_size(center, egui::vec2(2.0 * RADIUS, 2.0 * RADIUS)), ui.id().with(("fsm_state", index)), egui::Sense::drag());
+            if drag.dragged() {
+                let moved = state.position + drag.drag_delta();
+                state.position = moved.clamp(Pos2::new(RADIUS, RADIUS), (rect.size() - egui::vec2(RADIUS, RADIUS)).to_pos2());
+            }
+        }
+
+        let mealy = self.fsm.kind == MachineKind::Mealy;
+        for transition in &self.fsm.transitions {
+            let (Some(from), Some(to)) = (self.fsm.states.get(transition.from), self.fsm.states.get(transition.to)) else {
+                continue;
+            };
+            let (from, to) = (rect.min + from.position.to_vec2(), rect.min + to.position.to_vec2());
+            let mut label = if transition.condition.trim().is_empty() { "1".to_string() } else { transition.condition.clone() };
+            if mealy {
+                label = format!("{} / {}", label, bits(&transition.outputs));
+            }
+            if transition.from == transition.to {
+                let loop_center = from - egui::vec2(0.0, RADIUS + 8.0);
+                painter.circle_stroke(loop_center, 12.0, stroke);
+                painter.text(loop_center - egui::vec2(0.0, 14.0), Align2::CENTER_BOTTOM, label, font.clone(), text_color);
+                continue;
+            }
+            // shifted sideways so transitions both ways between two states don't overlap
+            let direction = (to - from).normalized();
+            let side = egui::vec2(-direction.y, direction.x) * 6.0;
+            let start = from + direction * RADIUS + side;
+            let end = to - direction * RADIUS + side;
+            painter.arrow(start, end - start, stroke);
+            painter.text(start + (end - start) / 2.0 + side * 2.0, Align2::CENTER_CENTER, label, font.clone(), text_color);
+        }
+
+        for (index, state) in self.fsm.states.iter().enumerate() {
+            let center = rect.min + state.position.to_vec2();
+            painter.circle(center, RADIUS, ui.visuals().panel_fill, stroke);
+            if index == 0 {
+                painter.circle_stroke(center, RADIUS - 4.0, stroke); // the state the machine starts in
+            }
+            let name = match self.fsm.kind {
+                MachineKind::Moore => format!("{}\n{}", state.name, bits(&state.outputs)),
+                MachineKind::Mealy => state.name.clone(),
+            };
+            painter.text(center, Align2::CENTER_CENTER, name, font.clone(), text_color);
+        }
+    }
+
+    /// State diagram editor that builds the machine on the board
+    fn show_fsm(&mut self, ctx: &Context) {
+        let mut open = self.show_fsm;
+        let mut build = false;
+        let mut save = false;
+        let mut load = false;
+        egui::Window::new("State Machine Designer")
+            .open(&mut open)
+            .default_width(480.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Name");
+                    ui.text_edit_singleline(&mut self.fsm.name);
+                    ui.radio_value(&mut self.fsm.kind, MachineKind::Moore, "Moore").on_hover_text("Outputs depend on the state");
+                    ui.radio_value(&mut self.fsm.kind, MachineKind::Mealy, "Mealy").on_hover_text("Outputs depend on the state and the inputs");
+                });
+                let split = |text: &str| text.split(',').map(|name| name.trim().to_string()).filter(|name| !name.is_empty()).collect();
+                egui::Grid::new("fsm_pins").show(ui, |ui| {
+                    ui.label("Inputs");
+                    if ui.text_edit_singleline(&mut self.fsm_pins.0).on_hover_text("Names separated by commas").changed() {
+                        self.fsm.inputs = split(&self.fsm_pins.0);
+                    }
+                    ui.end_row();
+                    ui.label("Outputs");
+                    if ui.text_edit_singleline(&mut self.fsm_pins.1).on_hover_text("Names separated by commas").changed() {
+                        self.fsm.outputs = split(&self.fsm_pins.1);
+                    }
+                    ui.end_row();
+                });
+                self.fsm.fit_outputs();
+                self.fsm_diagram(ui);
+
+                let mealy = self.fsm.kind == MachineKind::Mealy;
+                egui::CollapsingHeader::new("States").default_open(true).show(ui, |ui| {
+                    let mut remove = None;
+                    egui::Grid::new("fsm_states").striped(true).show(ui, |ui| {
+                        ui.strong("Name");
+                        if !mealy {
+                            for output in &self.fsm.outputs {
+                                ui.strong(output);
+                            }
+                        }
+                        ui.end_row();
+                        for (index, state) in self.fsm.states.iter_mut().enumerate() {
+                            ui.add(egui::TextEdit::singleline(&mut state.name).desired_width(80.0));
+                            if !mealy {
+                                for value in &mut state.outputs {
+                                    ui.checkbox(value, "");
+                                }
+                            }
+                            if ui.button("Remove").clicked() {
+                                remove = Some(index);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                    if let Some(index) = remove {
+                        self.fsm.remove_state(index);
+                    }
+                    if ui.add_enabled(self.fsm.states.len() < MAX_FSM_STATES, egui::Button::new("Add State")).clicked() {
+                        let index = self.fsm.states.len();
+                        let position = Pos2::new(50.0 + (index % 6) as f32 * 70.0, 50.0 + (index / 6) as f32 * 90.0);
+                        let outputs = vec![false; self.fsm.outputs.len()];
+                        self.fsm.states.push(FsmState { name: format!("S{}", index), outputs, position });
+                    }
+                    ui.label("The first state is the one the machine starts in");
+                });
+
+                egui::CollapsingHeader::new("Transitions").default_open(true).show(ui, |ui| {
+                    let mut remove = None;
+                    let names: Vec<String> = self.fsm.states.iter().map(|state| state.name.clone()).collect();
+                    egui::Grid::new("fsm_transitions").striped(true).show(ui, |ui| {
+                        for header in ["From", "To", "Condition"] {
+                            ui.strong(header);
+                        }
+                        if mealy {
+                            for output in &self.fsm.outputs {
+                                ui.strong(output);
+                            }
+                        }
+                        ui.end_row();
+                        for (index, transition) in self.fsm.transitions.iter_mut().enumerate() {
+                            for (salt, state) in [("from", &mut transition.from), ("to", &mut transition.to)] {
+                                egui::ComboBox::from_id_salt(("fsm_transition", salt, index))
+                                    .selected_text(names.get(*state).cloned().unwrap_or_default())
+                                    .width(70.0)
+                                    .show_ui(ui, |ui| {
+                                        for (option, name) in names.iter().enumerate() {
+                                            ui.selectable_value(state, option, name);
+                                        }
+                                    });
+                            }
+                            ui.add(egui::TextEdit::singleline(&mut transition.condition).hint_text("always").desired_width(120.0));
+                            if mealy {
+                                for value in &mut transition.outputs {
+                                    ui.checkbox(value, "");
+                                }
+                            }
+                            if ui.button("Remove").clicked() {
+                                remove = Some(index);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                    if let Some(index) = remove {
+                        self.fsm.transitions.remove(index);
+                    }
+                    if ui.add_enabled(!self.fsm.states.is_empty(), egui::Button::new("Add Transition")).clicked() {
+                        let outputs = vec![false; self.fsm.outputs.len()];
+                        self.fsm.transitions.push(Transition { from: 0, to: 0, condition: String::new(), outputs });
+                    }
+                    ui.label("Conditions use the inputs with ! & ^ | and parentheses, the first one that holds is taken");
+                });
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.label("Encoding");
+                    egui::ComboBox::from_id_salt("fsm_encoding").selected_text(self.fsm_encoding.to_string()).show_ui(ui, |ui| {
+                        for encoding in StateEncoding::ALL {
+                            ui.selectable_value(&mut self.fsm_encoding, encoding, encoding.to_string());
+                        }
+                    });
+                    build = ui.button("Build on Board").on_hover_text(format!("Flip-flops switch when {} goes from 0 to 1", FSM_CLOCK_PIN)).clicked();
+                });
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.fsm_path);
+                    save = ui.button("Save").clicked();
+                    load = ui.button("Load").clicked();
+                });
+                match &self.fsm_result {
+                    Some(Ok(report)) => {
+                        egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                            ui.monospace(report);
+                        });
+                    }
+                    Some(Err(e)) => {
+                        ui.colored_label(ui.visuals().error_fg_color, e);
+                    }
+                    None => {}
+                }
+            });
+
+        if build {
+            // below and to the right of the middle of the visible board
+            let center = self.pan_area_rect.map(|r| r.center()).unwrap_or_default() + self.pan_center.to_vec2();
+            let origin = center - egui::vec2(3.0 * LAYOUT_SPACING.x, 2.0 * LAYOUT_SPACING.y);
+            self.fsm_result = Some(match self.data.build_state_machine(&self.fsm, self.fsm_encoding, origin) {
+                Ok(report) => Ok(report.to_string()),
+                Err(e) => Err(e.to_string()),
+            });
+        }
+        if save {
+            self.fsm_result = Some(match self.fsm.save_to_file(&self.fsm_path) {
+                Ok(()) => Ok(format!("Saved {}", self.fsm_path)),
+                Err(e) => Err(format!("Could not save {}: {}", self.fsm_path, e)),
+            });
+        }
+        if load {
+            match StateMachine::load_from_file(&self.fsm_path) {
+                Ok(machine) => {
+                    self.fsm_pins = (machine.inputs.join(", "), machine.outputs.join(", "));
+                    self.fsm = machine;
+                    self.fsm_result = None;
+                }
+                Err(e) => self.fsm_result = Some(Err(format!("Could not load {}: {}", self.fsm_path, e))),
+            }
+        }
+        self.show_fsm = open;
+    }
+
+    /// Outlines the gates and wires of the path picked in the timing window
+    fn draw_timing_path(&self, ui: &Ui, pan_center: Pos2) {
+        if !self.show_timing {
+            return;
+        }
+        let Some(Ok(report)) = &self.timing else {
+            return;
+        };
+        let Some(path) = report.paths.get(self.selected_path) else {
+            return;
+        };
+        let color = ui.visuals().hyperlink_color;
+        for id in &path.ids {
+            self.draw_highlight(ui.painter(), pan_center, *id, color);
+        }
+        if let Some(pos) = path.ids.last().and_then(|id| component_position(*id, &self.data.live_data)) {
+            ui.painter().text(
+                pos - pan_center.to_vec2() - egui::vec2(0.0, 40.0),
+                Align2::CENTER_BOTTOM,
+                format!("delay {}", path.delay),
+                egui::FontId::proportional(13.0),
+                color,
+            );
+        }
+    }
+
+    /// Marks every component with an error and shows the error next to it
+    fn draw_diagnostics(&self, ui: &Ui, pan_center: Pos2) {
+        let color = ui.visuals().error_fg_color;
+        for error in self.data.diagnostics.iter().chain(&self.data.edit_errors) {
+            let Some(id) = error.component_id() else {
+                continue;
+            };
+            let Some(pos) = component_position(id, &self.data.live_data) else {
+                continue;
+            };
+            self.draw_highlight(ui.painter(), pan_center, id, color);
+            let anchor = pos - pan_center.to_vec2() + egui::vec2(-50.0, 34.0);
+            ui.painter().text(anchor, egui::Align2::LEFT_TOP, error.to_string(), egui::FontId::proportional(11.0), color);
+        }
+    }
+
+    /// Side panel listing the breakpoints, with a form to add new ones
+    fn show_breakpoints(&mut self, ctx: &Context) {
+        if !self.show_breakpoints {
+            return;
+        }
+        egui::SidePanel::right("Breakpoints").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.heading("Breakpoints");
+                if ui.button("Close").clicked() {
+                    self.show_breakpoints = false;
+                }
+            });
+            ui.separator();
+
+            ui.label("Pause when");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.breakpoint_target)
+                    .hint_text("pin, bus or id")
+                    .desired_width(120.0),
+            );
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_salt("breakpoint_condition")
+                    .selected_text(BreakCondition::NAMES[self.breakpoint_condition])
+                    .show_ui(ui, |ui| {
+                        for (index, name) in BreakCondition::NAMES.iter().enumerate() {
+                            ui.selectable_value(&mut self.breakpoint_condition, index, *name);
+                        }
+                    });
+                if self.breakpoint_condition == 3 {
+                    ui.add(egui::TextEdit::singleline(&mut self.breakpoint_value).desired_width(60.0));
+                }
+            });
+            if ui.button("Add").clicked() {
+                let condition = match self.breakpoint_condition {
+                    0 => Ok(BreakCondition::Rises),
+                    1 => Ok(BreakCondition::Falls),
+                    2 => Ok(BreakCondition::Changes),
+                    _ => parse_break_value(&self.breakpoint_value).map(BreakCondition::Equals),
+                };
+                self.breakpoint_error = condition
+                    .and_then(|condition| self.data.add_breakpoint(&self.breakpoint_target, condition))
+                    .err()
+                    .map(|e| e.to_string());
+            }
+            if let Some(error) = &self.breakpoint_error {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+            ui.separator();
+
+            if let Some(hit) = &self.data.break_hit {
+                let description = self.data.breakpoints[hit.index].describe(&self.data.live_data);
+                ui.colored_label(ui.visuals().warn_fg_color, format!("Hit on tick {}: {}", hit.tick, description));
+                if ui.button("Continue").clicked() {
+                    self.data.break_hit = None;
+                    self.data.sim.resume();
+                }
+                ui.separator();
+            }
+
+            let mut removed = None;
+            let hit_index = self.data.break_hit.as_ref().map(|hit| hit.index);
+            for (index, breakpoint) in self.data.breakpoints.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut breakpoint.enabled, "");
+                    let text = format!("{} ({} hits)", breakpoint.describe(&self.data.live_data), breakpoint.hits);
+                    if hit_index == Some(index) {
+                        ui.colored_label(ui.visuals().warn_fg_color, text);
+                    } else {
+                        ui.label(text);
+                    }
+                    if ui.small_button("x").clicked() {
+                        removed = Some(index);
+                    }
+                });
+            }
+            if let Some(index) = removed {
+                self.data.remove_breakpoint(index);
+            }
+        });
+    }
+
+    /// Outlines a gate, wire, input or output on the board
+    fn draw_highlight(&self, painter: &egui::Painter, pan_center: Pos2, id: usize, color: Color32) {
+        let Some(item) = self.data.live_data.get(&id) else {
+            return;
+        };
+        let stroke = egui::Stroke::new(3.0, color);
+        let any = item.as_any();
+        if let Some(gate) = any.downcast_ref::<Gate>() {
+            let center = gate.position.to_pos2() - pan_center.to_vec2();
+            let rect = egui::Rect::from_center_size(center, egui::vec2(100.0, 60.0)).expand(4.0);
+            painter.rect_stroke(rect, 6.0, stroke, egui::StrokeKind::Outside);
+        } else if let Some(wire) = any.downcast_ref::<Wire>() {
+            painter.line(wire.line.points(), egui::Stroke::new(5.0, color.gamma_multiply(0.7)));
+        } else {
+            let pos = if let Some(input) = any.downcast_ref::<Input>() {
+                input.get_position(&self.data.live_data)
+            } else if let Some(output) = any.downcast_ref::<Output>() {
+                output.get_position(&self.data.live_data)
+            } else {
+                return;
+            };
+            if let Ok(pos) = pos {
+                painter.circle_stroke(pos - pan_center.to_vec2(), 10.0, stroke);
             }
         }
     }
 
+    fn show_watch_list(&mut self, ctx: &Context) {
+        let mut open = self.show_watch_list;
+        egui::Window::new("Watch List")
+            .open(&mut open)
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                ui.checkbox(&mut self.probe_tool, "Probe tool (click a wire, input or output)");
+                ui.separator();
+                if self.data.probes.is_empty() {
+                    ui.label("No probes attached");
+                }
+
+                let mut removed = None;
+                let mut renamed = None;
+                for (index, probe) in self.data.probes.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        let mut name = probe.name.clone();
+                        if ui.add(egui::TextEdit::singleline(&mut name).desired_width(120.0)).changed() {
+                            renamed = Some((index, name));
+                        }
+                        match probe.value(&self.data.live_data) {
+                            Some(value) => {
+                                let key = if value { HI_SIGNAL_COLOR } else { LO_SIGNAL_COLOR };
+                                let color = self.data.color_values.get(key).cloned().unwrap_or(ui.visuals().text_color());
+                                ui.colored_label(color, if value { "1" } else { "0" });
+                            }
+                            None => {
+                                ui.label("(removed)");
+                            }
+                        }
+                        if ui.button("Remove").clicked() {
+                            removed = Some(index);
+                        }
+                    });
+                }
+                if let Some((index, name)) = renamed {
+                    self.data.rename_probe(index, &name);
+                }
+                if let Some(index) = removed {
+                    self.data.remove_probe(index);
+                }
+            });
+        self.show_watch_list = open;
+    }
+
+    /// The wire whose line passes closest to a screen position, ignoring the ends where inputs and outputs sit
+    fn wire_at(&self, pos: Pos2) -> Option<usize> {
+        const HIT_DISTANCE: f32 = 6.0;
+        const END_MARGIN: f32 = 12.0;
+        self.data
+            .live_data
+            .values()
+            .filter_map(|item| item.as_any().downcast_ref::<Wire>())
+            .filter(|wire| wire.connected)
+            .filter_map(|wire| {
+                let points = wire.line.points();
+                let near_end = [points[0], points[points.len() - 1]].iter().any(|p| p.distance(pos) < END_MARGIN);
+                if near_end {
+                    return None;
+                }
+                let distance = points
+                    .windows(2)
+                    .map(|seg| {
+                        let (a, b) = (seg[0], seg[1]);
+                        let ab = b - a;
+                        let t = if ab.length_sq() > 0.0 { ((pos - a).dot(ab) / ab.length_sq()).clamp(0.0, 1.0) } else { 0.0 };
+                        (a + ab * t).distance(pos)
+                    })
+                    .fold(f32::MAX, f32::min);
+                (distance < HIT_DISTANCE).then_some((distance, wire.id))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, id)| id)
+    }
+
+    /// Draws a small floating badge with the live value next to every probed wire, input and output
+    fn draw_probe_badges(&self, ui: &Ui, pan_center: Pos2) {
+        for probe in &self.data.probes {
+            let Some(item) = self.data.live_data.get(&probe.target_id) else {
+                continue;
+            };
+            let any = item.as_any();
+            let anchor = if let Some(wire) = any.downcast_ref::<Wire>() {
+                let points = wire.line.points();
+                points[points.len() / 2].lerp(points[(points.len() - 1) / 2], 0.5)
+            } else if let Some(input) = any.downcast_ref::<Input>() {
+                match input.get_position(&self.data.live_data) {
+                    Ok(pos) => pos - pan_center.to_vec2(),
+                    Err(_) => continue,
+                }
+            } else if let Some(output) = any.downcast_ref::<Output>() {
+                match output.get_position(&self.data.live_data) {
+                    Ok(pos) => pos - pan_center.to_vec2(),
+                    Err(_) => continue,
+                }
+            } else {
+                continue;
+            };
+
+            let value = probe.value(&self.data.live_data).unwrap_or(false);
+            let key = if value { HI_SIGNAL_COLOR } else { LO_SIGNAL_COLOR };
+            let color = self.data.color_values.get(key).cloned().unwrap_or(egui::Color32::GRAY);
+            let painter = ui.painter();
+            let galley = painter.layout_no_wrap(
+                format!("{}: {}", probe.name, value as u8),
+                egui::FontId::monospace(11.0),
+                egui::Color32::WHITE,
+            );
+            let rect = egui::Rect::from_min_size(anchor + egui::vec2(6.0, -22.0), galley.size() + egui::vec2(8.0, 4.0));
+            painter.line_segment([anchor, rect.left_bottom()], egui::Stroke::new(1.0, color));
+            painter.rect_filled(rect, 4.0, color.gamma_multiply(0.85));
+            painter.galley(rect.min + egui::vec2(4.0, 2.0), galley, egui::Color32::WHITE);
+        }
+    }
+
+    fn show_logisim_import(&mut self, ctx: &Context) {
+        let mut open = self.show_logisim_import;
+        egui::Window::new("Import Logisim Circuit")
+            .open(&mut open)
+            .default_width(500.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(".circ file:");
+                    ui.text_edit_singleline(&mut self.logisim_path);
+                    if ui.button("Import").clicked() {
+                        let result = LogisimImport::from_file(self.logisim_path.trim()).and_then(|import| {
+                            self.data.apply_logisim_import(&import)?;
+                            Ok(import.untranslated.iter().map(|u| u.to_string()).collect())
+                        });
+                        self.logisim_result = Some(result.map_err(|e| e.to_string()));
+                        self.pan_center = Pos2::new(0.0, 0.0);
+                        self.dragging_gate = None;
+                        self.holding_wire = None;
+                    }
+                });
+                ui.separator();
+
+                match &self.logisim_result {
+                    None => {
+                        ui.label("Replaces the board with the main circuit, every circuit is added to the saved chips");
+                    }
+                    Some(Err(e)) => {
+                        ui.colored_label(ui.visuals().error_fg_color, format!("Error: {}", e));
+                    }
+                    Some(Ok(untranslated)) if untranslated.is_empty() => {
+                        ui.label("Every component was translated");
+                    }
+                    Some(Ok(untranslated)) => {
+                        ui.label(format!("{} components could not be translated:", untranslated.len()));
+                        egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                            for line in untranslated {
+                                ui.label(line);
+                            }
+                        });
+                    }
+                }
+            });
+        self.show_logisim_import = open;
+    }
+
     fn apply_ui_events(&mut self) {
         // Process UI events from the receiver
         let mut queued_removal_id: Option<usize> = None;
+        let mut edited = false;
 
         if let Ok(clicked) = self.event_receiver.try_recv() {
             // an output was clicked, so we want to create a wire if we are not currently holding a wire
             //lookup the type of the clicked IO by its id in the live_data map
             match clicked {
+                UiEvent::ClickedIO(id, _, true) | UiEvent::ClickedWire(id, _, true) if self.probe_tool => {
+                    match self.data.add_probe(id) {
+                        Ok(_) => self.show_watch_list = true,
+                        Err(e) => println!("Could not attach probe: {}", e),
+                    }
+                }
                 UiEvent::ClickedGate(id, _, true) => {
                     // If a gate was clicked, toggle its state
                     if let Some(item) = self.data.live_data.get_mut(&id) {
@@ -190,18 +1702,20 @@ impl MyApp {
                         }
                     }
                 }
-                UiEvent::ClickedGate(_id, _, false) => {
-                    // If a gate was clicked with a secondary click, show its context menu
-                    // if let Some(item) = self.live_data.get_mut(&id) {
-                    //     if let Some(gate) = item.as_any_mut().downcast_mut::<Gate>() {
-                    //         println!("Right-clicked on Gate: {:?}", id);
-                    //         gate.show_context_menu();
-                    //     }
-                    // }
+                UiEvent::ClickedGate(id, _, false) => {
+                    // If a gate was clicked with a secondary click, open the rename window
+                    // naming TOGGLE and LIGHT gates is how a chip's pins get their names
+                    if let Some(gate) = self.data.live_data.get(&id).and_then(|item| item.as_any().downcast_ref::<Gate>()) {
+                        self.rename_buffer = gate.name.clone();
+                        self.renaming_gate = Some(id);
+                    }
                 }
                 UiEvent::ClickedIO(id, pos, true) => {
                     //primary click on an IO item
-                    let kind = self.data.live_data.get(&id).unwrap().get_kind();
+                    let Some(kind) = self.data.live_data.get(&id).map(|item| item.get_kind()) else {
+                        self.data.report_edit_error(CircuitError::InvalidDowncast { id, expected: "Input or Output" });
+                        return;
+                    };
 
                     match kind {
                         LogicalKind::IO(IOKind::Input) => {
@@ -210,11 +1724,8 @@ impl MyApp {
                             let in_wire_id: Option<usize> = self.data
                                 .live_data
                                 .get(&id)
-                                .unwrap()
-                                .as_any()
-                                .downcast_ref::<Input>()
-                                .unwrap()
-                                .source_wire_id;
+                                .and_then(|item| item.as_any().downcast_ref::<Input>())
+                                .and_then(|input| input.source_wire_id);
 
                             if in_wire_id.is_none() {
                                 //if this input has no wire connected
@@ -222,26 +1733,26 @@ impl MyApp {
                                     //connect the wire to the input
                                     println!("Connecting wire to input: {:?}", kind);
                                     self.holding_wire = None;
-                                    let wire = self.data
+                                    let Some(wire) = self.data
                                         .live_data
                                         .get_mut(&wire_id)
-                                        .unwrap()
-                                        .as_any_mut()
-                                        .downcast_mut::<Wire>()
-                                        .unwrap();
+                                        .and_then(|item| item.as_any_mut().downcast_mut::<Wire>())
+                                    else {
+                                        self.data.report_edit_error(CircuitError::InvalidDowncast { id: wire_id, expected: "Wire" });
+                                        return;
+                                    };
                                     //set wire's p2 to the clicked position
                                     // and set the wires destination to the input's id
                                     // wire.set_p2(clicked_io.screen_position);
                                     wire.dest = Some(id);
                                     wire.connected = true; // mark the wire as connected
 
-                                    self.data.live_data
+                                    if let Some(input) = self.data.live_data
                                         .get_mut(&id)
-                                        .unwrap()
-                                        .as_any_mut()
-                                        .downcast_mut::<Input>()
-                                        .unwrap()
-                                        .source_wire_id = Some(wire_id);
+                                        .and_then(|item| item.as_any_mut().downcast_mut::<Input>())
+                                    {
+                                        input.source_wire_id = Some(wire_id);
+                                    }
                                 } else {
                                     //this input has a wire do nothing, as inputs may only
                                     println!(
@@ -257,46 +1768,41 @@ impl MyApp {
                                 // println!("Creating wire from clicked IO: {:?}", id);
                                 let new_wire = Wire::from_io(id, pos);
 
-                                self.data.live_data
+                                if let Some(output) = self.data.live_data
                                     .get_mut(&id)
-                                    .unwrap()
-                                    .as_any_mut()
-                                    .downcast_mut::<Output>()
-                                    .unwrap()
-                                    .out_wire_ids
-                                    .push(new_wire.id);
+                                    .and_then(|item| item.as_any_mut().downcast_mut::<Output>())
+                                {
+                                    output.out_wire_ids.push(new_wire.id);
+                                }
 
                                 self.holding_wire = Some(new_wire.id);
                                 self.data.live_data.insert(new_wire.id, new_wire);
-                            } else {
-                                let wire_id = self.holding_wire.take().unwrap();
-                                //reconnect the wire using this output as the new source
-                                let old_output = self
-                                    .data.live_data
+                            } else if let Some(wire_id) = self.holding_wire {
+                                //reconnect the held wire using this output as the new source
+                                println!("Reconnecting wire to output: {:?}", id);
+                                let old_source = self.data
+                                    .live_data
                                     .get_mut(&wire_id)
-                                    .unwrap()
-                                    .as_any_mut()
-                                    .downcast_mut::<Output>()
-                                    .unwrap();
-
-                                old_output.out_wire_ids.retain(|&x| x != wire_id); // Remove the wire from the old output
-
-                                if let Some(wire_id) = self.holding_wire {
-                                    println!("Reconnecting wire to output: {:?}", id);
-                                    let wire = self.data
-                                        .live_data
-                                        .get_mut(&wire_id)
-                                        .unwrap()
-                                        .as_any_mut()
-                                        .downcast_mut::<Wire>()
-                                        .unwrap();
-
-                                    wire.source_id = id; // Set the new source to the clicked output
+                                    .and_then(|item| item.as_any_mut().downcast_mut::<Wire>())
+                                    .map(|wire| std::mem::replace(&mut wire.source_id, id)); // Set the new source to the clicked output
+
+                                if let Some(old_output) = old_source
+                                    .and_then(|old| self.data.live_data.get_mut(&old))
+                                    .and_then(|item| item.as_any_mut().downcast_mut::<Output>())
+                                {
+                                    old_output.out_wire_ids.retain(|&x| x != wire_id); // Remove the wire from the old output
+                                }
+                                if let Some(output) = self.data
+                                    .live_data
+                                    .get_mut(&id)
+                                    .and_then(|item| item.as_any_mut().downcast_mut::<Output>())
+                                {
+                                    output.out_wire_ids.push(wire_id);
                                 }
                             }
                         }
                         _ => {
-                            println!("Clicked on unknown IO type: {:?}", id);
+                            self.data.report_edit_error(CircuitError::InvalidDowncast { id, expected: "Input or Output" });
                         }
                     }
                 }
@@ -342,27 +1848,15 @@ impl MyApp {
                 }
                 UiEvent::ClickedWire(_, _, _) => {}
             }
+            edited = true;
         }
         // If we have a queued removal id, remove the item from live
         if let Some(wire_id) = queued_removal_id {
-            if let Some(mut item) = self.data.live_data.remove(&wire_id) {
-                if let Some(wire) = item.as_any_mut().downcast_mut::<Wire>() {
-                    // Remove the wire from any connected inputs
-                    if let Some(input_id) = wire.dest {
-                        if let Some(input) = self.data.live_data.get_mut(&input_id) {
-                            if let Some(input) = input.as_any_mut().downcast_mut::<Input>() {
-                                input.source_wire_id = None; // Disconnect the wire from the input
-                            }
-                        }
-                    }
-                    // Remove the wire from any connected outputs
-                    if let Some(item2) = self.data.live_data.get_mut(&wire.source_id) {
-                        if let Some(output) = item2.as_any_mut().downcast_mut::<Output>() {
-                            output.out_wire_ids.retain(|&x| x != wire_id); // Remove the wire from the output
-                        }
-                    }
-                }
-            }
+            self.data.remove_wire(wire_id);
+        }
+
+        if edited {
+            self.data.check_graph_after_edit();
         }
     }
 
@@ -379,6 +1873,8 @@ impl eframe::App for MyApp {
 
     /// Called each time the UI needs repainting, which may be many times per second.
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // everything created on the board this frame takes its id from the board
+        let _ids = self.data.ids.enter();
 
         // Put your widgets into a `SidePanel`, `TopBottomPanel`, `CentralPanel`, `Window` or `Area`.
         let theme = self.data.available_themes.get("fennec.css").unwrap().clone();
@@ -391,6 +1887,22 @@ impl eframe::App for MyApp {
         self.apply_ui_events();
         self.data.update_logicals(ctx);
 
+        self.show_rename_window(ctx);
+        self.show_test_runner(ctx);
+        self.show_logisim_import(ctx);
+        self.show_waveform_recorder(ctx);
+        self.show_watch_list(ctx);
+        self.show_history(ctx);
+        self.show_design_check(ctx);
+        self.show_graph_integrity(ctx);
+        self.show_diagnostics(ctx);
+        self.show_optimize(ctx);
+        self.show_equivalence(ctx);
+        self.show_timing(ctx);
+        self.show_stats(ctx);
+        self.show_kmap(ctx);
+        self.show_fsm(ctx);
+
         egui::SidePanel::left("Tools").show(ctx, |ui| {
             ui.set_max_width(SIDE_PANEL_WIDTH);
             ui.vertical_centered_justified(|ui| {
@@ -406,6 +1918,7 @@ impl eframe::App for MyApp {
                 //two "columns" first 80% th width for chip name, second 20% width for trash icon
                 let mut idx = 0;
                 let mut queue_rem: Option<usize> = None;
+                let mut queue_paste: Option<usize> = None;
 
                 for g in &self.data.saved_chips {
                     ui.horizontal(|ui| {
@@ -416,18 +1929,30 @@ impl eframe::App for MyApp {
                             // self.open_gate(idx)
                             println!("TODO! Open gate for editing")
                         }
+                        if ui.button("Paste").on_hover_text("Place a copy of this chip's gates on the board").clicked() {
+                            queue_paste = Some(idx);
+                        }
                         if ui.button("Delete").clicked() {
                             // Remove the gate from the saved gates
                             queue_rem = Some(idx);
-                            self.data.save_to_chip_file();
                         }
                         idx += 1;
                     });
                 }
                 
+                if let Some(idx) = queue_paste {
+                    // paste into the middle of the visible board
+                    let center = self.pan_area_rect.map(|r| r.center()).unwrap_or_default();
+                    let chip = self.data.saved_chips[idx].clone();
+                    let placed = self.data.place_chip(&chip, center + self.pan_center.to_vec2());
+                    println!("Pasted {} ({} items)", chip.name, placed.len());
+                }
+
                 // Remove the gate from the saved gates
-                if queue_rem.is_some() {
-                    self.data.saved_chips.remove(idx - 1);
+                if let Some(idx) = queue_rem
+                    && let Err(e) = self.data.delete_chip_file(idx)
+                {
+                    println!("Failed to delete chip: {}", e);
                 };
             })
         });
@@ -439,11 +1964,70 @@ impl eframe::App for MyApp {
 
             egui::MenuBar::new().ui(ui, |ui| {
                 ui.menu_button("File", |ui| {
+                    if ui.button("Run Test Script").clicked() {
+                        self.show_test_runner = true;
+                    }
+                    if ui.button("Import Logisim").clicked() {
+                        self.show_logisim_import = true;
+                    }
+                    if ui.button("State Machine Designer").clicked() {
+                        self.show_fsm = true;
+                    }
                     if ui.button("Quit").clicked() {
                         ctx.send_viewport_cmd(egui::ViewportCommand::Close);
                     }
                 });
 
+                ui.menu_button("Simulation", |ui| {
+                    if ui.button("Record Waveforms").clicked() {
+                        self.show_waveform_recorder = true;
+                    }
+                    if ui.button("Waveform Panel").clicked() {
+                        self.waveform_panel.open = true;
+                    }
+                    ui.checkbox(&mut self.probe_tool, "Probe Tool");
+                    if ui.button("Time Travel").clicked() {
+                        self.show_history = true;
+                    }
+                    if ui.button("Breakpoints").clicked() {
+                        self.show_breakpoints = true;
+                    }
+                    if ui.button("Watch List").clicked() {
+                        self.show_watch_list = true;
+                    }
+                });
+
+                ui.menu_button("Analysis", |ui| {
+                    if ui.button("Check Design").clicked() {
+                        self.show_design_check = true;
+                        self.design_problems = Some(self.data.check_design());
+                        self.selected_problem = None;
+                    }
+                    if ui.button("Check Graph Integrity").clicked() {
+                        self.graph_issues = Some(self.data.validate_graph());
+                    }
+                    if ui.button("Diagnostics").clicked() {
+                        self.show_diagnostics = true;
+                    }
+                    if ui.button("Flatten & Optimize").clicked() {
+                        self.show_optimize = true;
+                    }
+                    if ui.button("Check Equivalence").clicked() {
+                        self.show_equivalence = true;
+                    }
+                    if ui.button("Timing").clicked() {
+                        self.show_timing = true;
+                    }
+                    if ui.button("Statistics").clicked() {
+                        self.show_stats = true;
+                        self.refresh_stats();
+                    }
+                    if ui.button("Karnaugh Map").clicked() {
+                        self.show_kmap = true;
+                        self.load_kmap();
+                    }
+                });
+
                 let mut next_themes = Vec::new();
                 let themes = self.data.available_themes.clone();
 
@@ -466,6 +2050,8 @@ impl eframe::App for MyApp {
             });
         });
 
+        self.show_sim_toolbar(ctx);
+
         egui::TopBottomPanel::top("Primitive Library").show(ctx, |ui| {
             ui.set_max_height(150.);
             ui.horizontal(|ui| {
@@ -474,6 +2060,7 @@ impl eframe::App for MyApp {
             });
 
             // println!("Primitive gates: {:?}", self.primitive_gates);
+            let mut placed_gate = false;
             ui.horizontal_centered(|ui| {
                 dnd(ui, "Primitive").show(
                     &mut self.data.prim_templates.iter(),
@@ -503,6 +2090,7 @@ impl eframe::App for MyApp {
                                                     gate.id,
                                                     Box::new(gate),
                                                 );
+                                                placed_gate = true;
 
                                                 println!(
                                                     "Added new gate: {:?}",
@@ -521,6 +2109,9 @@ impl eframe::App for MyApp {
                     },
                 );
             });
+            if placed_gate {
+                self.data.check_graph_after_edit();
+            }
             ui.horizontal(|ui| {
                 ui.set_min_height(15.);
                 //create a left-justified button to clear the board
@@ -556,20 +2147,24 @@ impl eframe::App for MyApp {
                                 .close_behavior(close_behavior)
                                 .show(|ui| { 
                                     ui.label("Are you sure?\n This will save a chip and clear the current board.");
+                                    ui.text_edit_singleline(&mut self.save_name);
                                     if ui.button("Yes").clicked() {
                                         self.trying_save = false;
+                                        // Save the board as a chip before clearing it, and keep it if that failed
+                                        if let Err(e) = self.data.save_to_chip_file(self.save_name.trim()) {
+                                            println!("Failed to save chip: {}", e);
+                                            return;
+                                        }
+
                                         // Clear the live data
                                         self.data.live_data.clear();
+                                        self.data.clear_probes();
+                                        self.data.clear_breakpoints();
+                                        self.data.history.clear();
                                         self.pan_center = Pos2::new(0.0, 0.0);
                                         self.dragging_gate = None;
                                         self.holding_wire = None;
-
-
-                                        let new_chip = ChipDefenition::from_live_data(
-                                            &self.data.live_data,
-                                            "New Chip".to_string()
-                                        );
-
+                                        self.data.check_graph_after_edit();
 
                                         println!("Cleared the board");
                                     }
@@ -600,9 +2195,13 @@ impl eframe::App for MyApp {
                             if ui.button("Clear Board").clicked() {
                                 // Clear the live data
                                 self.data.live_data.clear();
+                                self.data.clear_probes();
+                                self.data.clear_breakpoints();
+                                self.data.history.clear();
                                 self.pan_center = Pos2::new(0.0, 0.0);
                                 self.dragging_gate = None;
                                 self.holding_wire = None;
+                                self.data.check_graph_after_edit();
                                 println!("Cleared the board");
                             }
                         });
@@ -611,6 +2210,11 @@ impl eframe::App for MyApp {
             });
         });
 
+        self.show_breakpoints(ctx);
+
+        // must come before the CentralPanel so a docked panel takes its space from the board
+        self.waveform_panel.show(ctx, &mut self.data.recorder, &self.data.color_values);
+
         egui::CentralPanel::default().show(ctx, |ui| {
             let mut new_pan_center = self.pan_center; // copy the value (Pos2 is Copy)
 
@@ -677,7 +2281,7 @@ impl eframe::App for MyApp {
                                             self.dragging_gate = None;
                                         }
 
-                                        if response.clicked() {
+                                        if response.clicked() || response.secondary_clicked() {
                                             //if the item was a gate (should always be), set the clicked_gate to this id
                                             self.event_sender
                                                 .try_send(UiEvent::ClickedGate(
@@ -685,7 +2289,7 @@ impl eframe::App for MyApp {
                                                     ui.ctx().input(|i| {
                                                         i.pointer.hover_pos().unwrap_or_default()
                                                     }),
-                                                    true,
+                                                    response.clicked(),
                                                 ))
                                                 .unwrap();
                                         }
@@ -728,6 +2332,58 @@ impl eframe::App for MyApp {
                     }
                     self.pan_center = pan_center; // update AFTER the widget runs
 
+                    self.draw_probe_badges(ui, pan_center);
+                    if let Some(report) = &self.data.oscillation.report {
+                        let color = ui.visuals().error_fg_color;
+                        for feedback_loop in &report.loops {
+                            for id in feedback_loop.ids() {
+                                self.draw_highlight(ui.painter(), pan_center, id, color);
+                            }
+                            if let Some(gate) = feedback_loop
+                                .gates
+                                .first()
+                                .and_then(|id| self.data.live_data.get(id))
+                                .and_then(|item| item.get_position().ok())
+                            {
+                                ui.painter().text(
+                                    gate - pan_center.to_vec2() - egui::vec2(0.0, 40.0),
+                                    Align2::CENTER_BOTTOM,
+                                    format!("Oscillating loop of {} gate(s), never settles", feedback_loop.gates.len()),
+                                    egui::FontId::proportional(13.0),
+                                    color,
+                                );
+                            }
+                        }
+                    }
+                    if let Some(problem) = self
+                        .selected_problem
+                        .and_then(|index| self.design_problems.as_ref()?.get(index))
+                    {
+                        let color = ui.visuals().warn_fg_color;
+                        for id in &problem.ids {
+                            self.draw_highlight(ui.painter(), pan_center, *id, color);
+                        }
+                    }
+                    if let Some(hit) = &self.data.break_hit {
+                        let color = ui.visuals().warn_fg_color;
+                        for id in &hit.component_ids {
+                            self.draw_highlight(ui.painter(), pan_center, *id, color);
+                        }
+                    }
+                    self.draw_timing_path(ui, pan_center);
+                    self.draw_diagnostics(ui, pan_center);
+
+                    // Wires have no widget to click on, so the probe tool hit tests them itself
+                    if self.probe_tool
+                        && ui.input(|i| i.pointer.primary_clicked())
+                        && let Some(pointer_pos) = ui.ctx().pointer_interact_pos()
+                        && let Some(wire_id) = self.wire_at(pointer_pos)
+                    {
+                        self.event_sender
+                            .try_send(UiEvent::ClickedWire(wire_id, pointer_pos, true))
+                            .unwrap_or_else(|_| println!("Failed to send ClickedWire event"));
+                    }
+
                     if let Some(gate_index)= self.dragging_gate {
                         // If dragging a gate, update its position
                         if let Some(pointer_pos) = ui.ctx().pointer_hover_pos() {
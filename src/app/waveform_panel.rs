@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+
+use eframe::egui::{
+    self, Align2, Color32, Context, FontId, Rect, Sense, Stroke, Ui, pos2, vec2,
+};
+
+use super::{HI_SIGNAL_COLOR, LO_SIGNAL_COLOR, SignalGroup, SignalRecorder};
+
+const LABEL_WIDTH: f32 = 140.0;
+const ROW_HEIGHT: f32 = 28.0;
+const TRACE_MARGIN: f32 = 5.0;
+
+/// How close (in pixels) a click has to be to an edge for a cursor to snap onto it
+const SNAP_DISTANCE: f32 = 8.0;
+
+const MIN_ZOOM: f32 = 0.05;
+const MAX_ZOOM: f32 = 80.0;
+
+/// A logic analyzer view of the signals held by the SignalRecorder
+/// Docked at the bottom of the window by default, or floating in its own window
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct WaveformPanel {
+    pub open: bool,
+    pub docked: bool,
+    pub zoom: f32,       // pixels per tick
+    pub following: bool, // keep the newest tick in view, false while paused
+    pub view_start: f32, // first visible tick while paused
+    pub cursor_a: Option<u64>,
+    pub cursor_b: Option<u64>,
+}
+
+impl Default for WaveformPanel {
+    fn default() -> Self {
+        WaveformPanel {
+            open: false,
+            docked: true,
+            zoom: 8.0,
+            following: true,
+            view_start: 0.0,
+            cursor_a: None,
+            cursor_b: None,
+        }
+    }
+}
+
+impl WaveformPanel {
+    /// Has to be called before the CentralPanel is shown so the docked panel gets its space
+    pub fn show(&mut self, ctx: &Context, recorder: &mut SignalRecorder, colors: &HashMap<String, Color32>) {
+        if !self.open {
+            return;
+        }
+        if self.docked {
+            egui::TopBottomPanel::bottom("Waveforms")
+                .resizable(true)
+                .default_height(220.0)
+                .show(ctx, |ui| self.ui(ui, recorder, colors));
+        } else {
+            let mut open = self.open;
+            egui::Window::new("Waveforms")
+                .open(&mut open)
+                .default_size([700.0, 250.0])
+                .show(ctx, |ui| self.ui(ui, recorder, colors));
+            self.open = open;
+        }
+    }
+
+    fn ui(&mut self, ui: &mut Ui, recorder: &mut SignalRecorder, colors: &HashMap<String, Color32>) {
+        ui.horizontal(|ui| {
+            if recorder.recording {
+                if ui.button("Stop").clicked() {
+                    recorder.stop();
+                }
+            } else if ui.button("Record").clicked() {
+                recorder.start();
+                self.following = true;
+                self.cursor_a = None;
+                self.cursor_b = None;
+            }
+
+            let label = if self.following { "Pause" } else { "Live" };
+            if ui.button(label).clicked() {
+                self.following = !self.following;
+            }
+
+            ui.label("Zoom:");
+            ui.add(egui::Slider::new(&mut self.zoom, MIN_ZOOM..=MAX_ZOOM).logarithmic(true));
+
+            let dock_label = if self.docked { "Undock" } else { "Dock" };
+            if ui.button(dock_label).clicked() {
+                self.docked = !self.docked;
+            }
+            if self.docked && ui.button("Close").clicked() {
+                self.open = false;
+            }
+
+            ui.separator();
+            ui.label(self.cursor_text(recorder));
+        });
+        ui.separator();
+
+        let groups = recorder.groups();
+        if groups.is_empty() {
+            ui.label("No signals are being recorded, pick some in Simulation > Record Waveforms");
+            return;
+        }
+
+        let width = ui.available_width();
+        let visible_ticks = ((width - LABEL_WIDTH) / self.zoom).max(1.0);
+        let latest = recorder.tick as f32;
+        if self.following {
+            self.view_start = (latest - visible_ticks).max(0.0);
+        }
+
+        egui::ScrollArea::vertical().auto_shrink([false, true]).show(ui, |ui| {
+            let height = groups.len() as f32 * ROW_HEIGHT;
+            let (rect, response) = ui.allocate_exact_size(vec2(width, height), Sense::click_and_drag());
+            let wave_rect = Rect::from_min_max(pos2(rect.left() + LABEL_WIDTH, rect.top()), rect.max);
+
+            // Dragging or scrolling sideways pauses the view and moves through the history,
+            // ctrl + scroll zooms around the pointer
+            if response.dragged() {
+                self.following = false;
+                self.view_start -= response.drag_delta().x / self.zoom;
+            }
+            if response.hovered() {
+                let (scroll, zoom_delta, pointer) =
+                    ui.input(|i| (i.smooth_scroll_delta, i.zoom_delta(), i.pointer.hover_pos()));
+                if zoom_delta != 1.0 {
+                    let anchor = pointer.map(|p| self.tick_at(p.x, wave_rect)).unwrap_or(self.view_start);
+                    self.zoom = (self.zoom * zoom_delta).clamp(MIN_ZOOM, MAX_ZOOM);
+                    if let Some(p) = pointer {
+                        self.view_start = anchor - (p.x - wave_rect.left()) / self.zoom;
+                    }
+                    self.following = false;
+                } else if scroll.x != 0.0 {
+                    self.view_start -= scroll.x / self.zoom;
+                    self.following = false;
+                }
+            }
+            self.view_start = self.view_start.clamp(0.0, (latest - 1.0).max(0.0));
+
+            if let Some(pointer) = response.interact_pointer_pos() {
+                let tick = self.snap(pointer.x, wave_rect, &groups, recorder);
+                if response.clicked() {
+                    self.cursor_a = Some(tick);
+                } else if response.secondary_clicked() {
+                    self.cursor_b = Some(tick);
+                }
+            }
+
+            let painter = ui.painter_at(rect);
+            let text_color = ui.visuals().text_color();
+            let grid_color = ui.visuals().widgets.noninteractive.bg_stroke.color;
+            let high = colors.get(HI_SIGNAL_COLOR).cloned().unwrap_or(Color32::GREEN);
+            let low = colors.get(LO_SIGNAL_COLOR).cloned().unwrap_or(Color32::RED);
+
+            for (row, group) in groups.iter().enumerate() {
+                let top = rect.top() + row as f32 * ROW_HEIGHT;
+                let row_rect = Rect::from_min_max(pos2(wave_rect.left(), top), pos2(wave_rect.right(), top + ROW_HEIGHT));
+                painter.line_segment(
+                    [pos2(rect.left(), row_rect.bottom()), row_rect.right_bottom()],
+                    Stroke::new(1.0, grid_color),
+                );
+
+                let value = group.value_at(&recorder.signals, self.cursor_a.unwrap_or(recorder.tick.saturating_sub(1)));
+                let label = if group.is_bus() {
+                    format!("{} = 0x{:X}", group.name, value)
+                } else {
+                    format!("{} = {}", group.name, value)
+                };
+                painter.text(
+                    pos2(rect.left() + 4.0, row_rect.center().y),
+                    Align2::LEFT_CENTER,
+                    label,
+                    FontId::monospace(12.0),
+                    text_color,
+                );
+
+                if group.is_bus() {
+                    self.draw_bus(&painter, row_rect, group, recorder, text_color);
+                } else {
+                    self.draw_bit(&painter, row_rect, group, recorder, high, low);
+                }
+            }
+
+            // Cursors
+            for (cursor, color) in [(self.cursor_a, Color32::YELLOW), (self.cursor_b, Color32::LIGHT_BLUE)] {
+                if let Some(tick) = cursor {
+                    let x = self.x_at(tick as f32, wave_rect);
+                    if wave_rect.x_range().contains(x) {
+                        painter.line_segment([pos2(x, rect.top()), pos2(x, rect.bottom())], Stroke::new(1.0, color));
+                    }
+                }
+            }
+        });
+    }
+
+    /// A single bit drawn as a square wave between a low and a high level
+    fn draw_bit(
+        &self,
+        painter: &egui::Painter,
+        row_rect: Rect,
+        group: &SignalGroup,
+        recorder: &SignalRecorder,
+        high: Color32,
+        low: Color32,
+    ) {
+        let signal = &recorder.signals[group.bits[0].1];
+        let y_of = |v: bool| if v { row_rect.top() + TRACE_MARGIN } else { row_rect.bottom() - TRACE_MARGIN };
+        let (first, last) = self.visible_range(row_rect);
+
+        let mut value = signal.value_at(first);
+        let mut x = row_rect.left().max(self.x_at(0.0, row_rect));
+        for (tick, next) in signal.changes.iter().filter(|(t, _)| *t > first && *t <= last) {
+            let edge_x = self.x_at(*tick as f32, row_rect);
+            let color = if value { high } else { low };
+            painter.line_segment([pos2(x, y_of(value)), pos2(edge_x, y_of(value))], Stroke::new(2.0, color));
+            painter.line_segment([pos2(edge_x, y_of(value)), pos2(edge_x, y_of(*next))], Stroke::new(1.0, color));
+            value = *next;
+            x = edge_x;
+        }
+        let end_x = self.x_at(recorder.tick as f32, row_rect).min(row_rect.right());
+        if end_x > x {
+            let color = if value { high } else { low };
+            painter.line_segment([pos2(x, y_of(value)), pos2(end_x, y_of(value))], Stroke::new(2.0, color));
+        }
+    }
+
+    /// A bus drawn as a band that crosses over at every change, labeled with its value in hex
+    fn draw_bus(&self, painter: &egui::Painter, row_rect: Rect, group: &SignalGroup, recorder: &SignalRecorder, color: Color32) {
+        let (first, last) = self.visible_range(row_rect);
+        let top = row_rect.top() + TRACE_MARGIN;
+        let bottom = row_rect.bottom() - TRACE_MARGIN;
+        let mid = row_rect.center().y;
+        let stroke = Stroke::new(1.0, color);
+
+        let mut bounds = vec![first];
+        bounds.extend(group.edges(&recorder.signals).into_iter().filter(|t| *t > first && *t <= last));
+        bounds.push(recorder.tick.min(last + 1));
+
+        for pair in bounds.windows(2) {
+            let x0 = self.x_at(pair[0] as f32, row_rect).max(row_rect.left());
+            let x1 = self.x_at(pair[1] as f32, row_rect).min(row_rect.right());
+            if x1 <= x0 {
+                continue;
+            }
+            let slant = 3.0f32.min((x1 - x0) / 2.0);
+            painter.add(egui::Shape::closed_line(
+                vec![
+                    pos2(x0, mid),
+                    pos2(x0 + slant, top),
+                    pos2(x1 - slant, top),
+                    pos2(x1, mid),
+                    pos2(x1 - slant, bottom),
+                    pos2(x0 + slant, bottom),
+                ],
+                stroke,
+            ));
+            let text = format!("{:X}", group.value_at(&recorder.signals, pair[0]));
+            if (x1 - x0) > text.len() as f32 * 8.0 + 2.0 * slant {
+                painter.text(pos2((x0 + x1) / 2.0, mid), Align2::CENTER_CENTER, text, FontId::monospace(11.0), color);
+            }
+        }
+    }
+
+    /// Cursor positions and the distance between them, in ticks and in the recorder's timescale
+    fn cursor_text(&self, recorder: &SignalRecorder) -> String {
+        match (self.cursor_a, self.cursor_b) {
+            (Some(a), Some(b)) => {
+                let delta = a.abs_diff(b);
+                format!(
+                    "A: {}  B: {}  Δ: {} ticks ({}{})",
+                    a,
+                    b,
+                    delta,
+                    delta * recorder.timescale.magnitude as u64,
+                    recorder.timescale.unit
+                )
+            }
+            (Some(a), None) => format!("A: {}  (right click to place B)", a),
+            (None, Some(b)) => format!("B: {}  (click to place A)", b),
+            (None, None) => "Click to place cursor A, right click for B".to_string(),
+        }
+    }
+
+    /// The closest edge of any signal within SNAP_DISTANCE of x, or the tick under x
+    fn snap(&self, x: f32, wave_rect: Rect, groups: &[SignalGroup], recorder: &SignalRecorder) -> u64 {
+        let tick = self.tick_at(x, wave_rect).max(0.0);
+        let nearest = groups
+            .iter()
+            .flat_map(|g| g.edges(&recorder.signals))
+            .min_by(|a, b| (*a as f32 - tick).abs().total_cmp(&(*b as f32 - tick).abs()));
+        match nearest {
+            Some(edge) if (edge as f32 - tick).abs() * self.zoom <= SNAP_DISTANCE => edge,
+            _ => tick.round() as u64,
+        }
+    }
+
+    fn visible_range(&self, rect: Rect) -> (u64, u64) {
+        let first = self.view_start.floor().max(0.0) as u64;
+        let last = self.tick_at(rect.right(), rect).ceil().max(0.0) as u64;
+        (first, last)
+    }
+
+    fn x_at(&self, tick: f32, rect: Rect) -> f32 {
+        rect.left() + (tick - self.view_start) * self.zoom
+    }
+
+    fn tick_at(&self, x: f32, rect: Rect) -> f32 {
+        self.view_start + (x - rect.left()) / self.zoom
+    }
+}
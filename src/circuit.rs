@@ -0,0 +1,164 @@
+use std::error::Error;
+use std::path::Path;
+
+use crate::*;
+
+/// Gates placed without a position are laid out in rows of this many
+const AUTO_LAYOUT_COLUMNS: usize = 8;
+
+/// A circuit built and run from code, without the editor
+/// Ids returned here are the same ids the board uses, so `data()` can be used for anything not covered
+pub struct Circuit {
+    data: Data,
+    placed: usize,
+}
+
+impl Default for Circuit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Circuit {
+    pub fn new() -> Self {
+        Circuit {
+            data: Data::new(),
+            placed: 0,
+        }
+    }
+
+    pub fn from_chip(chip: &ChipDefenition) -> Self {
+        let mut circuit = Circuit::new();
+        circuit.data.load_chip(chip);
+        circuit.placed = circuit.gate_count();
+        circuit
+    }
+
+    /// Loads a .chip file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Ok(Circuit::from_chip(&ChipDefenition::load_from_file(path)?))
+    }
+
+    pub fn to_chip(&self, name: &str) -> ChipDefenition {
+        ChipDefenition::from_live_data(&self.data.live_data, name.to_string())
+    }
+
+    /// Saves the circuit as a .chip file that the editor can open
+    pub fn save<P: AsRef<Path>>(&self, path: P, name: &str) -> Result<(), Box<dyn Error>> {
+        self.to_chip(name).save_to_file(path)
+    }
+
+    /// Adds a gate, placed next to the previous ones
+    /// Returns the id of the gate
+    pub fn add_gate(&mut self, kind: PrimitiveKind) -> usize {
+        let column = self.placed % AUTO_LAYOUT_COLUMNS;
+        let row = self.placed / AUTO_LAYOUT_COLUMNS;
+        self.add_gate_at(kind, Pos2::new(150.0 * column as f32, 100.0 * row as f32))
+    }
+
+    pub fn add_gate_at(&mut self, kind: PrimitiveKind, pos: Pos2) -> usize {
+        self.placed += 1;
+        self.data.add_primitive(kind, pos)
+    }
+
+    /// Adds a named input pin (a TOGGLE), use `name[0]`, `name[1]`, ... for the bits of a bus
+    pub fn add_input(&mut self, name: &str) -> usize {
+        let id = self.add_gate(PrimitiveKind::TOGGLE);
+        self.rename(id, name);
+        id
+    }
+
+    /// Adds a named output pin (a LIGHT)
+    pub fn add_output(&mut self, name: &str) -> usize {
+        let id = self.add_gate(PrimitiveKind::LIGHT);
+        self.rename(id, name);
+        id
+    }
+
+    pub fn rename(&mut self, gate_id: usize, name: &str) {
+        if let Some(gate) = self
+            .data
+            .live_data
+            .get_mut(&gate_id)
+            .and_then(|item| item.as_any_mut().downcast_mut::<Gate>())
+        {
+            gate.name = name.to_string();
+        }
+    }
+
+    /// Ids of a gate's inputs, in order
+    pub fn inputs_of(&self, gate_id: usize) -> Vec<usize> {
+        self.data.gate_inputs(gate_id)
+    }
+
+    /// Ids of a gate's outputs, in order
+    pub fn outputs_of(&self, gate_id: usize) -> Vec<usize> {
+        self.data.gate_outputs(gate_id)
+    }
+
+    /// Connects an output to an input by their ids, returns the id of the wire
+    pub fn connect(&mut self, output_id: usize, input_id: usize) -> Result<usize, Box<dyn Error>> {
+        self.data.connect(output_id, input_id)
+    }
+
+    /// Connects the first output of one gate to the given input of another, returns the id of the wire
+    pub fn wire(&mut self, from_gate: usize, to_gate: usize, input_index: usize) -> Result<usize, Box<dyn Error>> {
+        let output = *self
+            .outputs_of(from_gate)
+            .first()
+            .ok_or_else(|| InvalidOperationError(format!("Gate {} has no outputs", from_gate)))?;
+        let input = *self
+            .inputs_of(to_gate)
+            .get(input_index)
+            .ok_or_else(|| InvalidOperationError(format!("Gate {} has no input {}", to_gate, input_index)))?;
+        self.connect(output, input)
+    }
+
+    /// Sets an input pin or bus, the new value spreads through the circuit as it steps
+    pub fn set_input(&mut self, name: &str, value: u64) -> Result<(), Box<dyn Error>> {
+        self.data.set_pin(name, value)
+    }
+
+    /// Reads an output (or input) pin or bus
+    pub fn read_output(&self, name: &str) -> Result<u64, Box<dyn Error>> {
+        self.data.read_pin(name)
+    }
+
+    /// Runs one tick, every gate moves its inputs one gate further
+    pub fn step(&mut self) {
+        self.data.tick();
+    }
+
+    pub fn step_n(&mut self, ticks: usize) {
+        for _ in 0..ticks {
+            self.data.tick();
+        }
+    }
+
+    /// Steps until nothing changes anymore, returns false if the circuit oscillates
+    pub fn settle(&mut self) -> bool {
+        let limit = self.data.settle_limit();
+        self.data.settle(limit)
+    }
+
+    /// Number of ticks run so far
+    pub fn ticks(&self) -> u64 {
+        self.data.sim.total_ticks
+    }
+
+    pub fn gate_count(&self) -> usize {
+        self.data
+            .live_data
+            .values()
+            .filter(|item| item.as_any().is::<Gate>())
+            .count()
+    }
+
+    pub fn data(&self) -> &Data {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut Data {
+        &mut self.data
+    }
+}
@@ -0,0 +1,532 @@
+//! Headless subcommands, so boards and chips can be used from scripts and CI
+//!
+//! Exit codes: 0 on success, 1 when a check fails (test mismatch, design problems,
+//! board that never settles, chips that are not equivalent), 2 when the command could not run at all.
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use serde_json::json;
+
+use gates::*;
+
+const USAGE: &str = "usage:
+  Gates                                          start the editor
+  Gates test <file.tst>... [--threads N]         run nand2tetris test scripts
+  Gates sim <chip> [--ticks N] [--set PIN=VALUE]... [--threads N] [--optimize] [--json]
+  Gates truth-table <chip> [--threads N] [--optimize] [--json]
+  Gates kmap <chip> [--optimize] [--json]
+  Gates export <chip> --format verilog [--optimize] [--output FILE]
+  Gates flatten <chip> [--optimize] [--output FILE] [--json]
+  Gates equiv <chip> <other chip> [--json]
+  Gates timing <chip> [--delay KIND=DELAY]... [--optimize] [--json]
+  Gates stats <chip> [<other chip>] [--json]
+  Gates map <chip> --to nand|nor [--optimize] [--output FILE] [--json]
+  Gates fsm <file.fsm> [--encoding binary|gray|one-hot] [--output FILE] [--json]
+  Gates check <chip> [--json]
+
+<chip> is a .chip file, or the name of a chip in the saves folder.
+Chips used inside it are found next to it or in the saves folder, and are
+flattened into plain gates before simulating or exporting.
+Values can be written as 42, 0x2A or 0b101010.
+Every truth-table (and kmap) row starts from the board as loaded, not from the
+row before it, so boards with latches show what each row does from that state.
+Gate delays default to 1 for every logic gate, e.g. --delay XOR=2 --delay NOT=0.5;
+the pins of flattened chips become BUFFERs that add to the timing unless --optimize removes them.";
+
+/// Runs a subcommand if one was given, returning the process exit code
+/// Returns None when there is no subcommand and the editor should start
+pub fn run(args: &[String]) -> Option<i32> {
+    let (command, rest) = args.split_first()?;
+    let result = match command.as_str() {
+        "test" => return Some(run_test_scripts(rest)),
+        "sim" => sim(rest),
+        "truth-table" => truth_table(rest),
+        "kmap" => kmap(rest),
+        "export" => export(rest),
+        "flatten" => flatten(rest),
+        "equiv" => equiv(rest),
+        "timing" => timing(rest),
+        "stats" => stats(rest),
+        "map" => map(rest),
+        "fsm" => fsm(rest),
+        "check" => check(rest),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(0)
+        }
+        _ => Err(format!("unknown command {}\n{}", command, USAGE).into()),
+    };
+    Some(result.unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        2
+    }))
+}
+
+/// Command line options shared by the subcommands
+#[derive(Default)]
+struct Options {
+    file: Option<String>,
+    other: Option<String>, // second chip, for the commands that compare two
+    json: bool,
+    optimize: bool,
+    ticks: Option<usize>,
+    threads: Option<usize>,
+    sets: Vec<(String, u64)>,
+    delays: Vec<(PrimitiveKind, f64)>,
+    format: Option<String>,
+    target: Option<UniversalGate>, // what map rewrites the gates into
+    encoding: Option<StateEncoding>,
+    output: Option<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, Box<dyn Error>> {
+        Options::parse_chips(args, 1)
+    }
+
+    /// Parses the options of a command that takes `chips` chips
+    fn parse_chips(args: &[String], chips: usize) -> Result<Self, Box<dyn Error>> {
+        let mut options = Options::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("{} expects a value", arg))
+            };
+            match arg.as_str() {
+                "--json" => options.json = true,
+                "--optimize" => options.optimize = true,
+                "--ticks" => {
+                    let ticks = value()?;
+                    options.ticks = Some(ticks.parse().map_err(|_| format!("invalid tick count {}", ticks))?);
+                }
+                "--threads" => {
+                    let threads = value()?;
+                    match threads.parse() {
+                        Ok(threads) if threads > 0 => options.threads = Some(threads),
+                        _ => return Err(format!("invalid thread count {}", threads).into()),
+                    }
+                }
+                "--set" => {
+                    let set = value()?;
+                    let (pin, pin_value) = set
+                        .split_once('=')
+                        .ok_or_else(|| format!("--set expects PIN=VALUE, got {}", set))?;
+                    options.sets.push((pin.to_string(), parse_break_value(pin_value)?));
+                }
+                "--delay" => options.delays.push(parse_gate_delay(&value()?)?),
+                "--format" => options.format = Some(value()?),
+                "--to" => options.target = Some(UniversalGate::parse(&value()?)?),
+                "--encoding" => options.encoding = Some(StateEncoding::parse(&value()?)?),
+                "--output" | "-o" => options.output = Some(value()?),
+                _ if arg.starts_with('-') => return Err(format!("unknown option {}\n{}", arg, USAGE).into()),
+                _ if options.file.is_none() => options.file = Some(arg.clone()),
+                _ if chips > 1 && options.other.is_none() => options.other = Some(arg.clone()),
+                _ => return Err(format!("unexpected argument {}", arg).into()),
+            }
+        }
+        Ok(options)
+    }
+
+    /// The file of the chip named on the command line
+    fn path(&self) -> Result<PathBuf, Box<dyn Error>> {
+        let file = self.file.as_ref().ok_or_else(|| format!("no chip given\n{}", USAGE))?;
+        Ok(chip_file(file))
+    }
+
+    /// Loads the chip named on the command line onto a fresh board
+    fn load(&self) -> Result<(Data, String), Box<dyn Error>> {
+        let chip = load_chip_file(&self.path()?)?;
+        let mut data = Data::new();
+        data.load_chip(&chip);
+        if let Some(threads) = self.threads {
+            data.sim.threads = threads;
+        }
+        data.gate_delays.extend(self.delays.iter().cloned());
+        Ok((data, chip.name))
+    }
+
+    /// Loads the chip and flattens the chips inside it, optimizing the result if asked to
+    /// The optimization report goes to stderr so it never mixes with the output
+    fn load_flat(&self) -> Result<(Data, String), Box<dyn Error>> {
+        let (mut data, name) = self.load()?;
+        data.flatten(self.chip_lookup()?)?;
+        if self.optimize {
+            eprint!("{}", data.optimize()?);
+        }
+        Ok((data, name))
+    }
+
+    /// Finds chips by name next to the loaded chip, then in the saves folder
+    fn chip_lookup(&self) -> Result<impl FnMut(&str) -> Option<ChipDefenition>, Box<dyn Error>> {
+        let dir = self.path()?.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(move |name: &str| {
+            [dir.join(format!("{}.chip", name)), Data::chip_path(name)]
+                .iter()
+                .find_map(|path| ChipDefenition::load_from_file(path).ok())
+        })
+    }
+}
+
+/// A chip on the command line is a path, or the name of a chip in the saves folder
+fn chip_file(file: &str) -> PathBuf {
+    if Path::new(file).exists() || file.ends_with(".chip") {
+        Path::new(file).to_path_buf()
+    } else {
+        Data::chip_path(file)
+    }
+}
+
+fn load_chip_file(path: &Path) -> Result<ChipDefenition, Box<dyn Error>> {
+    Ok(ChipDefenition::load_from_file(path).map_err(|e| format!("could not load {}: {}", path.display(), e))?)
+}
+
+/// Sets the given pins, runs the board and prints every pin
+fn sim(args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let options = Options::parse(args)?;
+    let (mut data, name) = options.load_flat()?;
+    for (pin, value) in &options.sets {
+        data.set_pin(pin, *value)?;
+    }
+
+    let stable = match options.ticks {
+        Some(ticks) => {
+            for _ in 0..ticks {
+                data.tick();
+            }
+            None
+        }
+        None => Some(data.settle(data.settle_limit())),
+    };
+
+    let (inputs, outputs) = data.pin_groups();
+    let read = |pins: &[PinGroup]| -> Result<Vec<(String, u64)>, Box<dyn Error>> {
+        pins.iter().map(|pin| Ok((pin.name.clone(), data.read_pin(&pin.name)?))).collect()
+    };
+    let inputs = read(&inputs)?;
+    let outputs = read(&outputs)?;
+
+    if options.json {
+        let object = |values: &[(String, u64)]| {
+            values
+                .iter()
+                .map(|(name, value)| (name.clone(), json!(value)))
+                .collect::<serde_json::Map<_, _>>()
+        };
+        let report = json!({
+            "chip": name,
+            "ticks": data.sim.total_ticks,
+            "stable": stable,
+            "inputs": object(&inputs),
+            "outputs": object(&outputs),
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!("{} after {} ticks", name, data.sim.total_ticks);
+        for (name, value) in inputs.iter().chain(outputs.iter()) {
+            println!("  {} = {}", name, value);
+        }
+        if stable == Some(false) {
+            println!("  (still changing, the board never settled)");
+        }
+    }
+    Ok(if stable == Some(false) { 1 } else { 0 })
+}
+
+fn truth_table(args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let options = Options::parse(args)?;
+    let (mut data, name) = options.load_flat()?;
+    let table = data.truth_table()?;
+    if options.json {
+        let report = json!({ "chip": name, "table": table });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", table.to_text());
+    }
+    Ok(if table.rows.iter().all(|row| row.stable) { 0 } else { 1 })
+}
+
+/// Karnaugh map and minimal sum of products of every output bit
+fn kmap(args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let options = Options::parse(args)?;
+    let (mut data, name) = options.load_flat()?;
+    let table = data.truth_table()?;
+    let maps = (0..pin_bit_names(&table.outputs).len())
+        .map(|output| KarnaughMap::from_truth_table(&table, output))
+        .collect::<Result<Vec<KarnaughMap>, String>>()?;
+    if options.json {
+        let maps: Vec<_> = maps
+            .iter()
+            .map(|map| json!({ "map": map, "expression": map.expression(&map.minimal_cover()) }))
+            .collect();
+        let report = json!({ "chip": name, "maps": maps });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for map in &maps {
+            print!("{}", map);
+            println!("{} = {}\n", map.output, map.expression(&map.minimal_cover()));
+        }
+    }
+    Ok(0)
+}
+
+fn export(args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let options = Options::parse(args)?;
+    let (data, name) = options.load_flat()?;
+    let text = match options.format.as_deref() {
+        Some("verilog") | Some("v") => data.to_verilog(&name)?,
+        Some(format) => return Err(format!("unknown export format {}, supported: verilog", format).into()),
+        None => return Err("export needs --format verilog".into()),
+    };
+    match &options.output {
+        Some(path) => {
+            std::fs::write(path, text)?;
+            eprintln!("Wrote {}", path);
+        }
+        None => print!("{}", text),
+    }
+    Ok(0)
+}
+
+/// Flattens a chip into plain gates, optionally optimizes it, and reports the gate counts
+fn flatten(args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let options = Options::parse(args)?;
+    let (mut data, name) = options.load()?;
+    let count_gates = |data: &Data| data.live_data.values().filter(|item| item.as_any().is::<Gate>()).count();
+    let gates_before = count_gates(&data);
+    let expanded = data.flatten(options.chip_lookup()?)?;
+    let gates_flat = count_gates(&data);
+    let report = if options.optimize { Some(data.optimize()?) } else { None };
+
+    if options.json {
+        let report = json!({
+            "chip": name,
+            "chips_expanded": expanded,
+            "gates_before": gates_before,
+            "gates_flattened": gates_flat,
+            "optimize": report,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!("{}: expanded {} chips, {} -> {} gates", name, expanded, gates_before, gates_flat);
+        if let Some(report) = &report {
+            print!("{}", report);
+        }
+    }
+    if let Some(path) = &options.output {
+        ChipDefenition::from_live_data(&data.live_data, name).save_to_file(path)?;
+        eprintln!("Wrote {}", path);
+    }
+    Ok(0)
+}
+
+/// Proves two chips compute the same outputs, fails with a counterexample if they don't
+fn equiv(args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let options = Options::parse_chips(args, 2)?;
+    let other = options.other.as_ref().ok_or_else(|| format!("equiv needs two chips\n{}", USAGE))?;
+    let a = load_chip_file(&options.path()?)?;
+    let b = load_chip_file(&chip_file(other))?;
+    let result = check_chip_equivalence(&a, &b, options.chip_lookup()?)?;
+
+    if options.json {
+        let report = match &result {
+            Equivalence::Equivalent => json!({ "first": a.name, "second": b.name, "equivalent": true }),
+            Equivalence::Different(counterexample) => json!({
+                "first": a.name,
+                "second": b.name,
+                "equivalent": false,
+                "counterexample": counterexample,
+            }),
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        match &result {
+            Equivalence::Equivalent => println!("{} and {} are equivalent", a.name, b.name),
+            Equivalence::Different(counterexample) => {
+                println!("{} and {} differ", a.name, b.name);
+                println!("  {}", counterexample);
+            }
+        }
+    }
+    Ok(match result {
+        Equivalence::Equivalent => 0,
+        Equivalence::Different(_) => 1,
+    })
+}
+
+/// Prints the logic depth and delay from every input pin to every output pin
+fn timing(args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let options = Options::parse(args)?;
+    let (data, name) = options.load_flat()?;
+    let report = data.timing()?;
+
+    if options.json {
+        let report = json!({
+            "chip": name,
+            "critical_path": report.critical(),
+            "paths": report.paths,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!("{}", name);
+        print!("{}", report);
+    }
+    Ok(0)
+}
+
+/// Prints the gate counts and cost of a chip, or of two chips side by side
+fn stats(args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let options = Options::parse_chips(args, 2)?;
+    let mut chips = vec![load_chip_file(&options.path()?)?];
+    if let Some(other) = &options.other {
+        chips.push(load_chip_file(&chip_file(other))?);
+    }
+    let mut lookup = options.chip_lookup()?;
+    let stats = chips
+        .iter()
+        .map(|chip| chip_stats(chip, &mut lookup))
+        .collect::<Result<Vec<ChipStats>, CircuitError>>()?;
+
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(0);
+    }
+    let [first, second] = stats.as_slice() else {
+        print!("{}", stats[0]);
+        return Ok(0);
+    };
+    let rows = first.compare(second);
+    let width = rows.iter().map(|(label, _, _)| label.len()).max().unwrap_or(0);
+    println!("{:width$}  {:>12}  {:>12}  {:>10}", "", first.name, second.name, "difference");
+    for (label, a, b) in rows {
+        println!("{:width$}  {:>12}  {:>12}  {:>+10}", label, a, b, b as i64 - a as i64);
+    }
+    Ok(0)
+}
+
+/// Rewrites a chip with NAND or NOR gates only, and lays it out again
+fn map(args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let options = Options::parse(args)?;
+    let target = options.target.ok_or_else(|| format!("map needs --to nand or --to nor\n{}", USAGE))?;
+    let (mut data, name) = options.load_flat()?;
+    let report = data.map_to_universal(target, true)?;
+
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&json!({ "chip": name, "mapping": report }))?);
+    } else {
+        print!("{}: {}", name, report);
+    }
+    if let Some(path) = &options.output {
+        ChipDefenition::from_live_data(&data.live_data, name).save_to_file(path)?;
+        eprintln!("Wrote {}", path);
+    }
+    Ok(0)
+}
+
+/// Builds a state machine from its diagram, saved by the designer
+fn fsm(args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let options = Options::parse(args)?;
+    let file = options.file.as_ref().ok_or_else(|| format!("no state machine given\n{}", USAGE))?;
+    let machine = StateMachine::load_from_file(file).map_err(|e| format!("could not load {}: {}", file, e))?;
+    let mut data = Data::new();
+    let report = data.build_state_machine(&machine, options.encoding.unwrap_or_default(), Pos2::ZERO)?;
+
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&json!({ "machine": machine.name, "circuit": report }))?);
+    } else {
+        print!("{}: {}", machine.name, report);
+    }
+    if let Some(path) = &options.output {
+        data.to_chip(&machine.name).save_to_file(path)?;
+        eprintln!("Wrote {}", path);
+    }
+    Ok(0)
+}
+
+/// Runs the design rule checks, fails if a problem was found (warnings are listed but pass)
+fn check(args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let options = Options::parse(args)?;
+    let (data, name) = options.load()?;
+    let (warnings, problems): (Vec<Problem>, Vec<Problem>) =
+        data.check_design().into_iter().partition(|problem| problem.kind.is_warning());
+    if options.json {
+        let list = |problems: &[Problem]| -> Vec<serde_json::Value> {
+            problems
+                .iter()
+                .map(|problem| {
+                    json!({
+                        "kind": problem.kind.to_string(),
+                        "message": problem.message,
+                        "ids": problem.ids,
+                    })
+                })
+                .collect()
+        };
+        let report = json!({ "chip": name, "problems": list(&problems), "warnings": list(&warnings) });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        if problems.is_empty() {
+            println!("{}: no problems found", name);
+        } else {
+            println!("{}: {} problem(s)", name, problems.len());
+            for problem in &problems {
+                println!("  {}", problem);
+            }
+        }
+        if !warnings.is_empty() {
+            println!("{} warning(s), these don't fail the check:", warnings.len());
+            for warning in &warnings {
+                println!("  {}", warning);
+            }
+        }
+    }
+    Ok(if problems.is_empty() { 0 } else { 1 })
+}
+
+/// Runs every given test script and returns the process exit code:
+/// 0 if all passed, 1 if any output did not match its .cmp file, 2 if a script could not be run
+fn run_test_scripts(args: &[String]) -> i32 {
+    // every other argument is a script, so only --threads is taken from the usual options
+    let mut paths = Vec::new();
+    let mut threads = 1;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg != "--threads" {
+            paths.push(arg);
+            continue;
+        }
+        match args.next().map(|value| value.parse()) {
+            Some(Ok(count)) if count > 0 => threads = count,
+            _ => {
+                eprintln!("--threads expects a thread count above 0");
+                return 2;
+            }
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("usage: Gates test <file.tst>... [--threads N]");
+        return 2;
+    }
+    let mut code = 0;
+    for path in paths {
+        match TestScript::run_file(path, threads) {
+            Ok(report) => match &report.mismatch {
+                None if !report.compared => println!("{}: ran, nothing compared ({} rows)", path, report.lines.len()),
+                None => println!("{}: passed ({} rows)", path, report.lines.len()),
+                Some(mismatch) => {
+                    println!("{}: mismatch at row {}", path, mismatch.row);
+                    println!("  expected: {}", mismatch.expected);
+                    println!("  actual:   {}", mismatch.actual);
+                    code = code.max(1);
+                }
+            },
+            Err(e) => {
+                eprintln!("{}: error: {}", path, e);
+                code = 2;
+            }
+        }
+    }
+    code
+}
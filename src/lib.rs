@@ -0,0 +1,33 @@
+//! Gates as a library: the node model, the simulator and the editor app
+//!
+//! [`Circuit`] is the stable entry point for building and running circuits from code:
+//!
+//! ```no_run
+//! use gates::{Circuit, PrimitiveKind};
+//!
+//! let mut circuit = Circuit::new();
+//! let a = circuit.add_input("a");
+//! let b = circuit.add_input("b");
+//! let and = circuit.add_gate(PrimitiveKind::AND);
+//! let out = circuit.add_output("out");
+//! circuit.wire(a, and, 0).unwrap();
+//! circuit.wire(b, and, 1).unwrap();
+//! circuit.wire(and, out, 0).unwrap();
+//!
+//! circuit.set_input("a", 1).unwrap();
+//! circuit.set_input("b", 1).unwrap();
+//! circuit.settle();
+//! assert_eq!(circuit.read_output("out").unwrap(), 1);
+//! circuit.save("and.chip", "MyAnd").unwrap();
+//! ```
+
+pub use eframe::egui;
+
+pub mod app;
+pub use app::*;
+
+pub mod node;
+pub use node::*;
+
+mod circuit;
+pub use circuit::Circuit;
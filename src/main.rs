@@ -1,25 +1,27 @@
-pub use eframe::egui;
+use gates::*;
 
-pub mod app;
-pub use app::*;
-
-pub mod node;
-pub use node::*;
+mod cli;
 
 
 fn main() -> eframe::Result {
     // env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
 
+    // `Gates <command> ...` runs headless and exits, see cli.rs for the commands
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(code) = cli::run(&args[1..]) {
+        std::process::exit(code);
+    }
+
     let native_options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([1000.0, 1000.0])
             .with_min_inner_size([1000.0, 1000.0]),
         ..Default::default()
-        
+
     };
     eframe::run_native(
         "Gates",
         native_options,
         Box::new(|cc| Ok(Box::new(MyApp::new(cc)))),
     )
-}
\ No newline at end of file
+}
@@ -1,6 +1,8 @@
+use std::path::Path;
+
 use crossbeam::channel::Sender;
 
-use crate::{gate::GridVec2, MyApp};
+use crate::gate::GridVec2;
 
 use super::*;
 
@@ -29,7 +31,7 @@ pub struct ChipDefenition {
 impl ChipDefenition{
     pub fn create_blank_chip(name: String) -> Self {
         ChipDefenition {
-            id: MyApp::next_id(),
+            id: next_id(),
             name,
             position: None,
             sub_gates: HashMap::new(),
@@ -67,7 +69,7 @@ impl ChipDefenition{
     }
 
     fn next_chip_id() -> usize {
-        MyApp::next_id()
+        next_id()
     }
 
     pub fn from_live_data(board_data: &HashMap<usize, Box<dyn Logical>>, name: String) -> Self {
@@ -116,28 +118,61 @@ impl ChipDefenition{
     }
 
 
+    /// A chip's pins are the TOGGLE gates (inputs) and LIGHT gates (outputs) on its board,
+    /// keyed by gate id with their current state
     fn get_io_from_gates(board_data: &HashMap<usize, Box<dyn Logical>>)-> (HashMap<usize, bool>, HashMap<usize, bool>) {
         let (ins, outs) = board_data.iter().fold(
             (HashMap::new(), HashMap::new()),
             |(mut ins, mut outs), (id, item)| {
-                match item.get_kind() {
-                    LogicalKind::Gate(GateKind::Primitive(PrimitiveKind::TOGGLE)) => {
-                        if let Some(input) = item.as_any().downcast_ref::<Input>() {
-                            ins.insert(*id, input.signal);
+                if let Some(gate) = item.as_any().downcast_ref::<Gate>() {
+                    match gate.kind {
+                        GateKind::Primitive(PrimitiveKind::TOGGLE) => {
+                            ins.insert(*id, gate.state);
                         }
-                    }
-                    LogicalKind::IO(IOKind::Output) => {
-                        if let Some(output) = item.as_any().downcast_ref::<Output>() {
-                            outs.insert(*id, output.signal);
+                        GateKind::Primitive(PrimitiveKind::LIGHT) => {
+                            outs.insert(*id, gate.state);
                         }
+                        _ => {}
                     }
-                    _ => {}
                 }
                 (ins, outs)
             },
         );
         (ins, outs)
     }
+
+    /// Copies every sub-item of this chip into a fresh board map, ready to be simulated
+    pub fn to_live_data(&self) -> HashMap<usize, Box<dyn Logical>> {
+        let mut board: HashMap<usize, Box<dyn Logical>> = HashMap::new();
+        for (id, gate) in &self.sub_gates {
+            board.insert(*id, Box::new(gate.clone()));
+        }
+        for (id, wire) in &self.sub_wires {
+            board.insert(*id, Box::new(wire.clone()));
+        }
+        for (id, input) in &self.sub_inputs {
+            board.insert(*id, Box::new(input.clone()));
+        }
+        for (id, output) in &self.sub_outputs {
+            board.insert(*id, Box::new(output.clone()));
+        }
+        board
+    }
+
+    /// Writes this chip to `path` in RON format
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let ron_string = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new())?;
+        std::fs::write(path, ron_string)?;
+        Ok(())
+    }
+
+    /// Reads a chip previously written with `save_to_file`
+    /// Parse errors come back as `CircuitError::FileFormat` with the line and column
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let content = std::fs::read_to_string(&path)?;
+        ron::from_str(&content)
+            .map_err(|e| CircuitError::file_format(&path.as_ref().display().to_string(), e).into())
+    }
 }
 
 
@@ -176,4 +211,8 @@ impl Logical for ChipDefenition {
     fn get_kind(&self) -> LogicalKind {
         LogicalKind::Gate(GateKind::Custom(self.name.clone()))
     }
+
+    fn remap_ids(&mut self, map: &HashMap<usize, usize>) {
+        self.id = *map.get(&self.id).unwrap_or(&self.id);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,99 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+/// Everything that can go wrong while simulating or editing a board
+/// Variants carry the id of the component that produced them, so they can be shown next to it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CircuitError {
+    /// A gate has a different number of inputs or outputs than its kind needs
+    ArityMismatch {
+        id: usize,
+        kind: String,
+        pins: &'static str, // "inputs" or "outputs"
+        expected: usize,
+        found: usize,
+    },
+    /// An input or output whose parent gate is not on the board
+    MissingParent { id: usize, parent_id: usize },
+    /// A gate lists a pin that is not on the board
+    UnconnectedPin { id: usize, pin_id: usize },
+    /// A gate input that no wire drives, it reads false
+    UnwiredInput { id: usize, input_id: usize },
+    /// An id refers to something of the wrong type
+    InvalidDowncast { id: usize, expected: &'static str },
+    /// A file that could not be read, with the position of the problem when known
+    FileFormat {
+        file: String,
+        line: Option<usize>,
+        column: Option<usize>,
+        message: String,
+    },
+    /// Something a component can't do (yet)
+    Unsupported { id: Option<usize>, message: String },
+    /// Several TOGGLE or LIGHT gates answer to the same pin name (or bus bit)
+    DuplicatePin { name: String, ids: Vec<usize> },
+}
+
+impl CircuitError {
+    /// The id of the component the error is about, if there is one
+    pub fn component_id(&self) -> Option<usize> {
+        match self {
+            CircuitError::ArityMismatch { id, .. }
+            | CircuitError::MissingParent { id, .. }
+            | CircuitError::UnconnectedPin { id, .. }
+            | CircuitError::UnwiredInput { id, .. }
+            | CircuitError::InvalidDowncast { id, .. } => Some(*id),
+            CircuitError::FileFormat { .. } => None,
+            CircuitError::Unsupported { id, .. } => *id,
+            CircuitError::DuplicatePin { ids, .. } => ids.first().copied(),
+        }
+    }
+
+    /// Turns any error returned by a `Logical` into a `CircuitError` about component `id`
+    pub fn from_boxed(id: usize, error: Box<dyn Error>) -> Self {
+        match error.downcast::<CircuitError>() {
+            Ok(error) => *error,
+            Err(error) => CircuitError::Unsupported { id: Some(id), message: error.to_string() },
+        }
+    }
+
+    pub fn file_format(file: &str, error: ron::error::SpannedError) -> Self {
+        CircuitError::FileFormat {
+            file: file.to_string(),
+            line: Some(error.position.line),
+            column: Some(error.position.col),
+            message: error.code.to_string(),
+        }
+    }
+}
+
+impl Display for CircuitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitError::ArityMismatch { id, kind, pins, expected, found } => {
+                write!(f, "{} {} has {} {} but needs {}", kind, id, found, pins, expected)
+            }
+            CircuitError::MissingParent { id, parent_id } => {
+                write!(f, "{} belongs to gate {}, which is not on the board", id, parent_id)
+            }
+            CircuitError::UnconnectedPin { id, pin_id } => {
+                write!(f, "Gate {} has pin {}, which is not on the board", id, pin_id)
+            }
+            CircuitError::UnwiredInput { id, input_id } => {
+                write!(f, "Input {} of gate {} has no wire, it reads false", input_id, id)
+            }
+            CircuitError::InvalidDowncast { id, expected } => write!(f, "{} is not a {}", id, expected),
+            CircuitError::FileFormat { file, line: Some(line), column: Some(column), message } => {
+                write!(f, "{}:{}:{}: {}", file, line, column, message)
+            }
+            CircuitError::FileFormat { file, message, .. } => write!(f, "{}: {}", file, message),
+            CircuitError::Unsupported { id: Some(id), message } => write!(f, "{}: {}", id, message),
+            CircuitError::Unsupported { id: None, message } => write!(f, "{}", message),
+            CircuitError::DuplicatePin { name, ids } => {
+                write!(f, "{} pins are named {}, give them different names", ids.len(), name)
+            }
+        }
+    }
+}
+
+impl Error for CircuitError {}
@@ -1,5 +1,5 @@
 use crate::node::io::*;
-use crate::{MyApp, UiEvent};
+use crate::UiEvent;
 
 use super::*;
 
@@ -39,7 +39,10 @@ impl Logical for Gate {
                 // For primitive gates, we can run their logic
                 k.tick(self, ins)
             }
-            _ => Err("Tick not implemented for this type".into()),
+            _ => Err(Box::new(CircuitError::Unsupported {
+                id: Some(self.id),
+                message: format!("{} can't be simulated, flatten it first", self.name),
+            })),
         }
     }
 
@@ -196,6 +199,19 @@ impl Logical for Gate {
         );
         response
     }
+
+    fn remap_ids(&mut self, map: &HashMap<usize, usize>) {
+        remap(&mut self.id, map);
+        for pins in [&mut self.ins, &mut self.outs] {
+            *pins = pins
+                .drain()
+                .map(|(mut id, signal)| {
+                    remap(&mut id, map);
+                    (id, signal)
+                })
+                .collect();
+        }
+    }
 }
 
 impl Gate {
@@ -236,7 +252,7 @@ impl Gate {
     fn from_template(t: &PrimitiveTemplate, pos: Pos2) -> Gate {
         let g = Gate {
             name: t.label.clone(),
-            id: MyApp::next_id(),
+            id: next_id(),
             position: GridVec2::new(pos.x, pos.y),
             size: GridVec2::new(150.0, 110.0),
 
@@ -252,8 +268,7 @@ impl Gate {
     }
 
     pub fn create_gate_from_template(t: GateKind, pos: Pos2) -> Gate {
-        print!("Creating gate from template ID: {:?}", t);
-        let new_gate = match t {
+        match t {
             GateKind::Primitive(PrimitiveKind::HISIGNAL) => {
                 Gate::from_template(&PrimitiveTemplate::from_values("HI-SIGNAL", 0, 1), pos)
             }
@@ -291,10 +306,7 @@ impl Gate {
                 Gate::from_template(&PrimitiveTemplate::from_values("NOR", 2, 1), pos)
             }
             _ => Gate::from_template(&PrimitiveTemplate::from_values("E: Not Found", 1, 1), pos),
-        };
-
-        println!("Created gate: {:?}", new_gate);
-        new_gate
+        }
     }
 
     pub fn create_io(&mut self, live_data: &mut HashMap<usize, Box<dyn Logical>>) {
@@ -324,7 +336,7 @@ impl Gate {
 
     pub fn generate(label: String, n_ins: usize, n_outs: usize) -> Gate {
         let kind: GateKind;
-        let id = MyApp::next_id();
+        let id = next_id();
         match label.as_str() {
             "HI-SIGNAL" => {
                 kind = GateKind::Primitive(PrimitiveKind::HISIGNAL);
@@ -0,0 +1,100 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::Logical;
+
+static GLOBAL_NEXT_ID: AtomicUsize = AtomicUsize::new(0); // used when no circuit's allocator is entered
+
+thread_local! {
+    static ENTERED: RefCell<Vec<IdAllocator>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Hands out ids for one circuit, clones share the same counter so it can be used from any thread
+#[derive(Debug, Clone, Default)]
+pub struct IdAllocator {
+    next: Arc<AtomicUsize>,
+}
+
+impl IdAllocator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn next(&self) -> usize {
+        self.next.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Makes sure `id` is never handed out, for ids that came from a file
+    pub fn reserve(&self, id: usize) {
+        self.next.fetch_max(id + 1, Ordering::Relaxed);
+    }
+
+    /// Starts counting again right after the given ids, for when the circuit is replaced
+    pub fn reset_past<I: IntoIterator<Item = usize>>(&self, ids: I) {
+        let next = ids.into_iter().max().map_or(0, |id| id + 1);
+        self.next.store(next, Ordering::Relaxed);
+    }
+
+    /// Until the returned guard is dropped, `next_id()` on this thread takes ids from this allocator
+    /// This is how nodes created deep inside constructors end up with ids of the right circuit
+    pub fn enter(&self) -> EnteredIds {
+        ENTERED.with(|entered| entered.borrow_mut().push(self.clone()));
+        EnteredIds { _private: () }
+    }
+}
+
+/// Guard returned by `IdAllocator::enter`
+pub struct EnteredIds {
+    _private: (),
+}
+
+impl Drop for EnteredIds {
+    fn drop(&mut self) {
+        ENTERED.with(|entered| entered.borrow_mut().pop());
+    }
+}
+
+/// Hands out a new id for a gate, wire, input, output or chip
+/// Comes from the allocator of the circuit being edited, or a process wide counter if none is entered
+pub fn next_id() -> usize {
+    ENTERED
+        .with(|entered| entered.borrow().last().map(IdAllocator::next))
+        .unwrap_or_else(|| GLOBAL_NEXT_ID.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Replaces `id` with its new id, ids missing from `map` are left alone
+pub fn remap(id: &mut usize, map: &HashMap<usize, usize>) {
+    if let Some(new) = map.get(id) {
+        *id = *new;
+    }
+}
+
+/// Like `remap` for references that may be unset
+pub fn remap_opt(id: &mut Option<usize>, map: &HashMap<usize, usize>) {
+    if let Some(id) = id {
+        remap(id, map);
+    }
+}
+
+/// Gives every item a new id from `ids` and rewrites all references between them
+/// References to ids outside of `items` are left alone
+/// Returns the mapping from old to new ids
+pub fn remap_ids(
+    items: HashMap<usize, Box<dyn Logical>>,
+    ids: &IdAllocator,
+) -> (HashMap<usize, Box<dyn Logical>>, HashMap<usize, usize>) {
+    let mut old_ids: Vec<usize> = items.keys().copied().collect();
+    old_ids.sort(); // keep the new ids in the same order as the old ones
+    let map: HashMap<usize, usize> = old_ids.into_iter().map(|old| (old, ids.next())).collect();
+
+    let remapped = items
+        .into_iter()
+        .map(|(old, mut item)| {
+            item.remap_ids(&map);
+            (map[&old], item)
+        })
+        .collect();
+    (remapped, map)
+}
@@ -19,7 +19,7 @@ pub struct Input {
 
 impl Input {
     pub fn new(parent_id: usize, index: usize) -> Self {
-        let n = MyApp::next_id();
+        let n = next_id();
         Input {
             id: n,
             index,
@@ -86,15 +86,16 @@ impl Input {
                             println!("Before: {:?}", item.get_kind());
                         }
                     }
-                    Err(Box::new(InvalidOperationError(
-                        "Parent could not be downcast to a Gate".to_string(),
-                    )))
+                    Err(Box::new(CircuitError::InvalidDowncast { id: p_id, expected: "Gate" }))
                 }
             } else {
-                Err(Box::new(InvalidOperationError("Parent gate does not exist on this input, Operation is not allowed, implement wall mounted inputs".into())))
+                Err(Box::new(CircuitError::MissingParent { id: self.id, parent_id: p_id }))
             }
         } else {
-            Err(Box::new(InvalidOperationError("Parent gate does not exist on this output, Operation is not allowed, implement wall mounted outputs".into())))
+            Err(Box::new(CircuitError::Unsupported {
+                id: Some(self.id),
+                message: "Input has no parent gate, wall mounted inputs are not implemented".to_string(),
+            }))
         }
     }
 }
@@ -182,4 +183,10 @@ impl Logical for Input {
         })
         .response
     }
+
+    fn remap_ids(&mut self, map: &HashMap<usize, usize>) {
+        remap(&mut self.id, map);
+        remap_opt(&mut self.parent_id, map);
+        remap_opt(&mut self.source_wire_id, map);
+    }
 }
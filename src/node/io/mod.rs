@@ -5,7 +5,7 @@ mod input;
 pub use input::Input;
 use serde::{Deserialize, Serialize};
 
-pub use crate::{ MyApp, node::InvalidOperationError, UiEvent};
+pub use crate::{node::InvalidOperationError, UiEvent};
 
 pub use eframe::egui::{Align, PointerButton};
 
@@ -19,7 +19,7 @@ pub struct Output {
 
 impl Output {
     pub fn new(parent_id: usize, index: usize) -> Self {
-        let n = MyApp::next_id();
+        let n = next_id();
         Output {
             id: n,
             parent_id: Some(parent_id),
@@ -86,16 +86,16 @@ impl Output {
                             println!("Before: {:?}", item.get_kind());
                         }
                     }
-                    Err(Box::new(InvalidOperationError(
-                        "Parent gate could not be downcast to Gate, Operation is not allowed"
-                            .to_string(),
-                    )))
+                    Err(Box::new(CircuitError::InvalidDowncast { id: p_id, expected: "Gate" }))
                 }
             } else {
-                Err(Box::new(InvalidOperationError("Parent gate does not exist on this input, Operation is not allowed, implement wall mounted inputs".into())))
+                Err(Box::new(CircuitError::MissingParent { id: self.id, parent_id: p_id }))
             }
         } else {
-            Err(Box::new(InvalidOperationError("Parent gate does not exist on this output, Operation is not allowed, implement wall mounted outputs".into())))
+            Err(Box::new(CircuitError::Unsupported {
+                id: Some(self.id),
+                message: "Output has no parent gate, wall mounted outputs are not implemented".to_string(),
+            }))
         }
     }
 }
@@ -191,4 +191,12 @@ impl Logical for Output {
         })
         .response
     }
+
+    fn remap_ids(&mut self, map: &HashMap<usize, usize>) {
+        remap(&mut self.id, map);
+        remap_opt(&mut self.parent_id, map);
+        for id in &mut self.out_wire_ids {
+            remap(id, map);
+        }
+    }
 }
@@ -36,6 +36,9 @@ pub trait Logical: AsAny {
         println!("Click on not implemented for this type");
     }    
 
+    /// Rewrites this item's own id and the ids it refers to, ids missing from the map stay as they are
+    fn remap_ids(&mut self, _map: &HashMap<usize, usize>) {}
+
 }
 
 // Define a trait to allow downcasting
@@ -16,6 +16,12 @@ pub use io::{IOKind, Input, Io, Output};
 mod chip;
 pub use chip::{ChipDefenition};
 
+mod error;
+pub use error::CircuitError;
+
+mod id;
+pub use id::{EnteredIds, IdAllocator, next_id, remap, remap_ids, remap_opt};
+
 pub use super::app::UiEvent;
 pub use eframe::egui::{
     Button, Color32, Direction, Layout, Pos2, Response, Sense, Ui, Widget, vec2,
@@ -27,12 +33,11 @@ use std::fmt::{Display, Formatter};
 use std::hash::Hash;
 
 use std::error::Error;
-
 const LINE_THICKNESS: f32 = 3.0;
 
-const HI_SIGNAL_COLOR: &str = "color-success-500";
+pub const HI_SIGNAL_COLOR: &str = "color-success-500";
 const HI_ACCENT_COLOR: &str = "color-success-900";
 
 
-const LO_SIGNAL_COLOR: &str = "color-error-500";
+pub const LO_SIGNAL_COLOR: &str = "color-error-500";
 const LO_ACCENT_COLOR: &str = "color-error-900";
\ No newline at end of file
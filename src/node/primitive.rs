@@ -102,103 +102,106 @@ impl PrimitiveKind {
         ins: HashMap<usize, bool>,
     ) -> Result<HashMap<usize, bool>, Box<dyn Error>> {
         // println!("Ticking primitive type: {}", self);
+        let signals: Vec<bool> = ins.into_values().collect();
+        let result = self.evaluate(gate.id, &mut gate.state, &signals, gate.outs.len())?;
 
-        if ins.len() > self.get_n_desired_inputs() {
-            return Err(format!("{} requires exactly {} or less inputs", self, self.get_n_desired_inputs()).into());
+        // the value goes to the (only) output, LIGHT has none
+        Ok(match (result, gate.outs.keys().next()) {
+            (Some(signal), Some(out_id)) => HashMap::from([(*out_id, signal)]),
+            _ => HashMap::new(),
+        })
+    }
+
+    /// The logic of one gate: updates its state and returns the value for its output (None for LIGHT)
+    /// `n_outs` is the number of outputs the gate has, every kind but LIGHT needs one
+    /// Shared by `tick` and the compiled netlist so both simulate exactly the same way
+    pub fn evaluate(&self, id: usize, state: &mut bool, ins: &[bool], n_outs: usize) -> Result<Option<bool>, CircuitError> {
+        self.check_arity(id, ins.len(), n_outs)?;
+        // a bool is a lane that is all ones or all zeros, no primitive takes more than 2 inputs (checked above)
+        let lane = |signal: bool| if signal { u64::MAX } else { 0 };
+        let mut wide_ins = [0u64; 2];
+        for (wide, &signal) in wide_ins.iter_mut().zip(ins) {
+            *wide = lane(signal);
         }
+        let mut wide_state = lane(*state);
+        let result = self.evaluate_wide(id, &mut wide_state, &wide_ins[..ins.len()], n_outs)?;
+        *state = wide_state & 1 == 1;
+        Ok(result.map(|signal| signal & 1 == 1))
+    }
 
-        match self {
+    /// `evaluate` for 64 independent simulations at once, bit n of every value belongs to simulation n
+    pub fn evaluate_wide(&self, id: usize, state: &mut u64, ins: &[u64], n_outs: usize) -> Result<Option<u64>, CircuitError> {
+        self.check_arity(id, ins.len(), n_outs)?;
+        let first = || {
+            ins.first().copied().ok_or_else(|| CircuitError::ArityMismatch {
+                id,
+                kind: self.to_string(),
+                pins: "inputs",
+                expected: 1,
+                found: 0,
+            })
+        };
+
+        let result = match self {
+            //HI-SIGNAL always outputs true
             PrimitiveKind::HISIGNAL => {
-                //HI-SIGNAL always outputs true
-                // println!("Ticking HISIGNAL, setting output to true");
-                let (out_id, _) = gate.outs.iter().next().unwrap();
-                gate.state = true; // Set gate state to true
-                Ok(HashMap::from([(*out_id, true)]))
+                *state = u64::MAX;
+                u64::MAX
             }
+            // LO-SIGNAL always outputs false
             PrimitiveKind::LOSIGNAL => {
-                // LO-SIGNAL always outputs false
-                let (out_id, _) = gate.outs.iter().next().unwrap();
-                gate.state = false; // Set gate state to false
-                Ok(HashMap::from([(*out_id, false)]))
-            }
-            PrimitiveKind::BUFFER => {
-                // BUFFER logic, for example, passes the signal through
-                //ensure only one input
-                let (out_id, _) = gate.outs.iter().next().unwrap(); //get the  id of the (only) output
-                if let Some((_, signal)) = ins.iter().next() {
-                    Ok(HashMap::from([(*out_id, *signal)])) // Assuming single output at index 0
-                } else {
-                    return Err("Input signal not found".into());
-                }
+                *state = 0;
+                0
             }
+            // BUFFER passes the signal through
+            PrimitiveKind::BUFFER => first()?,
             PrimitiveKind::LIGHT => {
-                gate.state = ins.values().next().cloned().unwrap_or(false); // Set gate state based on input
-                Ok(HashMap::new())// No output, just update state
-            }
-            PrimitiveKind::PULSE => {
-                // 1-Tick pulse creator, on rising edge of any pulse (or click) it will send a true signal for one tick
-                // and then set self state to false
-                let (out_id, _) = gate.outs.iter().next().expect("PULSE was ticked but did not have an output"); //get the  id of the (only) output
-                if gate.state {//set state to false and send a true signal
-                    gate.state = false;
-                    Ok(HashMap::from([(*out_id, true)])) // Assuming single output at index 0
-                } else {
-                    //send a false signal
-                    Ok(HashMap::from([(*out_id, false)])) // Assuming single output at index 0
-                }
-            }
-            PrimitiveKind::TOGGLE => {
-                // pretty much the same as PULSE but doesnt handle its own state, instead state is handled externally by user input
-                let (out_id, _) = gate.outs.iter().next().expect("TOGGLE was ticked but did not have an output");
-                Ok(HashMap::from([(*out_id, gate.state)])) // Assuming single output at index 0
+                *state = ins.first().copied().unwrap_or(0); // Set gate state based on input
+                return Ok(None); // No output, just update state
+            }
+            // 1-Tick pulse creator, on rising edge of any pulse (or click) it will send a true signal for one tick
+            // and then set self state to false
+            PrimitiveKind::PULSE => std::mem::replace(state, 0),
+            // pretty much the same as PULSE but doesnt handle its own state, instead state is handled externally by user input
+            PrimitiveKind::TOGGLE => *state,
+            // NOT inverts the input signal
+            PrimitiveKind::NOT => !first()?,
+            PrimitiveKind::OR | PrimitiveKind::AND | PrimitiveKind::XOR | PrimitiveKind::NAND | PrimitiveKind::NOR => {
+                let any = ins.iter().fold(0, |acc, v| acc | v);
+                let all = ins.iter().fold(u64::MAX, |acc, v| acc & v);
+                let result = match self {
+                    PrimitiveKind::OR => any,
+                    PrimitiveKind::AND => all,
+                    PrimitiveKind::XOR => ins.iter().fold(0, |acc, v| acc ^ v), // true if an odd number of inputs are true
+                    PrimitiveKind::NAND => !all,
+                    _ => !any,
+                };
+                *state = result; // Set gate state based on input
+                result
+            }
+            PrimitiveKind::None => {
+                return Err(CircuitError::Unsupported { id: Some(id), message: format!("{} can't be simulated", self) });
             }
+        };
+        Ok(Some(result))
+    }
 
-            PrimitiveKind::NOT => {
-                // NOT logic, inverts the input signal
-                let (out_id, _) = gate.outs.iter().next().expect("NOT was ticked but did not have an output"); //get the  id of the (only) output
-                if let Some((_, signal)) = ins.iter().next() {
-                    Ok(HashMap::from([(*out_id, !signal)])) // Assuming single output at index 0
-                } else {
-                    return Err("Input signal not found".into());
-                }
-            }
-            PrimitiveKind::OR => {
-                // OR logic, returns true if any input is true
-                let (out_id, _) = gate.outs.iter().next().expect("OR was ticked but did not have an output"); //get the  id of the (only) output
-                let result = ins.values().any(|&v| v);
-                gate.state = result; // Set gate state based on input
-                Ok(HashMap::from([(*out_id, result)])) // Assuming single output at index 0
-            }
-            PrimitiveKind::AND => {
-                // AND logic, returns true if all inputs are true
-                let (out_id, _) = gate.outs.iter().next().expect("AND was ticked but did not have an output"); //get the  id of the (only) output
-                let result = ins.values().all(|&v| v);
-                gate.state = result; // Set gate state based on input
-                Ok(HashMap::from([(*out_id, result)])) // Assuming single output at index 0
-            }
-            PrimitiveKind::XOR => {
-                // XOR logic, returns true if an odd number of inputs are true
-                let (out_id, _) = gate.outs.iter().next().expect("XOR was ticked but did not have an output"); //get the  id of the (only) output
-                let result = ins.values().filter(|&&v| v).count() % 2 == 1;
-                gate.state = result; // Set gate state based on input
-                Ok(HashMap::from([(*out_id, result)])) // Assuming single output at index 0
-            }
-            PrimitiveKind::NAND => {
-                // NAND logic, returns true if not all inputs are true
-                let (out_id, _) = gate.outs.iter().next().expect("NAND was ticked but did not have an output"); //get the  id of the (only) output
-                let result = !ins.values().all(|&v| v);
-                gate.state = result; // Set gate state based on input
-                Ok(HashMap::from([(*out_id, result)])) // Assuming single output at index 0
-            }
-            PrimitiveKind::NOR => {
-                // NOR logic, returns true if all inputs are false
-                let (out_id, _) = gate.outs.iter().next().expect("NOR was ticked but did not have an output"); //get the  id of the (only) output
-                let result = !ins.values().any(|&v| v);
-                gate.state = result; // Set gate state based on input
-                Ok(HashMap::from([(*out_id, result)])) // Assuming single output at index 0
-            }
-            _ => Err(Box::new(InvalidOperationError("Could not determine primitive type".to_string()))), // Other types not implemented yet
+    /// Too many inputs, or no output on a kind that drives one
+    fn check_arity(&self, id: usize, n_ins: usize, n_outs: usize) -> Result<(), CircuitError> {
+        let arity = |pins: &'static str, expected: usize, found: usize| CircuitError::ArityMismatch {
+            id,
+            kind: self.to_string(),
+            pins,
+            expected,
+            found,
+        };
+        if n_ins > self.get_n_desired_inputs() {
+            return Err(arity("inputs", self.get_n_desired_inputs(), n_ins));
+        }
+        if !matches!(self, PrimitiveKind::LIGHT | PrimitiveKind::None) && n_outs == 0 {
+            return Err(arity("outputs", 1, 0));
         }
+        Ok(())
     }
 
     pub fn get_gate_kind(&self) -> GateKind {
@@ -207,7 +210,7 @@ impl PrimitiveKind {
     pub fn get_logical_kind(&self) ->LogicalKind{
         LogicalKind::Gate(self.get_gate_kind())
     }
-    fn get_n_desired_inputs(&self) -> usize {
+    pub fn get_n_desired_inputs(&self) -> usize {
         match self {
             PrimitiveKind::HISIGNAL | PrimitiveKind::LOSIGNAL | PrimitiveKind::PULSE | PrimitiveKind::TOGGLE => 0,
             PrimitiveKind::LIGHT => 1,
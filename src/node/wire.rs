@@ -1,18 +1,37 @@
 use super::*;
 
-use crate::{MyApp, UiEvent};
+use crate::UiEvent;
 use crossbeam::channel::Sender;
-use eframe::egui::{Rect, Stroke};
+use eframe::egui::{Rect, Stroke, Vec2};
 
 #[derive(serde::Deserialize, serde::Serialize, Default, Clone, Debug)]
 pub struct WireLine {
     pub p1: Pos2,
     pub p2: Pos2,
     smoothing: bool,
+
+    #[serde(default)]
+    pub waypoints: Vec<Pos2>, // bend points between p1 and p2, in world space
+    #[serde(skip)]
+    screen_waypoints: Vec<Pos2>, // waypoints moved into screen space, updated with p1 and p2
 }
 impl WireLine {
     pub fn new(p1: Pos2, p2: Pos2, smoothing: bool) -> Self {
-        WireLine { p1, p2, smoothing }
+        WireLine {
+            p1,
+            p2,
+            smoothing,
+            waypoints: Vec::new(),
+            screen_waypoints: Vec::new(),
+        }
+    }
+
+    /// All points of the line in screen space, from p1 through every waypoint to p2
+    pub fn points(&self) -> Vec<Pos2> {
+        let mut points = vec![self.p1];
+        points.extend(self.screen_waypoints.iter().cloned());
+        points.push(self.p2);
+        points
     }
 }
 
@@ -42,7 +61,7 @@ pub struct Wire {
 impl Wire {
     fn new(source_id: usize, position: Pos2, smoothing: bool) -> Self {
         Wire {
-            id: MyApp::next_id(),
+            id: next_id(),
             signal: false,
             source_id,
             dest: None,
@@ -57,6 +76,11 @@ impl Wire {
         self.line.p2 = p2;
     }
 
+    /// Moves the world space waypoints into screen space for the current pan position
+    pub fn set_pan_offset(&mut self, pan: Vec2) {
+        self.line.screen_waypoints = self.line.waypoints.iter().map(|p| *p - pan).collect();
+    }
+
     pub fn delete(&mut self) {
         self.connected = false;
         self.dest = None;
@@ -67,6 +91,10 @@ impl Wire {
         self.signal = signal;
     }
 
+    pub fn get_signal(&self) -> bool {
+        self.signal
+    }
+
     pub fn set_p1(&mut self, p1: Pos2) {
         self.line.p1 = p1;
     }
@@ -155,19 +183,29 @@ impl Logical for Wire {
         //if wire is connected, update the line's end points to be the current source -> destination positions
         if self.connected {}
         // Draw the wire line
-        ui.painter().line_segment(
-            [self.line.p1, self.line.p2],
-            Stroke::new(LINE_THICKNESS, color),
-        );
+        if self.line.screen_waypoints.is_empty() {
+            ui.painter().line_segment(
+                [self.line.p1, self.line.p2],
+                Stroke::new(LINE_THICKNESS, color),
+            );
+        } else {
+            ui.painter().line(self.line.points(), Stroke::new(LINE_THICKNESS, color));
+        }
 
         response
     }
+
+    fn remap_ids(&mut self, map: &HashMap<usize, usize>) {
+        remap(&mut self.id, map);
+        remap(&mut self.source_id, map);
+        remap_opt(&mut self.dest, map);
+    }
 }
 
 impl Default for Wire {
     fn default() -> Self {
         Wire {
-            id: MyApp::next_id(),
+            id: next_id(),
             source_id: 0,
             signal: false,
             dest: None,